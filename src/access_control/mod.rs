@@ -0,0 +1,141 @@
+use super::api_type::AccessError;
+use super::ic_util;
+use super::migration::{self, MigrationError, MigrationReport, MigrationStep};
+use candid::{CandidType, Deserialize, Principal};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+pub type PreUpgradeStableData<'a> = (&'a u8, &'a AccessList);
+pub type PostUpgradeStableData = (u8, AccessList);
+
+// `MIGRATIONS[i]` upgrades the access list of version `i + 1` to `i + 2`.
+const MIGRATIONS: [MigrationStep<AccessList>; 0] = [];
+const VERSION: u8 = MIGRATIONS.len() as u8 + 1;
+
+/// Principals allowed to call the canistergeek API.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub struct AccessList {
+    principals: BTreeSet<Principal>,
+    allow_controllers: bool,
+}
+
+impl Default for AccessList {
+    fn default() -> Self {
+        Self {
+            principals: BTreeSet::new(),
+            allow_controllers: true,
+        }
+    }
+}
+
+impl AccessList {
+    pub fn is_allowed(&self, principal: &Principal) -> bool {
+        self.principals.contains(principal)
+            || (self.allow_controllers && ic_util::is_controller(principal))
+    }
+}
+
+thread_local! {
+    static ACCESS_LIST: RefCell<AccessList> = RefCell::new(AccessList::default());
+}
+
+/// Allows the principal to call the canistergeek API, returns `false` if it is already allowed.
+pub fn add_allowed_principal(principal: Principal) -> bool {
+    ACCESS_LIST.with(|list| list.borrow_mut().principals.insert(principal))
+}
+
+/// Returns `false` if the principal has not been allowed.
+pub fn remove_allowed_principal(principal: &Principal) -> bool {
+    ACCESS_LIST.with(|list| list.borrow_mut().principals.remove(principal))
+}
+
+pub fn get_allowed_principals() -> Vec<Principal> {
+    ACCESS_LIST.with(|list| list.borrow().principals.iter().cloned().collect())
+}
+
+/// Allows canister controllers besides the allowed principals (enabled by default).
+pub fn set_allow_controllers(allow_controllers: bool) {
+    ACCESS_LIST.with(|list| list.borrow_mut().allow_controllers = allow_controllers);
+}
+
+pub fn is_allow_controllers() -> bool {
+    ACCESS_LIST.with(|list| list.borrow().allow_controllers)
+}
+
+/// Checks that the caller of the current message is allowed.
+pub fn check_caller() -> Result<(), AccessError> {
+    let caller = ic_util::get_caller();
+    if ACCESS_LIST.with(|list| list.borrow().is_allowed(&caller)) {
+        Ok(())
+    } else {
+        Err(AccessError::unauthorizedCaller(caller))
+    }
+}
+
+/// Returns a copy of the allowlist.
+pub fn pre_upgrade_stable_data() -> PostUpgradeStableData {
+    (VERSION, ACCESS_LIST.with(|list| list.borrow().clone()))
+}
+
+/// Like `pre_upgrade_stable_data`, but runs the function with the allowlist borrowed.
+pub fn with_pre_upgrade_stable_data<R>(f: impl FnOnce(PreUpgradeStableData<'_>) -> R) -> R {
+    ACCESS_LIST.with(|list| f((&VERSION, &list.borrow())))
+}
+
+/// Nothing is restored on error, trap to roll the upgrade back and keep the allowlist.
+pub fn post_upgrade_stable_data(
+    (version, list): PostUpgradeStableData,
+) -> Result<MigrationReport, MigrationError> {
+    let (list, report) = migration::migrate("access control", version, list, &MIGRATIONS)?;
+    ACCESS_LIST.with(|cell| *cell.borrow_mut() = list);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::{Decode, Encode};
+
+    #[test]
+    fn test_access_list() {
+        let user = Principal::from_slice(&[1]);
+        let controller = Principal::from_slice(&[2]);
+        ic_util::set_fake_controllers(vec![controller]);
+
+        let mut list = AccessList::default();
+        assert!(list.is_allowed(&controller));
+        assert!(!list.is_allowed(&user));
+        assert!(!list.is_allowed(&Principal::anonymous()));
+
+        list.principals.insert(user);
+        list.allow_controllers = false;
+        assert!(list.is_allowed(&user));
+        assert!(!list.is_allowed(&controller));
+
+        // survives the upgrade
+        ACCESS_LIST.with(|cell| *cell.borrow_mut() = list.clone());
+        let bytes = with_pre_upgrade_stable_data(|data| candid::encode_one(data)).unwrap();
+        let restored: PostUpgradeStableData = candid::decode_one(&bytes).unwrap();
+        assert_eq!(restored, (VERSION, list));
+
+        // upgrade data saved without the access list
+        let bytes = Encode!(&VERSION).unwrap();
+        let (_, restored) = Decode!(&bytes, u8, Option<PostUpgradeStableData>).unwrap();
+        assert_eq!(restored, None);
+    }
+
+    #[test]
+    fn test_check_caller() {
+        let user = Principal::from_slice(&[3]);
+        ic_util::set_fake_caller(user);
+        assert_eq!(check_caller(), Err(AccessError::unauthorizedCaller(user)));
+
+        assert!(add_allowed_principal(user));
+        assert!(!add_allowed_principal(user));
+        assert_eq!(check_caller(), Ok(()));
+        assert_eq!(get_allowed_principals(), vec![user]);
+
+        assert!(remove_allowed_principal(&user));
+        assert!(check_caller().is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use super::super::api_type::{AlertEvent, AlertEventKind, LogLevel, Nanos};
+use std::collections::VecDeque;
+
+const HOUR_NANOS: u64 = 60 * 60 * 1_000_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlertCondition {
+    CyclesBelow(u64),
+    HeapMemorySizeAbove(u64),
+    MemorySizeAbove(u64),
+    UpdateCallsPerIntervalAbove(u64),
+    ErrorLogMessagesPerHourAbove(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlertRule {
+    pub name: String,
+    pub condition: AlertCondition,
+}
+
+/// Values the metrics rules are checked against.
+pub struct MetricsSample {
+    pub cycles: u64,
+    pub heap_memory_size: u64,
+    pub memory_size: u64,
+    pub update_calls_in_interval: u64,
+}
+
+struct RuleState {
+    rule: AlertRule,
+    firing: bool,
+    // times of the error messages logged during the last hour
+    error_times: VecDeque<Nanos>,
+}
+
+/// Checks alert rules and keeps the bounded history of fired and resolved alerts.
+pub struct Alerts {
+    rules: Vec<RuleState>,
+    history: VecDeque<AlertEvent>,
+    history_capacity: usize,
+}
+
+impl Alerts {
+    pub fn new(history_capacity: usize) -> Self {
+        Self {
+            rules: Vec::new(),
+            history: VecDeque::new(),
+            history_capacity,
+        }
+    }
+
+    /// Replaces the rules, all of them are considered resolved.
+    pub fn set_rules(&mut self, rules: Vec<AlertRule>) -> Result<(), &'static str> {
+        for (index, rule) in rules.iter().enumerate() {
+            if rules[..index].iter().any(|other| other.name == rule.name) {
+                return Err("Duplicate alert rule name");
+            }
+        }
+        self.rules = rules
+            .into_iter()
+            .map(|rule| RuleState {
+                rule,
+                firing: false,
+                error_times: VecDeque::new(),
+            })
+            .collect();
+        Ok(())
+    }
+
+    pub fn has_rules(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    pub fn set_history_capacity(&mut self, history_capacity: usize) {
+        self.history_capacity = history_capacity;
+        self.truncate_history();
+    }
+
+    /// Checks all rules against the collected metrics, returns fired and resolved alerts.
+    pub fn on_metrics(&mut self, time_nanos: Nanos, sample: &MetricsSample) -> Vec<AlertEvent> {
+        self.check_rules(time_nanos, |state| match state.rule.condition {
+            AlertCondition::CyclesBelow(limit) => Some((sample.cycles < limit, sample.cycles)),
+            AlertCondition::HeapMemorySizeAbove(limit) => {
+                Some((sample.heap_memory_size > limit, sample.heap_memory_size))
+            }
+            AlertCondition::MemorySizeAbove(limit) => {
+                Some((sample.memory_size > limit, sample.memory_size))
+            }
+            AlertCondition::UpdateCallsPerIntervalAbove(limit) => Some((
+                sample.update_calls_in_interval > limit,
+                sample.update_calls_in_interval,
+            )),
+            AlertCondition::ErrorLogMessagesPerHourAbove(limit) => {
+                Some(count_error_messages(state, time_nanos, limit))
+            }
+        })
+    }
+
+    /// Checks the log rules when a message is logged, returns fired and resolved alerts.
+    pub fn on_log_message(&mut self, time_nanos: Nanos, level: LogLevel) -> Vec<AlertEvent> {
+        self.check_rules(time_nanos, |state| match state.rule.condition {
+            AlertCondition::ErrorLogMessagesPerHourAbove(limit) => {
+                if level == LogLevel::error {
+                    state.error_times.push_back(time_nanos);
+                }
+                Some(count_error_messages(state, time_nanos, limit))
+            }
+            _ => None,
+        })
+    }
+
+    pub fn get_history(&self, from_time_nanos: Option<Nanos>) -> Vec<AlertEvent> {
+        self.history
+            .iter()
+            .filter(|event| event.timeNanos >= from_time_nanos.unwrap_or(0))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_firing_rules(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter(|state| state.firing)
+            .map(|state| state.rule.name.clone())
+            .collect()
+    }
+
+    fn check_rules<F>(&mut self, time_nanos: Nanos, mut check: F) -> Vec<AlertEvent>
+    where
+        F: FnMut(&mut RuleState) -> Option<(bool, u64)>,
+    {
+        let mut events = Vec::new();
+        for state in self.rules.iter_mut() {
+            if let Some((firing, value)) = check(state) {
+                if firing != state.firing {
+                    state.firing = firing;
+                    events.push(AlertEvent {
+                        timeNanos: time_nanos,
+                        rule: state.rule.name.clone(),
+                        kind: if firing {
+                            AlertEventKind::fired
+                        } else {
+                            AlertEventKind::resolved
+                        },
+                        value,
+                    });
+                }
+            }
+        }
+        self.history.extend(events.iter().cloned());
+        self.truncate_history();
+        events
+    }
+
+    fn truncate_history(&mut self) {
+        while self.history.len() > self.history_capacity {
+            self.history.pop_front();
+        }
+    }
+}
+
+/// Drops messages older than an hour, only `limit + 1` latest messages are needed to fire the rule.
+fn count_error_messages(state: &mut RuleState, time_nanos: Nanos, limit: u64) -> (bool, u64) {
+    while state.error_times.front().is_some_and(|time| {
+        *time + HOUR_NANOS <= time_nanos || state.error_times.len() as u64 > limit + 1
+    }) {
+        state.error_times.pop_front();
+    }
+    let count = state.error_times.len() as u64;
+    (count > limit, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(cycles: u64, update_calls_in_interval: u64) -> MetricsSample {
+        MetricsSample {
+            cycles,
+            heap_memory_size: 100,
+            memory_size: 200,
+            update_calls_in_interval,
+        }
+    }
+
+    #[test]
+    fn test_metrics_rules() {
+        let mut alerts = Alerts::new(2);
+        assert!(alerts
+            .set_rules(vec![
+                AlertRule {
+                    name: "cycles".to_string(),
+                    condition: AlertCondition::CyclesBelow(1000),
+                },
+                AlertRule {
+                    name: "cycles".to_string(),
+                    condition: AlertCondition::MemorySizeAbove(1000),
+                },
+            ])
+            .is_err());
+        alerts
+            .set_rules(vec![
+                AlertRule {
+                    name: "cycles".to_string(),
+                    condition: AlertCondition::CyclesBelow(1000),
+                },
+                AlertRule {
+                    name: "calls".to_string(),
+                    condition: AlertCondition::UpdateCallsPerIntervalAbove(2),
+                },
+            ])
+            .unwrap();
+
+        assert!(alerts.on_metrics(1, &sample(2000, 1)).is_empty());
+        let events = alerts.on_metrics(2, &sample(900, 3));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].rule, "cycles");
+        assert_eq!(events[0].kind, AlertEventKind::fired);
+        assert_eq!(events[0].value, 900);
+        assert_eq!(alerts.get_firing_rules(), vec!["cycles", "calls"]);
+
+        // still firing
+        assert!(alerts.on_metrics(3, &sample(800, 4)).is_empty());
+
+        let events = alerts.on_metrics(4, &sample(5000, 4));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, AlertEventKind::resolved);
+        assert_eq!(alerts.get_firing_rules(), vec!["calls"]);
+
+        // bounded history
+        let history = alerts.get_history(None);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].rule, "calls");
+        assert_eq!(alerts.get_history(Some(3)).len(), 1);
+    }
+
+    #[test]
+    fn test_error_log_messages_rule() {
+        let mut alerts = Alerts::new(10);
+        alerts
+            .set_rules(vec![AlertRule {
+                name: "errors".to_string(),
+                condition: AlertCondition::ErrorLogMessagesPerHourAbove(2),
+            }])
+            .unwrap();
+
+        assert!(alerts.on_log_message(1, LogLevel::error).is_empty());
+        assert!(alerts.on_log_message(2, LogLevel::warn).is_empty());
+        assert!(alerts.on_log_message(3, LogLevel::error).is_empty());
+        let events = alerts.on_log_message(4, LogLevel::error);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, AlertEventKind::fired);
+        assert_eq!(events[0].value, 3);
+        assert!(alerts.on_log_message(5, LogLevel::error).is_empty());
+
+        // resolved by the metrics collection after an hour
+        let events = alerts.on_metrics(HOUR_NANOS + 4, &sample(2000, 1));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, AlertEventKind::resolved);
+        assert_eq!(events[0].value, 1);
+    }
+}
@@ -0,0 +1,58 @@
+mod evaluator;
+
+use super::api_type::{AlertEvent, AlertsRequest, AlertsResponse};
+use super::instance;
+pub use evaluator::{AlertCondition, AlertRule};
+pub(crate) use evaluator::{Alerts, MetricsSample};
+use std::cell::RefCell;
+
+pub(crate) const DEFAULT_HISTORY_CAPACITY: usize = 100;
+
+pub type AlertCallback = Box<dyn Fn(&AlertEvent)>;
+
+thread_local! {
+    static CALLBACK: RefCell<Option<AlertCallback>> = const { RefCell::new(None) };
+}
+
+/// Replaces the alert rules, rule names have to be unique.
+///
+/// Rules, their state and the alert history are not persisted,
+/// so the rules should be set in `init` and `post_upgrade`.
+pub fn set_alert_rules(rules: Vec<AlertRule>) -> Result<(), &'static str> {
+    instance::with_mut(|canistergeek| canistergeek.set_alert_rules(rules))
+}
+
+/// Sets how many fired and resolved alerts are kept, the oldest ones are dropped first.
+pub fn set_alert_history_capacity(history_capacity: usize) {
+    instance::with_mut(|canistergeek| canistergeek.set_alert_history_capacity(history_capacity));
+}
+
+/// Sets the function called for every fired and resolved alert.
+pub fn set_alert_callback(callback: AlertCallback) {
+    CALLBACK.with(|cell| *cell.borrow_mut() = Some(callback));
+}
+
+pub fn clear_alert_callback() {
+    CALLBACK.with(|cell| *cell.borrow_mut() = None);
+}
+
+// callbacks are invoked after the evaluation, so they may log or collect metrics
+pub(crate) fn notify(events: &[AlertEvent]) {
+    if events.is_empty() {
+        return;
+    }
+    CALLBACK.with(|cell| {
+        if let Some(callback) = cell.borrow().as_ref() {
+            for event in events {
+                callback(event);
+            }
+        }
+    });
+}
+
+pub(crate) fn get_alerts(alerts: &Alerts, request: AlertsRequest) -> AlertsResponse {
+    AlertsResponse {
+        events: alerts.get_history(request.fromTimeNanos),
+        firingRules: alerts.get_firing_rules(),
+    }
+}
@@ -1,5 +1,6 @@
 use candid::{CandidType, Deserialize};
 use serde::Serialize;
+use std::borrow::Cow;
 
 #[allow(non_snake_case)]
 #[derive(Debug, CandidType, Deserialize)]
@@ -8,6 +9,8 @@ pub struct GetInformationRequest {
     pub status: Option<StatusRequest>,
     pub metrics: Option<MetricsRequest>,
     pub logs: Option<CanisterLogRequest>,
+    pub cyclesForecast: Option<CyclesForecastRequest>,
+    pub alerts: Option<AlertsRequest>,
 }
 
 #[allow(non_snake_case)]
@@ -17,6 +20,69 @@ pub struct GetInformationResponse<'a> {
     pub status: Option<StatusResponse>,
     pub metrics: Option<MetricsResponse<'a>>,
     pub logs: Option<CanisterLogResponse<'a>>,
+    pub cyclesForecast: Option<CyclesForecastResponse>,
+    pub alerts: Option<AlertsResponse>,
+    /// Set when the metrics request is rejected.
+    pub metricsError: Option<InformationError>,
+    /// Set when the logs request is rejected.
+    pub logsError: Option<InformationError>,
+}
+
+impl GetInformationResponse<'_> {
+    /// Copies the data borrowed from the storages, so the response can outlive them.
+    pub fn into_owned(self) -> GetInformationResponse<'static> {
+        GetInformationResponse {
+            version: self.version,
+            status: self.status,
+            metrics: self.metrics.map(|response| MetricsResponse {
+                metrics: response.metrics.map(CanisterMetrics::into_owned),
+            }),
+            logs: self.logs.map(CanisterLogResponse::into_owned),
+            cyclesForecast: self.cyclesForecast,
+            alerts: self.alerts,
+            metricsError: self.metricsError,
+            logsError: self.logsError,
+        }
+    }
+}
+
+/// Reason of a rejected `getCanistergeekInformation` section.
+#[allow(non_camel_case_types, non_snake_case)]
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub enum InformationError {
+    invalidCount { maxCount: u32 },
+    invalidDateRange,
+    unsupportedFilter(String),
+    invalidRegex(String),
+    tooManyDays { maxDays: u32 },
+    unknownChannel(String),
+    expressionTooLarge { maxDepth: u32, maxNodes: u32 },
+}
+
+impl std::fmt::Display for InformationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InformationError::invalidCount { maxCount } => {
+                write!(f, "Count should be from 1 to {}", maxCount)
+            }
+            InformationError::invalidDateRange => write!(f, "Invalid date range"),
+            InformationError::unsupportedFilter(reason) => {
+                write!(f, "Unsupported filter: {}", reason)
+            }
+            InformationError::invalidRegex(reason) => write!(f, "Invalid regex: {}", reason),
+            InformationError::tooManyDays { maxDays } => {
+                write!(f, "Date range should not exceed {} days", maxDays)
+            }
+            InformationError::unknownChannel(channel) => {
+                write!(f, "Unknown log channel: {}", channel)
+            }
+            InformationError::expressionTooLarge { maxDepth, maxNodes } => write!(
+                f,
+                "Filter expression should not exceed {} levels and {} nodes",
+                maxDepth, maxNodes
+            ),
+        }
+    }
 }
 
 #[allow(non_snake_case)]
@@ -47,6 +113,65 @@ pub struct MetricsResponse<'a> {
     pub metrics: Option<CanisterMetrics<'a>>,
 }
 
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType, Deserialize)]
+pub struct CyclesForecastRequest {
+    /// Days (including today) analyzed, 7 by default.
+    pub periodDays: Option<u32>,
+    /// Runway is calculated till the balance reaches it.
+    pub freezingThresholdCycles: Option<u64>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType)]
+pub struct CyclesForecastResponse {
+    /// None if there are less than two cycles samples.
+    pub forecast: Option<CyclesForecast>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType)]
+pub struct CyclesForecast {
+    pub fromTimeMillis: candid::Int,
+    pub toTimeMillis: candid::Int,
+    pub cycles: u64,
+    pub burnedCycles: u64,
+    pub depositedCycles: u64,
+    pub burnedCyclesPerDay: u64,
+    pub runwayDays: Option<u64>,
+    pub freezeTimeMillis: Option<candid::Int>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType, Deserialize)]
+pub struct AlertsRequest {
+    pub fromTimeNanos: Option<Nanos>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType)]
+pub struct AlertsResponse {
+    pub events: Vec<AlertEvent>,
+    pub firingRules: Vec<String>,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, CandidType, Clone, Copy, PartialEq, Eq)]
+pub enum AlertEventKind {
+    fired,
+    resolved,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType, Clone, PartialEq, Eq)]
+pub struct AlertEvent {
+    pub timeNanos: Nanos,
+    pub rule: String,
+    pub kind: AlertEventKind,
+    /// The value which fired or resolved the rule.
+    pub value: u64,
+}
+
 #[allow(non_snake_case)]
 #[derive(Debug, CandidType, Deserialize)]
 pub struct UpdateInformationRequest {
@@ -83,6 +208,22 @@ pub struct CanisterMetrics<'a> {
     pub data: CanisterMetricsData<'a>,
 }
 
+impl CanisterMetrics<'_> {
+    /// Copies the data borrowed from the storage.
+    pub fn into_owned(self) -> CanisterMetrics<'static> {
+        CanisterMetrics {
+            data: match self.data {
+                CanisterMetricsData::daily(data) => CanisterMetricsData::daily(data),
+                CanisterMetricsData::hourly(data) => CanisterMetricsData::hourly(
+                    data.into_iter()
+                        .map(HourlyMetricsData::into_owned)
+                        .collect(),
+                ),
+            },
+        }
+    }
+}
+
 #[allow(non_camel_case_types)]
 #[derive(Debug, CandidType)]
 pub enum CanisterMetricsData<'a> {
@@ -98,6 +239,48 @@ pub struct DailyMetricsData {
     pub canisterMemorySize: NumericEntity,
     pub timeMillis: candid::Int,
     pub updateCalls: u64,
+    pub customMetrics: Option<Vec<DailyCustomMetricData>>,
+    pub methodCalls: Option<Vec<DailyMethodCallsData>>,
+    pub callInstructions: Option<Vec<DailyCallInstructionsData>>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType)]
+pub struct DailyCallInstructionsData {
+    pub name: String,
+    pub calls: u64,
+    pub totalInstructions: u64,
+    pub maxInstructions: u64,
+    pub meanInstructions: u64,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType)]
+pub struct DailyMethodCallsData {
+    pub name: String,
+    pub updateCalls: u64,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, CandidType, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum CustomMetricKind {
+    /// Values of an interval are summed up.
+    counter,
+    /// The last value of an interval is kept.
+    gauge,
+}
+
+#[derive(Debug, CandidType)]
+pub struct DailyCustomMetricData {
+    pub name: String,
+    pub value: DailyCustomMetricValue,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, CandidType)]
+pub enum DailyCustomMetricValue {
+    counter(u64),
+    gauge(NumericEntity),
 }
 
 #[derive(Debug, CandidType)]
@@ -117,17 +300,103 @@ pub struct HourlyMetricsData<'a> {
     pub canisterHeapMemorySize: CanisterHeapMemoryAggregatedData<'a>,
     pub canisterMemorySize: CanisterMemoryAggregatedData<'a>,
     pub updateCalls: UpdateCallsAggregatedData<'a>,
+    pub customMetrics: Option<Vec<HourlyCustomMetricData<'a>>>,
+    pub methodCalls: Option<Vec<HourlyMethodCallsData<'a>>>,
+    pub callInstructions: Option<Vec<HourlyCallInstructionsData<'a>>>,
+}
+
+impl HourlyMetricsData<'_> {
+    fn into_owned(self) -> HourlyMetricsData<'static> {
+        HourlyMetricsData {
+            timeMillis: self.timeMillis,
+            canisterCycles: into_owned(self.canisterCycles),
+            canisterHeapMemorySize: into_owned(self.canisterHeapMemorySize),
+            canisterMemorySize: into_owned(self.canisterMemorySize),
+            updateCalls: into_owned(self.updateCalls),
+            customMetrics: self.customMetrics.map(|metrics| {
+                metrics
+                    .into_iter()
+                    .map(|metric| HourlyCustomMetricData {
+                        name: into_owned(metric.name),
+                        kind: metric.kind,
+                        values: into_owned(metric.values),
+                    })
+                    .collect()
+            }),
+            methodCalls: self.methodCalls.map(|methods| {
+                methods
+                    .into_iter()
+                    .map(|method| HourlyMethodCallsData {
+                        name: into_owned(method.name),
+                        updateCalls: into_owned(method.updateCalls),
+                    })
+                    .collect()
+            }),
+            callInstructions: self.callInstructions.map(|methods| {
+                methods
+                    .into_iter()
+                    .map(|method| HourlyCallInstructionsData {
+                        name: into_owned(method.name),
+                        calls: into_owned(method.calls),
+                        totalInstructions: into_owned(method.totalInstructions),
+                        maxInstructions: into_owned(method.maxInstructions),
+                        meanInstructions: method.meanInstructions,
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+fn into_owned<T: ToOwned + ?Sized + 'static>(value: Cow<'_, T>) -> Cow<'static, T> {
+    Cow::Owned(value.into_owned())
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType)]
+pub struct HourlyCallInstructionsData<'a> {
+    pub name: Cow<'a, str>,
+    pub calls: Cow<'a, [u64]>,
+    pub totalInstructions: Cow<'a, [u64]>,
+    pub maxInstructions: Cow<'a, [u64]>,
+    pub meanInstructions: Vec<u64>,
 }
 
-pub type CanisterCyclesAggregatedData<'a> = &'a Vec<u64>;
-pub type CanisterMemoryAggregatedData<'a> = &'a Vec<u64>;
-pub type CanisterHeapMemoryAggregatedData<'a> = &'a Vec<u64>;
-pub type UpdateCallsAggregatedData<'a> = &'a Vec<u64>;
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType)]
+pub struct HourlyMethodCallsData<'a> {
+    pub name: Cow<'a, str>,
+    pub updateCalls: UpdateCallsAggregatedData<'a>,
+}
+
+#[derive(Debug, CandidType)]
+pub struct HourlyCustomMetricData<'a> {
+    pub name: Cow<'a, str>,
+    pub kind: CustomMetricKind,
+    pub values: Cow<'a, [u64]>,
+}
+
+pub type CanisterCyclesAggregatedData<'a> = Cow<'a, [u64]>;
+pub type CanisterMemoryAggregatedData<'a> = Cow<'a, [u64]>;
+pub type CanisterHeapMemoryAggregatedData<'a> = Cow<'a, [u64]>;
+pub type UpdateCallsAggregatedData<'a> = Cow<'a, [u64]>;
 
 // LOG messages
 
 pub type Nanos = u64;
 
+#[allow(non_camel_case_types)]
+#[derive(
+    Debug, CandidType, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum LogLevel {
+    trace,
+    debug,
+    info,
+    warn,
+    error,
+}
+
 #[allow(non_camel_case_types)]
 #[derive(Debug, CandidType, Deserialize)]
 pub enum CanisterLogRequest {
@@ -143,12 +412,50 @@ pub enum CanisterLogResponse<'a> {
     messages(CanisterLogMessages<'a>),
 }
 
+impl CanisterLogResponse<'_> {
+    /// Copies the messages borrowed from the storage.
+    pub fn into_owned(self) -> CanisterLogResponse<'static> {
+        match self {
+            CanisterLogResponse::messagesInfo(info) => CanisterLogResponse::messagesInfo(info),
+            CanisterLogResponse::messages(messages) => {
+                CanisterLogResponse::messages(CanisterLogMessages {
+                    data: messages.data.into_iter().map(into_owned).collect(),
+                    lastAnalyzedMessageTimeNanos: messages.lastAnalyzedMessageTimeNanos,
+                })
+            }
+        }
+    }
+}
+
 #[allow(non_snake_case)]
 #[derive(Debug, CandidType, Deserialize)]
 pub struct GetLogMessagesFilter {
     pub analyzeCount: u32,
     pub messageContains: Option<String>,
     pub messageRegex: Option<String>,
+    pub minLevel: Option<LogLevel>,
+    pub fieldsEqual: Option<Vec<LogField>>,
+    pub expression: Option<LogFilterExpression>,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, CandidType, Deserialize)]
+pub enum LogFilterExpression {
+    and(Vec<LogFilterExpression>),
+    or(Vec<LogFilterExpression>),
+    not(Box<LogFilterExpression>),
+    messageContains(String),
+    messageRegex(String),
+    minLevel(LogLevel),
+    timeRange(LogTimeRange),
+    fieldEquals(LogField),
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType, Deserialize)]
+pub struct LogTimeRange {
+    pub fromTimeNanos: Option<Nanos>,
+    pub toTimeNanos: Option<Nanos>,
 }
 
 #[allow(non_snake_case)]
@@ -157,6 +464,8 @@ pub struct GetLogMessagesParameters {
     pub count: u32,
     pub filter: Option<GetLogMessagesFilter>,
     pub fromTimeNanos: Option<Nanos>,
+    /// None for the default channel.
+    pub channel: Option<String>,
 }
 
 #[allow(non_snake_case)]
@@ -165,20 +474,48 @@ pub struct GetLatestLogMessagesParameters {
     pub count: u32,
     pub filter: Option<GetLogMessagesFilter>,
     pub upToTimeNanos: Option<Nanos>,
+    /// None for the default channel.
+    pub channel: Option<String>,
 }
 
 #[allow(non_snake_case)]
 #[derive(Debug, CandidType)]
 pub struct CanisterLogMessages<'a> {
-    pub data: Vec<&'a LogMessageData>,
+    pub data: Vec<Cow<'a, LogMessageData>>,
     pub lastAnalyzedMessageTimeNanos: Option<Nanos>,
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, CandidType, Deserialize, Clone, Serialize)]
+#[derive(Debug, CandidType, Deserialize, Clone, Serialize, Default)]
 pub struct LogMessageData {
     pub timeNanos: Nanos,
     pub message: String,
+    pub level: Option<LogLevel>,
+    pub fields: Option<Vec<LogField>>,
+    /// None for messages stored before truncation was marked.
+    pub truncated: Option<bool>,
+    /// Length in bytes of the truncated message.
+    pub originalLength: Option<u64>,
+    /// Number of collapsed identical messages, none for a single one.
+    pub repeatCount: Option<u32>,
+    /// Time of the last collapsed message.
+    pub lastTimeNanos: Option<Nanos>,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, CandidType, Deserialize, Clone, Serialize, PartialEq)]
+pub enum LogFieldValue {
+    text(String),
+    nat(candid::Nat),
+    int(candid::Int),
+    principal(candid::Principal),
+    bool(bool),
+}
+
+#[derive(Debug, CandidType, Deserialize, Clone, Serialize, PartialEq)]
+pub struct LogField {
+    pub name: String,
+    pub value: LogFieldValue,
 }
 
 #[allow(non_camel_case_types)]
@@ -186,6 +523,9 @@ pub struct LogMessageData {
 pub enum CanisterLogFeature {
     filterMessageByContains,
     filterMessageByRegex,
+    filterMessageByLevel,
+    filterMessageByFields,
+    filterMessageByExpression,
 }
 
 #[allow(non_snake_case)]
@@ -195,4 +535,52 @@ pub struct CanisterLogMessagesInfo {
     pub features: Vec<Option<CanisterLogFeature>>,
     pub firstTimeNanos: Option<Nanos>,
     pub lastTimeNanos: Option<Nanos>,
+    pub usedBytes: Option<u64>,
+    pub maxBytes: Option<u64>,
+    pub compressionRatio: Option<f64>,
+    /// None without rate limits.
+    pub droppedMessages: Option<Vec<DroppedLogMessages>>,
+    /// Named channels, fields above describe the default one.
+    pub channels: Option<Vec<LogChannelInfo>>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq)]
+pub struct LogChannelInfo {
+    pub name: String,
+    pub count: u32,
+    pub maxCount: u32,
+    pub firstTimeNanos: Option<Nanos>,
+    pub lastTimeNanos: Option<Nanos>,
+}
+
+/// Messages of the source dropped by its rate limit.
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq)]
+pub struct DroppedLogMessages {
+    pub source: String,
+    pub count: u64,
+}
+
+pub type HeaderField = (String, String);
+
+/// Request of the canister `http_request` query served by the HTTP gateway.
+#[derive(Debug, CandidType, Deserialize, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<HeaderField>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<HeaderField>,
+    pub body: Vec<u8>,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, CandidType, Deserialize, Clone, PartialEq, Eq)]
+pub enum AccessError {
+    unauthorizedCaller(candid::Principal),
 }
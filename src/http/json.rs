@@ -0,0 +1,214 @@
+use super::super::api_type::{
+    CanisterLogMessages, CanisterLogRequest, CanisterLogResponse, CanisterMetricsData,
+    DailyCustomMetricValue, GetLatestLogMessagesParameters, GetLogMessagesFilter,
+    GetMetricsParameters, LogFieldValue, LogLevel, LogMessageData, MetricsGranularity,
+    NumericEntity,
+};
+use super::super::ic_util;
+use super::super::instance::Canistergeek;
+use super::query::get_parameter;
+use num_traits::ToPrimitive;
+use serde_json::{json, Value};
+
+pub const CONTENT_TYPE: &str = "application/json";
+
+const DEFAULT_LOG_MESSAGES_COUNT: u32 = 100;
+const DEFAULT_ANALYZE_COUNT: u32 = 10_000;
+
+/// Serves `/canistergeek/metrics?from=..&to=..&granularity=..`,
+/// times are in milliseconds, `to` defaults to now and `from` to `to`.
+pub fn get_metrics(
+    canistergeek: &Canistergeek,
+    parameters: &[(String, String)],
+) -> Result<Value, String> {
+    let to = match get_parameter(parameters, "to") {
+        Some(to) => parse_number::<u64>("to", to)?,
+        None => ic_util::get_ic_time_nanos() / 1_000_000,
+    };
+    let from = match get_parameter(parameters, "from") {
+        Some(from) => parse_number::<u64>("from", from)?,
+        None => to,
+    };
+    let granularity = match get_parameter(parameters, "granularity") {
+        None | Some("hourly") => MetricsGranularity::hourly,
+        Some("daily") => MetricsGranularity::daily,
+        Some(_) => return Err("granularity should be hourly or daily".to_string()),
+    };
+
+    let metrics = canistergeek
+        .monitor()
+        .try_get_metrics(&GetMetricsParameters {
+            granularity,
+            dateFromMillis: candid::Nat::from(from),
+            dateToMillis: candid::Nat::from(to),
+        })
+        .map_err(|error| error.to_string())?;
+
+    Ok(match metrics.data {
+        CanisterMetricsData::hourly(data) => json!({
+            "granularity": "hourly",
+            "data": data.iter().map(|hour| json!({
+                "timeMillis": hour.timeMillis.0.to_i64(),
+                "updateCalls": hour.updateCalls,
+                "canisterCycles": hour.canisterCycles,
+                "canisterMemorySize": hour.canisterMemorySize,
+                "canisterHeapMemorySize": hour.canisterHeapMemorySize,
+                "customMetrics": hour.customMetrics.iter().flatten().map(|metric| json!({
+                    "name": metric.name,
+                    "kind": format!("{:?}", metric.kind),
+                    "values": metric.values,
+                })).collect::<Vec<_>>(),
+                "methodCalls": hour.methodCalls.iter().flatten().map(|method| json!({
+                    "name": method.name,
+                    "updateCalls": method.updateCalls,
+                })).collect::<Vec<_>>(),
+                "callInstructions": hour.callInstructions.iter().flatten().map(|method| json!({
+                    "name": method.name,
+                    "calls": method.calls,
+                    "totalInstructions": method.totalInstructions,
+                    "maxInstructions": method.maxInstructions,
+                    "meanInstructions": method.meanInstructions,
+                })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+        }),
+        CanisterMetricsData::daily(data) => json!({
+            "granularity": "daily",
+            "data": data.iter().map(|day| json!({
+                "timeMillis": day.timeMillis.0.to_i64(),
+                "updateCalls": day.updateCalls,
+                "canisterCycles": numeric_entity(&day.canisterCycles),
+                "canisterMemorySize": numeric_entity(&day.canisterMemorySize),
+                "canisterHeapMemorySize": numeric_entity(&day.canisterHeapMemorySize),
+                "customMetrics": day.customMetrics.iter().flatten().map(|metric| match &metric.value {
+                    DailyCustomMetricValue::counter(value) => json!({
+                        "name": metric.name,
+                        "kind": "counter",
+                        "value": value,
+                    }),
+                    DailyCustomMetricValue::gauge(value) => json!({
+                        "name": metric.name,
+                        "kind": "gauge",
+                        "value": numeric_entity(value),
+                    }),
+                }).collect::<Vec<_>>(),
+                "methodCalls": day.methodCalls.iter().flatten().map(|method| json!({
+                    "name": method.name,
+                    "updateCalls": method.updateCalls,
+                })).collect::<Vec<_>>(),
+                "callInstructions": day.callInstructions.iter().flatten().map(|method| json!({
+                    "name": method.name,
+                    "calls": method.calls,
+                    "totalInstructions": method.totalInstructions,
+                    "maxInstructions": method.maxInstructions,
+                    "meanInstructions": method.meanInstructions,
+                })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+        }),
+    })
+}
+
+/// Serves `/canistergeek/logs?count=..&contains=..&level=..&upTo=..&analyze=..&channel=..`,
+/// returns the latest messages first.
+pub fn get_logs(
+    canistergeek: &Canistergeek,
+    parameters: &[(String, String)],
+) -> Result<Value, String> {
+    let count = match get_parameter(parameters, "count") {
+        Some(count) => parse_number::<u32>("count", count)?,
+        None => DEFAULT_LOG_MESSAGES_COUNT,
+    };
+    let up_to_time_nanos = get_parameter(parameters, "upTo")
+        .map(|up_to| parse_number::<u64>("upTo", up_to))
+        .transpose()?;
+    let analyze_count = match get_parameter(parameters, "analyze") {
+        Some(analyze) => parse_number::<u32>("analyze", analyze)?,
+        None => DEFAULT_ANALYZE_COUNT,
+    };
+    let min_level = get_parameter(parameters, "level")
+        .map(parse_log_level)
+        .transpose()?;
+    let contains = get_parameter(parameters, "contains").map(str::to_string);
+
+    let filter = if contains.is_some() || min_level.is_some() {
+        Some(GetLogMessagesFilter {
+            analyzeCount: analyze_count,
+            messageContains: contains,
+            messageRegex: None,
+            minLevel: min_level,
+            fieldsEqual: None,
+            expression: None,
+        })
+    } else {
+        None
+    };
+    let request = CanisterLogRequest::getLatestMessages(GetLatestLogMessagesParameters {
+        count,
+        filter,
+        upToTimeNanos: up_to_time_nanos,
+        channel: get_parameter(parameters, "channel").map(str::to_string),
+    });
+
+    match canistergeek.logger().try_get_canister_log(request) {
+        Ok(CanisterLogResponse::messages(messages)) => Ok(log_messages(&messages)),
+        Ok(_) => Err("Unexpected log response".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+fn log_messages(messages: &CanisterLogMessages) -> Value {
+    json!({
+        "data": messages.data.iter().map(|message| log_message(message)).collect::<Vec<_>>(),
+        // nanos exceed the safe integer range of JSON numbers in JS, so they are sent as strings
+        "lastAnalyzedMessageTimeNanos": messages.lastAnalyzedMessageTimeNanos.map(|time| time.to_string()),
+    })
+}
+
+fn log_message(message: &LogMessageData) -> Value {
+    json!({
+        "timeNanos": message.timeNanos.to_string(),
+        "timeMillis": message.timeNanos / 1_000_000,
+        "level": message.level.map(|level| format!("{:?}", level)),
+        "message": message.message,
+        "truncated": message.truncated,
+        "originalLength": message.originalLength,
+        "repeatCount": message.repeatCount,
+        "lastTimeNanos": message.lastTimeNanos.map(|time| time.to_string()),
+        "fields": message.fields.iter().flatten().map(|field| json!({
+            "name": field.name,
+            "value": match &field.value {
+                LogFieldValue::text(value) => json!(value),
+                LogFieldValue::nat(value) => json!(value.0.to_string()),
+                LogFieldValue::int(value) => json!(value.0.to_string()),
+                LogFieldValue::principal(value) => json!(value.to_text()),
+                LogFieldValue::bool(value) => json!(value),
+            },
+        })).collect::<Vec<_>>(),
+    })
+}
+
+fn numeric_entity(entity: &NumericEntity) -> Value {
+    json!({
+        "avg": entity.avg,
+        "first": entity.first,
+        "last": entity.last,
+        "max": entity.max,
+        "min": entity.min,
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| std::format!("{} should be a number", name))
+}
+
+fn parse_log_level(level: &str) -> Result<LogLevel, String> {
+    match level {
+        "trace" => Ok(LogLevel::trace),
+        "debug" => Ok(LogLevel::debug),
+        "info" => Ok(LogLevel::info),
+        "warn" => Ok(LogLevel::warn),
+        "error" => Ok(LogLevel::error),
+        _ => Err("level should be trace, debug, info, warn or error".to_string()),
+    }
+}
@@ -0,0 +1,188 @@
+#[cfg(feature = "json")]
+mod json;
+mod prometheus;
+mod query;
+
+use super::api_type::{HttpRequest, HttpResponse, StatusRequest};
+use super::instance::{self, Canistergeek};
+use super::monitor;
+
+pub const PROMETHEUS_PATH: &str = "/canistergeek/prometheus";
+#[cfg(feature = "json")]
+pub const METRICS_PATH: &str = "/canistergeek/metrics";
+#[cfg(feature = "json")]
+pub const LOGS_PATH: &str = "/canistergeek/logs";
+
+// content type and body of the response or the error message
+type RenderResult = Result<(&'static str, Vec<u8>), String>;
+type Render = fn(&Canistergeek, &[(String, String)]) -> RenderResult;
+
+/// Renders the current status, values of the latest collected interval
+/// and logger counts in the Prometheus text exposition format.
+pub fn get_prometheus_metrics() -> String {
+    instance::with(render_prometheus_metrics)
+}
+
+fn render_prometheus_metrics(canistergeek: &Canistergeek) -> String {
+    let status = monitor::get_status(StatusRequest {
+        cycles: true,
+        memory_size: true,
+        heap_memory_size: true,
+    });
+    prometheus::render(
+        &status,
+        canistergeek.monitor().get_latest_interval().as_ref(),
+        &canistergeek.logger().get_log_messages_info(),
+        &canistergeek.logger().get_log_level_counts(),
+    )
+}
+
+/// Serves canistergeek paths of the canister `http_request` query,
+/// returns `None` for other paths so the canister can handle them itself.
+///
+/// JSON paths `/canistergeek/metrics` and `/canistergeek/logs` are served with the `json` feature,
+/// the logs path only after `set_json_logs_enabled(true)`.
+///
+/// ```ignore
+/// #[ic_cdk_macros::query]
+/// fn http_request(request: HttpRequest) -> HttpResponse {
+///     canistergeek_ic_rust::http::http_request(&request).unwrap_or_else(not_found)
+/// }
+/// ```
+pub fn http_request(request: &HttpRequest) -> Option<HttpResponse> {
+    instance::with(|canistergeek| serve(canistergeek, request))
+}
+
+/// Serves the log messages at `/canistergeek/logs` (disabled by default). HTTP gateway requests
+/// are anonymous and bypass `access_control`, so the messages become public.
+/// The setting is not persisted, so set it in `init` and `post_upgrade` hooks.
+#[cfg(feature = "json")]
+pub fn set_json_logs_enabled(enabled: bool) {
+    instance::with_mut(|canistergeek| canistergeek.set_json_logs_enabled(enabled));
+}
+
+#[cfg(feature = "json")]
+pub fn is_json_logs_enabled() -> bool {
+    instance::with(Canistergeek::is_json_logs_enabled)
+}
+
+pub(crate) fn serve(canistergeek: &Canistergeek, request: &HttpRequest) -> Option<HttpResponse> {
+    let (path, parameters) = query::parse_url(&request.url);
+    let render: Render = match path {
+        PROMETHEUS_PATH => |canistergeek, _| {
+            Ok((
+                prometheus::CONTENT_TYPE,
+                render_prometheus_metrics(canistergeek).into_bytes(),
+            ))
+        },
+        #[cfg(feature = "json")]
+        METRICS_PATH => |canistergeek, parameters| {
+            json::get_metrics(canistergeek, parameters)
+                .map(|value| (json::CONTENT_TYPE, value.to_string().into_bytes()))
+        },
+        #[cfg(feature = "json")]
+        LOGS_PATH if canistergeek.is_json_logs_enabled() => |canistergeek, parameters| {
+            json::get_logs(canistergeek, parameters)
+                .map(|value| (json::CONTENT_TYPE, value.to_string().into_bytes()))
+        },
+        _ => return None,
+    };
+    if request.method != "GET" {
+        return Some(text_response(405, "Method Not Allowed".to_string()));
+    }
+    Some(match render(canistergeek, &parameters) {
+        Ok((content_type, body)) => HttpResponse {
+            status_code: 200,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body,
+        },
+        Err(error) => text_response(400, error),
+    })
+}
+
+fn text_response(status_code: u16, text: String) -> HttpResponse {
+    HttpResponse {
+        status_code,
+        headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+        body: text.into_bytes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, url: &str) -> HttpRequest {
+        HttpRequest {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    #[test]
+    fn test_http_request() {
+        assert_eq!(http_request(&request("GET", "/index.html")), None);
+        assert_eq!(
+            http_request(&request("POST", PROMETHEUS_PATH))
+                .unwrap()
+                .status_code,
+            405
+        );
+
+        let response = http_request(&request("GET", "/canistergeek/prometheus?x=1")).unwrap();
+        assert_eq!(response.status_code, 200);
+        let text = String::from_utf8(response.body).unwrap();
+        assert!(text.contains("canistergeek_cycles 0\n"));
+        assert!(text.contains("# TYPE canistergeek_log_messages gauge\n"));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_requests() {
+        // logs are not served by default
+        assert!(!is_json_logs_enabled());
+        assert_eq!(http_request(&request("GET", LOGS_PATH)), None);
+        set_json_logs_enabled(true);
+
+        crate::logger::log_with_fields(
+            crate::api_type::LogLevel::warn,
+            "json transfer failed".to_string(),
+            vec![crate::api_type::LogField {
+                name: "amount".to_string(),
+                value: crate::api_type::LogFieldValue::nat(candid::Nat::from(1_000_000_u64)),
+            }],
+        );
+
+        let response = http_request(&request(
+            "GET",
+            "/canistergeek/logs?count=1&contains=json+transfer&level=warn",
+        ))
+        .unwrap();
+        assert_eq!(response.status_code, 200);
+        let value: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        let message = &value["data"][0];
+        assert_eq!(message["message"], "json transfer failed");
+        assert_eq!(message["level"], "warn");
+        assert_eq!(message["fields"][0]["value"], "1000000");
+        assert!(message["timeNanos"].is_string());
+
+        let response = http_request(&request("GET", "/canistergeek/logs?level=fatal")).unwrap();
+        assert_eq!(response.status_code, 400);
+
+        let response = http_request(&request(
+            "GET",
+            "/canistergeek/metrics?from=1700000000000&to=1700000000000&granularity=daily",
+        ))
+        .unwrap();
+        assert_eq!(response.status_code, 200);
+        let value: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(value["granularity"], "daily");
+        assert!(value["data"].is_array());
+
+        let response =
+            http_request(&request("GET", "/canistergeek/metrics?granularity=weekly")).unwrap();
+        assert_eq!(response.status_code, 400);
+    }
+}
@@ -0,0 +1,249 @@
+use super::super::api_type::{CanisterLogMessagesInfo, LogLevel, StatusResponse};
+use super::super::monitor::calculator::latest_interval::LatestInterval;
+use std::fmt::Write;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Writes metric families in the Prometheus text exposition format.
+struct PrometheusWriter {
+    text: String,
+}
+
+impl PrometheusWriter {
+    fn new() -> Self {
+        Self {
+            text: String::new(),
+        }
+    }
+
+    fn family(&mut self, name: &str, help: &str) {
+        writeln!(self.text, "# HELP {} {}", name, help).unwrap();
+        writeln!(self.text, "# TYPE {} gauge", name).unwrap();
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: u64) {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            self.text.push('{');
+            for (index, (label, label_value)) in labels.iter().enumerate() {
+                if index > 0 {
+                    self.text.push(',');
+                }
+                write!(
+                    self.text,
+                    "{}=\"{}\"",
+                    label,
+                    escape_label_value(label_value)
+                )
+                .unwrap();
+            }
+            self.text.push('}');
+        }
+        writeln!(self.text, " {}", value).unwrap();
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: u64) {
+        self.family(name, help);
+        self.sample(name, &[], value);
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Renders the current status, values of the latest collected interval and logger counts.
+pub fn render(
+    status: &StatusResponse,
+    latest_interval: Option<&LatestInterval>,
+    log_messages_info: &CanisterLogMessagesInfo,
+    log_level_counts: &[(LogLevel, u32)],
+) -> String {
+    let mut writer = PrometheusWriter::new();
+
+    if let Some(cycles) = status.cycles {
+        writer.gauge("canistergeek_cycles", "Current cycles balance.", cycles);
+    }
+    if let Some(memory_size) = status.memory_size {
+        writer.gauge(
+            "canistergeek_memory_size_bytes",
+            "Current canister memory size.",
+            memory_size,
+        );
+    }
+    if let Some(heap_memory_size) = status.heap_memory_size {
+        writer.gauge(
+            "canistergeek_heap_memory_size_bytes",
+            "Current canister heap memory size.",
+            heap_memory_size,
+        );
+    }
+
+    if let Some(interval) = latest_interval {
+        writer.gauge(
+            "canistergeek_interval_start_timestamp_seconds",
+            "Start of the latest interval with collected metrics.",
+            interval.start_time_nanos / 1_000_000_000,
+        );
+        writer.gauge(
+            "canistergeek_interval_length_seconds",
+            "Length of the latest interval.",
+            interval.interval_in_seconds,
+        );
+        writer.gauge(
+            "canistergeek_interval_update_calls",
+            "Update calls counted in the latest interval.",
+            interval.update_calls,
+        );
+        writer.gauge(
+            "canistergeek_interval_cycles",
+            "Cycles balance collected in the latest interval.",
+            interval.cycles,
+        );
+        writer.gauge(
+            "canistergeek_interval_memory_size_bytes",
+            "Canister memory size collected in the latest interval.",
+            interval.memory_size,
+        );
+        writer.gauge(
+            "canistergeek_interval_heap_memory_size_bytes",
+            "Canister heap memory size collected in the latest interval.",
+            interval.heap_memory_size,
+        );
+        if !interval.custom_metrics.is_empty() {
+            writer.family(
+                "canistergeek_interval_custom_metric",
+                "Application metric values of the latest interval.",
+            );
+            for (name, kind, value) in interval.custom_metrics.iter() {
+                writer.sample(
+                    "canistergeek_interval_custom_metric",
+                    &[("name", name), ("kind", &format!("{:?}", kind))],
+                    *value,
+                );
+            }
+        }
+        if !interval.method_calls.is_empty() {
+            writer.family(
+                "canistergeek_interval_method_calls",
+                "Update calls of the method counted in the latest interval.",
+            );
+            for (name, value) in interval.method_calls.iter() {
+                writer.sample(
+                    "canistergeek_interval_method_calls",
+                    &[("method", name)],
+                    *value,
+                );
+            }
+        }
+    }
+
+    writer.family("canistergeek_log_messages", "Stored log messages by level.");
+    for (level, count) in log_level_counts {
+        writer.sample(
+            "canistergeek_log_messages",
+            &[("level", &format!("{:?}", level))],
+            *count as u64,
+        );
+    }
+    if let Some(last_time_nanos) = log_messages_info.lastTimeNanos {
+        writer.gauge(
+            "canistergeek_log_last_message_timestamp_seconds",
+            "Time of the latest stored log message.",
+            last_time_nanos / 1_000_000_000,
+        );
+    }
+    if let Some(dropped_messages) = &log_messages_info.droppedMessages {
+        writer.family(
+            "canistergeek_log_dropped_messages",
+            "Log messages dropped by the rate limit of the source.",
+        );
+        for dropped in dropped_messages {
+            writer.sample(
+                "canistergeek_log_dropped_messages",
+                &[("source", &dropped.source)],
+                dropped.count,
+            );
+        }
+    }
+    if let Some(used_bytes) = log_messages_info.usedBytes {
+        writer.gauge(
+            "canistergeek_log_messages_bytes",
+            "Size of the stored log messages.",
+            used_bytes,
+        );
+    }
+    if let Some(max_bytes) = log_messages_info.maxBytes {
+        writer.gauge(
+            "canistergeek_log_messages_max_bytes",
+            "Size limit of the stored log messages.",
+            max_bytes,
+        );
+    }
+
+    writer.text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_type::{CustomMetricKind, DroppedLogMessages};
+
+    #[test]
+    fn test_render() {
+        let status = StatusResponse {
+            cycles: Some(1000),
+            memory_size: Some(200),
+            heap_memory_size: None,
+        };
+        let interval = LatestInterval {
+            start_time_nanos: 1_700_000_100_000_000_000,
+            interval_in_seconds: 300,
+            update_calls: 3,
+            heap_memory_size: 100,
+            memory_size: 200,
+            cycles: 1200,
+            custom_metrics: vec![("queue \"size\"".to_string(), CustomMetricKind::gauge, 7)],
+            method_calls: vec![("doThis".to_string(), 2)],
+        };
+        let log_messages_info = CanisterLogMessagesInfo {
+            count: 3,
+            features: vec![],
+            firstTimeNanos: Some(1_000_000_000),
+            lastTimeNanos: Some(5_000_000_000),
+            usedBytes: Some(300),
+            maxBytes: None,
+            compressionRatio: None,
+            droppedMessages: Some(vec![DroppedLogMessages {
+                source: "http".to_string(),
+                count: 532,
+            }]),
+            channels: None,
+        };
+
+        let text = render(
+            &status,
+            Some(&interval),
+            &log_messages_info,
+            &[(LogLevel::info, 2), (LogLevel::error, 1)],
+        );
+        assert!(text.starts_with(
+            "# HELP canistergeek_cycles Current cycles balance.\n# TYPE canistergeek_cycles gauge\ncanistergeek_cycles 1000\n"
+        ));
+        assert!(!text.contains("canistergeek_heap_memory_size_bytes "));
+        assert!(text.contains("canistergeek_interval_start_timestamp_seconds 1700000100\n"));
+        assert!(text.contains("canistergeek_interval_update_calls 3\n"));
+        assert!(text.contains(
+            "canistergeek_interval_custom_metric{name=\"queue \\\"size\\\"\",kind=\"gauge\"} 7\n"
+        ));
+        assert!(text.contains("canistergeek_interval_method_calls{method=\"doThis\"} 2\n"));
+        assert!(text.contains("canistergeek_log_messages{level=\"error\"} 1\n"));
+        assert!(text.contains("canistergeek_log_last_message_timestamp_seconds 5\n"));
+        assert!(text.contains("canistergeek_log_dropped_messages{source=\"http\"} 532\n"));
+        assert!(text.ends_with("canistergeek_log_messages_bytes 300\n"));
+        assert_eq!(text.matches("# TYPE canistergeek_log_messages ").count(), 1);
+    }
+}
@@ -0,0 +1,77 @@
+/// Splits the request url into the path and decoded query parameters.
+pub fn parse_url(url: &str) -> (&str, Vec<(String, String)>) {
+    match url.split_once('?') {
+        None => (url, Vec::new()),
+        Some((path, query)) => {
+            let parameters = query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (decode(name), decode(value))
+                })
+                .collect();
+            (path, parameters)
+        }
+    }
+}
+
+/// Returns the value of the first parameter with the name.
+#[cfg_attr(not(feature = "json"), allow(dead_code))]
+pub fn get_parameter<'a>(parameters: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    parameters
+        .iter()
+        .find(|(parameter, _)| parameter == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Decodes `+` and `%XX` escapes, invalid escapes are kept as is.
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => match bytes
+                .get(index + 1..index + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    index += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            parse_url("/canistergeek/logs"),
+            ("/canistergeek/logs", vec![])
+        );
+
+        let (path, parameters) =
+            parse_url("/canistergeek/logs?count=10&contains=transfer+failed%21&flag&bad=%zz%4");
+        assert_eq!(path, "/canistergeek/logs");
+        assert_eq!(get_parameter(&parameters, "count"), Some("10"));
+        assert_eq!(
+            get_parameter(&parameters, "contains"),
+            Some("transfer failed!")
+        );
+        assert_eq!(get_parameter(&parameters, "flag"), Some(""));
+        assert_eq!(get_parameter(&parameters, "bad"), Some("%zz%4"));
+        assert_eq!(get_parameter(&parameters, "level"), None);
+    }
+}
@@ -0,0 +1,231 @@
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+pub const WASM_PAGE_SIZE_IN_BYTES: u64 = 65536;
+
+/// Linear memory addressed by byte offsets and growing by wasm pages.
+pub trait Memory {
+    /// Returns current size of the memory in wasm pages.
+    fn size(&self) -> u64;
+
+    /// Grows memory by `pages` wasm pages.
+    /// Returns previous size in pages, or -1 if the memory can not grow.
+    fn grow(&self, pages: u64) -> i64;
+
+    fn read(&self, offset: u64, dst: &mut [u8]);
+
+    fn write(&self, offset: u64, src: &[u8]);
+}
+
+/// Grows memory (if necessary) to hold at least `size_in_bytes` bytes.
+pub fn ensure_memory_size(memory: &dyn Memory, size_in_bytes: u64) -> Result<(), &'static str> {
+    let required_pages = size_in_bytes.div_ceil(WASM_PAGE_SIZE_IN_BYTES);
+    let current_pages = memory.size();
+    if current_pages < required_pages && memory.grow(required_pages - current_pages) < 0 {
+        return Err("Can not grow memory");
+    }
+    Ok(())
+}
+
+/// Whole canister stable memory.
+/// Outside of wasm it is emulated with a thread local vector (for tests).
+#[derive(Clone, Copy, Default)]
+pub struct StableMemory;
+
+#[cfg(not(target_arch = "wasm32"))]
+thread_local! {
+    static FAKE_STABLE_MEMORY: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Memory for StableMemory {
+    fn size(&self) -> u64 {
+        #[cfg(target_arch = "wasm32")]
+        {
+            ic_cdk::stable::stable_size()
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            FAKE_STABLE_MEMORY.with(|memory| memory.borrow().len() as u64 / WASM_PAGE_SIZE_IN_BYTES)
+        }
+    }
+
+    fn grow(&self, pages: u64) -> i64 {
+        #[cfg(target_arch = "wasm32")]
+        {
+            match ic_cdk::stable::stable_grow(pages) {
+                Ok(previous_pages) => previous_pages as i64,
+                Err(_) => -1,
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            FAKE_STABLE_MEMORY.with(|memory| grow_vector(&mut memory.borrow_mut(), pages))
+        }
+    }
+
+    fn read(&self, offset: u64, dst: &mut [u8]) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            ic_cdk::stable::stable_read(offset, dst)
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            FAKE_STABLE_MEMORY.with(|memory| read_vector(&memory.borrow(), offset, dst))
+        }
+    }
+
+    fn write(&self, offset: u64, src: &[u8]) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            ic_cdk::stable::stable_write(offset, src)
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            FAKE_STABLE_MEMORY.with(|memory| write_vector(&mut memory.borrow_mut(), offset, src))
+        }
+    }
+}
+
+/// Part of the memory limited by the range of wasm pages.
+/// Allows to keep several storages (or `ic_cdk::storage::stable_save` data) in one stable memory.
+pub struct RestrictedMemory<M: Memory> {
+    memory: M,
+    page_range: Range<u64>,
+}
+
+impl<M: Memory> RestrictedMemory<M> {
+    pub fn new(memory: M, page_range: Range<u64>) -> Self {
+        assert!(page_range.start <= page_range.end);
+        Self { memory, page_range }
+    }
+}
+
+impl<M: Memory> Memory for RestrictedMemory<M> {
+    fn size(&self) -> u64 {
+        let base_size = self.memory.size();
+        if base_size < self.page_range.start {
+            0
+        } else {
+            base_size.min(self.page_range.end) - self.page_range.start
+        }
+    }
+
+    fn grow(&self, pages: u64) -> i64 {
+        let base_size = self.memory.size();
+        if base_size < self.page_range.start {
+            let required = self.page_range.start + pages - base_size;
+            if self.page_range.start + pages > self.page_range.end || self.memory.grow(required) < 0
+            {
+                return -1;
+            }
+            return 0;
+        }
+
+        let size = self.size();
+        if size + pages > self.page_range.end - self.page_range.start {
+            return -1;
+        }
+        let base_end = self.page_range.start + size;
+        if base_end + pages > base_size && self.memory.grow(base_end + pages - base_size) < 0 {
+            return -1;
+        }
+        size as i64
+    }
+
+    fn read(&self, offset: u64, dst: &mut [u8]) {
+        self.memory.read(
+            self.page_range.start * WASM_PAGE_SIZE_IN_BYTES + offset,
+            dst,
+        )
+    }
+
+    fn write(&self, offset: u64, src: &[u8]) {
+        self.memory.write(
+            self.page_range.start * WASM_PAGE_SIZE_IN_BYTES + offset,
+            src,
+        )
+    }
+}
+
+/// Heap backed memory, useful for tests.
+/// Clones share the same bytes, so a storage can be reopened on the same memory.
+#[derive(Clone, Default)]
+pub struct VecMemory {
+    bytes: Rc<RefCell<Vec<u8>>>,
+}
+
+impl Memory for VecMemory {
+    fn size(&self) -> u64 {
+        self.bytes.borrow().len() as u64 / WASM_PAGE_SIZE_IN_BYTES
+    }
+
+    fn grow(&self, pages: u64) -> i64 {
+        grow_vector(&mut self.bytes.borrow_mut(), pages)
+    }
+
+    fn read(&self, offset: u64, dst: &mut [u8]) {
+        read_vector(&self.bytes.borrow(), offset, dst)
+    }
+
+    fn write(&self, offset: u64, src: &[u8]) {
+        write_vector(&mut self.bytes.borrow_mut(), offset, src)
+    }
+}
+
+fn grow_vector(bytes: &mut Vec<u8>, pages: u64) -> i64 {
+    let previous_pages = bytes.len() as u64 / WASM_PAGE_SIZE_IN_BYTES;
+    bytes.resize(
+        ((previous_pages + pages) * WASM_PAGE_SIZE_IN_BYTES) as usize,
+        0,
+    );
+    previous_pages as i64
+}
+
+fn read_vector(bytes: &[u8], offset: u64, dst: &mut [u8]) {
+    let offset = offset as usize;
+    dst.copy_from_slice(&bytes[offset..offset + dst.len()]);
+}
+
+fn write_vector(bytes: &mut [u8], offset: u64, src: &[u8]) {
+    let offset = offset as usize;
+    bytes[offset..offset + src.len()].copy_from_slice(src);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vec_memory() {
+        let memory = VecMemory::default();
+        assert_eq!(memory.size(), 0);
+        ensure_memory_size(&memory, 10).unwrap();
+        assert_eq!(memory.size(), 1);
+
+        memory.write(3, &[1, 2, 3]);
+        let mut dst = [0_u8; 4];
+        memory.clone().read(2, &mut dst);
+        assert_eq!(dst, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_restricted_memory() {
+        let base = VecMemory::default();
+        let memory = RestrictedMemory::new(base.clone(), 2..4);
+        assert_eq!(memory.size(), 0);
+
+        assert_eq!(memory.grow(1), 0);
+        assert_eq!(base.size(), 3);
+        assert_eq!(memory.size(), 1);
+
+        assert_eq!(memory.grow(2), -1);
+        assert_eq!(memory.grow(1), 1);
+        assert_eq!(memory.size(), 2);
+
+        memory.write(1, &[7]);
+        let mut dst = [0_u8; 1];
+        base.read(2 * WASM_PAGE_SIZE_IN_BYTES + 1, &mut dst);
+        assert_eq!(dst, [7]);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod memory;
+
 #[cfg(target_arch = "wasm32")]
 const WASM_PAGE_SIZE: u64 = 65536;
 
@@ -46,3 +48,145 @@ pub fn get_heap_memory_size() -> u64 {
         0
     }
 }
+
+/// Returns the number of instructions executed in the current call context (across awaits).
+pub fn get_instruction_counter() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::performance_counter(
+            ic_cdk::api::PerformanceCounterType::CallContextInstructionCounter,
+        )
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        FAKE_INSTRUCTION_COUNTER.with(|counter| counter.get())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+thread_local! {
+    static FAKE_INSTRUCTION_COUNTER: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+/// Advances the instruction counter faked outside of the IC.
+#[cfg(all(test, not(target_arch = "wasm32")))]
+pub(crate) fn advance_fake_instruction_counter(instructions: u64) {
+    FAKE_INSTRUCTION_COUNTER.with(|counter| counter.set(counter.get() + instructions));
+}
+
+pub fn get_caller() -> candid::Principal {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::msg_caller()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        FAKE_CALLER.with(|caller| *caller.borrow())
+    }
+}
+
+pub fn is_controller(principal: &candid::Principal) -> bool {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::is_controller(principal)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        FAKE_CONTROLLERS.with(|controllers| controllers.borrow().contains(principal))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+thread_local! {
+    static FAKE_CALLER: std::cell::RefCell<candid::Principal> =
+        const { std::cell::RefCell::new(candid::Principal::anonymous()) };
+    static FAKE_CONTROLLERS: std::cell::RefCell<Vec<candid::Principal>> =
+        const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Sets the caller faked outside of the IC.
+#[cfg(all(test, not(target_arch = "wasm32")))]
+pub(crate) fn set_fake_caller(caller: candid::Principal) {
+    FAKE_CALLER.with(|fake_caller| *fake_caller.borrow_mut() = caller);
+}
+
+/// Sets the canister controllers faked outside of the IC.
+#[cfg(all(test, not(target_arch = "wasm32")))]
+pub(crate) fn set_fake_controllers(controllers: Vec<candid::Principal>) {
+    FAKE_CONTROLLERS.with(|fake_controllers| *fake_controllers.borrow_mut() = controllers);
+}
+
+/// Calls the function every interval by `ic-cdk-timers`.
+#[cfg(feature = "timers")]
+pub fn set_timer_interval(
+    interval: std::time::Duration,
+    func: impl FnMut() + 'static,
+) -> ic_cdk_timers::TimerId {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk_timers::set_timer_interval(interval, func)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        FAKE_TIMER.with(|timer| *timer.borrow_mut() = Some((interval, Box::new(func))));
+        ic_cdk_timers::TimerId::default()
+    }
+}
+
+#[cfg(feature = "timers")]
+pub fn clear_timer(timer_id: ic_cdk_timers::TimerId) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk_timers::clear_timer(timer_id);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = timer_id;
+        FAKE_TIMER.with(|timer| *timer.borrow_mut() = None);
+    }
+}
+
+// the single interval timer faked outside of the IC
+#[cfg(all(feature = "timers", not(target_arch = "wasm32")))]
+type FakeTimer = (std::time::Duration, Box<dyn FnMut()>);
+
+#[cfg(all(feature = "timers", not(target_arch = "wasm32")))]
+thread_local! {
+    static FAKE_TIMER: std::cell::RefCell<Option<FakeTimer>> =
+        std::cell::RefCell::new(None);
+}
+
+/// Returns the interval of the timer faked outside of the IC.
+#[cfg(all(test, feature = "timers", not(target_arch = "wasm32")))]
+pub(crate) fn get_fake_timer_interval() -> Option<std::time::Duration> {
+    FAKE_TIMER.with(|timer| timer.borrow().as_ref().map(|(interval, _)| *interval))
+}
+
+/// Calls the function of the timer faked outside of the IC.
+#[cfg(all(test, feature = "timers", not(target_arch = "wasm32")))]
+pub(crate) fn run_fake_timer() {
+    let fake_timer = FAKE_TIMER.with(|timer| timer.borrow_mut().take());
+    if let Some((interval, mut func)) = fake_timer {
+        func();
+        FAKE_TIMER.with(|timer| {
+            timer.borrow_mut().get_or_insert((interval, func));
+        });
+    }
+}
+
+pub fn debug_print<T: AsRef<str>>(message: T) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::debug_print(message)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        eprintln!("{}", message.as_ref());
+    }
+}
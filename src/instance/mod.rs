@@ -0,0 +1,414 @@
+use super::alert::{self, AlertRule, Alerts};
+use super::api_type::{
+    AlertEvent, CollectMetricsRequestType, GetInformationRequest, GetInformationResponse,
+    HttpRequest, HttpResponse, LogField, LogLevel, MetricsResponse, UpdateInformationRequest,
+};
+use super::http;
+use super::ic_util;
+use super::logger::{self, Logger};
+use super::migration::{MigrationError, MigrationReport};
+use super::monitor::{self, Monitor};
+use std::cell::RefCell;
+
+const API_VERSION: u8 = 1;
+
+thread_local! {
+    static CANISTERGEEK: RefCell<Canistergeek> = RefCell::new(Canistergeek::new());
+}
+
+/// Runs the function with the instance the free functions of the crate work with.
+pub(crate) fn with<R>(f: impl FnOnce(&Canistergeek) -> R) -> R {
+    CANISTERGEEK.with(|canistergeek| f(&canistergeek.borrow()))
+}
+
+pub(crate) fn with_mut<R>(f: impl FnOnce(&mut Canistergeek) -> R) -> R {
+    CANISTERGEEK.with(|canistergeek| f(&mut canistergeek.borrow_mut()))
+}
+
+/// Monitor, logger and alerts state with the API of the crate free functions,
+/// which work with the instance of the current thread.
+///
+/// Methods collecting metrics or logging return alerts fired or resolved by them,
+/// the callback set by `alert::set_alert_callback` is called by the free functions only.
+pub struct Canistergeek {
+    monitor: Monitor,
+    logger: Logger,
+    alerts: Alerts,
+    #[cfg(feature = "json")]
+    json_logs_enabled: bool,
+}
+
+impl Default for Canistergeek {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Canistergeek {
+    pub fn new() -> Self {
+        Self {
+            monitor: Monitor::default(),
+            logger: Logger::default(),
+            alerts: Alerts::new(alert::DEFAULT_HISTORY_CAPACITY),
+            #[cfg(feature = "json")]
+            json_logs_enabled: false,
+        }
+    }
+
+    pub fn monitor(&self) -> &Monitor {
+        &self.monitor
+    }
+
+    pub fn monitor_mut(&mut self) -> &mut Monitor {
+        &mut self.monitor
+    }
+
+    pub fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    pub fn logger_mut(&mut self) -> &mut Logger {
+        &mut self.logger
+    }
+
+    /// See `alert::set_alert_rules`.
+    pub fn set_alert_rules(&mut self, rules: Vec<AlertRule>) -> Result<(), &'static str> {
+        self.alerts.set_rules(rules)
+    }
+
+    pub fn set_alert_history_capacity(&mut self, history_capacity: usize) {
+        self.alerts.set_history_capacity(history_capacity);
+    }
+
+    pub fn collect_metrics(&mut self) -> Vec<AlertEvent> {
+        self.collect_metrics_int(false)
+    }
+
+    /// See `monitor::collect_metrics_for_method`.
+    pub fn collect_metrics_for_method(&mut self, method_name: &str) -> Vec<AlertEvent> {
+        let events = self.collect_metrics_int(false);
+        self.monitor.collect_method_call(method_name);
+        events
+    }
+
+    pub(crate) fn collect_metrics_int(&mut self, force_set_info: bool) -> Vec<AlertEvent> {
+        let removed_days = self.monitor.collect_metrics(force_set_info);
+        self.log_removed_days(removed_days);
+        self.logger.flush_drop_summaries();
+        self.check_alerts()
+    }
+
+    #[cfg(feature = "timers")]
+    pub(crate) fn collect_canister_info(&mut self) -> Vec<AlertEvent> {
+        let removed_days = self.monitor.collect_canister_info();
+        self.log_removed_days(removed_days);
+        self.logger.flush_drop_summaries();
+        self.check_alerts()
+    }
+
+    /// Days pruned while collecting metrics are reported in the log, so the canister can see them.
+    fn log_removed_days(&mut self, removed_days: usize) {
+        if removed_days > 0 {
+            self.logger.log(
+                LogLevel::info,
+                format!(
+                    "Monitor removed {} days exceeding the retention policy",
+                    removed_days
+                ),
+            );
+        }
+    }
+
+    fn check_alerts(&mut self) -> Vec<AlertEvent> {
+        if !self.alerts.has_rules() {
+            return Vec::new();
+        }
+        let time_nanos = ic_util::get_ic_time_nanos();
+        let sample = self.monitor.get_metrics_sample(time_nanos);
+        self.alerts.on_metrics(time_nanos, &sample)
+    }
+
+    pub fn log(&mut self, level: LogLevel, message: String) -> Vec<AlertEvent> {
+        let stored = self.logger.log(level, message);
+        self.on_log_message(stored, level)
+    }
+
+    /// See `logger::log_with_fields`.
+    pub fn log_with_fields(
+        &mut self,
+        level: LogLevel,
+        message: String,
+        fields: Vec<LogField>,
+    ) -> Vec<AlertEvent> {
+        let stored = self.logger.log_with_fields(level, message, fields);
+        self.on_log_message(stored, level)
+    }
+
+    /// See `logger::log_to_channel`.
+    pub fn log_to_channel(
+        &mut self,
+        channel: &str,
+        level: LogLevel,
+        message: String,
+        fields: Vec<LogField>,
+    ) -> Result<Vec<AlertEvent>, &'static str> {
+        let stored = self
+            .logger
+            .log_to_channel(channel, level, message, fields)?;
+        Ok(self.on_log_message(stored, level))
+    }
+
+    fn on_log_message(&mut self, stored: bool, level: LogLevel) -> Vec<AlertEvent> {
+        if stored && self.alerts.has_rules() {
+            self.alerts
+                .on_log_message(ic_util::get_ic_time_nanos(), level)
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn update_information(&mut self, request: UpdateInformationRequest) -> Vec<AlertEvent> {
+        match request.metrics {
+            Some(CollectMetricsRequestType::normal) => self.collect_metrics_int(false),
+            Some(CollectMetricsRequestType::force) => self.collect_metrics_int(true),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn get_information(&self, request: GetInformationRequest) -> GetInformationResponse<'_> {
+        let version = if request.version {
+            Some(candid::Nat::from(API_VERSION))
+        } else {
+            None
+        };
+
+        let status = request.status.map(monitor::get_status);
+        let (metrics, metrics_error) = match request.metrics {
+            Some(request) => match self.monitor.try_get_metrics(&request.parameters) {
+                Ok(metrics) => (
+                    Some(MetricsResponse {
+                        metrics: Some(metrics),
+                    }),
+                    None,
+                ),
+                Err(error) => (Some(MetricsResponse { metrics: None }), Some(error)),
+            },
+            None => (None, None),
+        };
+        let (logs, logs_error) = match request
+            .logs
+            .map(|request| self.logger.try_get_canister_log(request))
+        {
+            Some(Ok(logs)) => (Some(logs), None),
+            Some(Err(error)) => (None, Some(error)),
+            None => (None, None),
+        };
+        let cycles_forecast = request
+            .cyclesForecast
+            .map(|request| self.monitor.get_cycles_forecast(request));
+        let alerts = request
+            .alerts
+            .map(|request| alert::get_alerts(&self.alerts, request));
+
+        GetInformationResponse {
+            version,
+            status,
+            metrics,
+            logs,
+            cyclesForecast: cycles_forecast,
+            alerts,
+            metricsError: metrics_error,
+            logsError: logs_error,
+        }
+    }
+
+    /// See `http::set_json_logs_enabled`.
+    #[cfg(feature = "json")]
+    pub fn set_json_logs_enabled(&mut self, enabled: bool) {
+        self.json_logs_enabled = enabled;
+    }
+
+    #[cfg(feature = "json")]
+    pub fn is_json_logs_enabled(&self) -> bool {
+        self.json_logs_enabled
+    }
+
+    /// See `http::http_request`.
+    pub fn http_request(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        http::serve(self, request)
+    }
+
+    pub fn pre_upgrade_stable_data(
+        &self,
+    ) -> (
+        monitor::PreUpgradeStableData<'_>,
+        logger::PreUpgradeStableData<'_>,
+    ) {
+        (
+            self.monitor.pre_upgrade_stable_data(),
+            self.logger.pre_upgrade_stable_data(),
+        )
+    }
+
+    /// Restores the data of both modules, returns the first error.
+    pub fn post_upgrade_stable_data(
+        &mut self,
+        (monitor_stable_data, logger_stable_data): (
+            monitor::PostUpgradeStableData,
+            logger::PostUpgradeStableData,
+        ),
+    ) -> Result<Vec<MigrationReport>, MigrationError> {
+        let monitor_result = self.monitor.post_upgrade_stable_data(monitor_stable_data);
+        let logger_result = self.logger.post_upgrade_stable_data(logger_stable_data);
+        Ok(vec![monitor_result?, logger_result?])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::AlertCondition;
+    use crate::api_type::{
+        AlertEventKind, AlertsRequest, CanisterLogRequest, CanisterLogResponse,
+        GetLatestLogMessagesParameters,
+    };
+
+    fn get_information_request(logs: Option<CanisterLogRequest>) -> GetInformationRequest {
+        GetInformationRequest {
+            version: true,
+            status: None,
+            metrics: None,
+            logs,
+            cyclesForecast: None,
+            alerts: Some(AlertsRequest {
+                fromTimeNanos: None,
+            }),
+        }
+    }
+
+    fn latest_messages() -> Option<CanisterLogRequest> {
+        Some(CanisterLogRequest::getLatestMessages(
+            GetLatestLogMessagesParameters {
+                count: 10,
+                filter: None,
+                upToTimeNanos: None,
+                channel: None,
+            },
+        ))
+    }
+
+    #[test]
+    fn test_independent_instances() {
+        let mut first = Canistergeek::new();
+        let mut second = Canistergeek::new();
+        first.log(LogLevel::info, "first".to_string());
+        second.logger_mut().set_min_log_level(LogLevel::warn);
+        second.log(LogLevel::info, "dropped".to_string());
+        second.log(LogLevel::error, "second".to_string());
+
+        let response = first.get_information(get_information_request(latest_messages()));
+        assert_eq!(response.version, Some(candid::Nat::from(API_VERSION)));
+        match response.logs {
+            Some(CanisterLogResponse::messages(messages)) => {
+                assert_eq!(messages.data.len(), 1);
+                assert_eq!(messages.data[0].message, "first");
+            }
+            _ => panic!(),
+        }
+        match second
+            .get_information(get_information_request(latest_messages()))
+            .logs
+        {
+            Some(CanisterLogResponse::messages(messages)) => {
+                assert_eq!(messages.data.len(), 1);
+                assert_eq!(messages.data[0].message, "second");
+            }
+            _ => panic!(),
+        }
+
+        // the instance of the free functions is not touched
+        let response = crate::get_information(get_information_request(latest_messages()));
+        match response.logs {
+            Some(CanisterLogResponse::messages(messages)) => assert!(messages.data.is_empty()),
+            _ => panic!(),
+        }
+        crate::with_information(get_information_request(latest_messages()), |response| {
+            match response.logs {
+                Some(CanisterLogResponse::messages(messages)) => assert!(messages.data.is_empty()),
+                _ => panic!(),
+            }
+        });
+    }
+
+    #[test]
+    fn test_removed_days_logged() {
+        use crate::monitor::data_type::{DayData, DayDataStorage, RetentionPolicy};
+        let mut storage = crate::monitor::store::Storage::default();
+        storage.store_day_data(&2022, &1, &27, DayData::new(&288));
+        let mut canistergeek = Canistergeek::new();
+        canistergeek
+            .monitor_mut()
+            .post_upgrade_stable_data((1, storage.get_day_data_table().clone(), None))
+            .unwrap();
+        canistergeek
+            .monitor_mut()
+            .set_retention_policy(RetentionPolicy {
+                max_days: Some(1),
+                max_bytes: None,
+            });
+
+        canistergeek.collect_metrics();
+        match canistergeek
+            .get_information(get_information_request(latest_messages()))
+            .logs
+        {
+            Some(CanisterLogResponse::messages(messages)) => assert_eq!(
+                messages.data[0].message,
+                "Monitor removed 1 days exceeding the retention policy"
+            ),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_alert_events() {
+        let mut canistergeek = Canistergeek::new();
+        canistergeek
+            .set_alert_rules(vec![AlertRule {
+                name: "errors".to_string(),
+                condition: AlertCondition::ErrorLogMessagesPerHourAbove(1),
+            }])
+            .unwrap();
+        assert!(canistergeek
+            .log(LogLevel::error, "error 1".to_string())
+            .is_empty());
+        let events = canistergeek.log(LogLevel::error, "error 2".to_string());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, AlertEventKind::fired);
+
+        let alerts = canistergeek
+            .get_information(get_information_request(None))
+            .alerts
+            .unwrap();
+        assert_eq!(alerts.firingRules, vec!["errors"]);
+    }
+
+    #[test]
+    fn test_alert_callback() {
+        let fired = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = fired.clone();
+        alert::set_alert_callback(Box::new(move |_| {
+            counter.set(counter.get() + 1);
+            // callbacks may use the free functions
+            logger::log_info("alert".to_string());
+        }));
+        alert::set_alert_rules(vec![AlertRule {
+            name: "errors".to_string(),
+            condition: AlertCondition::ErrorLogMessagesPerHourAbove(0),
+        }])
+        .unwrap();
+        logger::log_error("error".to_string());
+        assert_eq!(fired.get(), 1);
+        alert::clear_alert_callback();
+    }
+}
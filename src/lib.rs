@@ -4,63 +4,125 @@
 //! to track your project canisters cycles and memory status.
 
 use crate::api_type::{
-    CollectMetricsRequestType, GetInformationRequest, GetInformationResponse, MetricsResponse,
-    UpdateInformationRequest,
+    AccessError, GetInformationRequest, GetInformationResponse, UpdateInformationRequest,
 };
-use crate::monitor::{collect_metrics_int, get_metrics};
+use crate::migration::{MigrationError, MigrationReport};
 
+pub mod access_control;
+pub mod alert;
 pub mod api_type;
+pub mod http;
 pub mod ic_util;
+mod instance;
 pub mod logger;
+pub mod migration;
 pub mod monitor;
 
-const API_VERSION: u8 = 1;
+pub use instance::Canistergeek;
 
-pub fn pre_upgrade_stable_data<'a>() -> (
-    monitor::PreUpgradeStableData<'a>,
-    logger::PreUpgradeStableData<'a>,
+/// Returns a copy of the data kept in the heap storages.
+pub fn pre_upgrade_stable_data() -> (
+    monitor::PostUpgradeStableData,
+    logger::PostUpgradeStableData,
+    access_control::PostUpgradeStableData,
 ) {
     let monitor_stable_data = monitor::pre_upgrade_stable_data();
     let logger_stable_data = logger::pre_upgrade_stable_data();
-    (monitor_stable_data, logger_stable_data)
+    let access_control_stable_data = access_control::pre_upgrade_stable_data();
+    (
+        monitor_stable_data,
+        logger_stable_data,
+        access_control_stable_data,
+    )
 }
 
+/// Like `pre_upgrade_stable_data`, but runs the function with the data borrowed, so it is saved
+/// in `pre_upgrade` without copying:
+/// `with_pre_upgrade_stable_data(|data| ic_cdk::storage::stable_save(data))`.
+pub fn with_pre_upgrade_stable_data<R>(
+    f: impl FnOnce(
+        (
+            monitor::PreUpgradeStableData<'_>,
+            logger::PreUpgradeStableData<'_>,
+            access_control::PreUpgradeStableData<'_>,
+        ),
+    ) -> R,
+) -> R {
+    instance::with(|canistergeek| {
+        access_control::with_pre_upgrade_stable_data(|access_control_stable_data| {
+            let (monitor_stable_data, logger_stable_data) = canistergeek.pre_upgrade_stable_data();
+            f((
+                monitor_stable_data,
+                logger_stable_data,
+                access_control_stable_data,
+            ))
+        })
+    })
+}
+
+/// Restores the data of all modules, access control data is optional,
+/// so data saved by previous versions can be restored.
+///
+/// Returns the first error, the data of other modules is restored anyway.
 pub fn post_upgrade_stable_data(
-    (monitor_stable_data, logger_stable_data): (
+    (monitor_stable_data, logger_stable_data, access_control_stable_data): (
         monitor::PostUpgradeStableData,
         logger::PostUpgradeStableData,
+        Option<access_control::PostUpgradeStableData>,
     ),
-) {
-    monitor::post_upgrade_stable_data(monitor_stable_data);
-    logger::post_upgrade_stable_data(logger_stable_data);
+) -> Result<Vec<MigrationReport>, MigrationError> {
+    let mut results = vec![
+        monitor::post_upgrade_stable_data(monitor_stable_data),
+        logger::post_upgrade_stable_data(logger_stable_data),
+    ];
+    if let Some(access_control_stable_data) = access_control_stable_data {
+        results.push(access_control::post_upgrade_stable_data(
+            access_control_stable_data,
+        ));
+    }
+    results.into_iter().collect()
 }
 
 pub fn update_information(request: UpdateInformationRequest) {
-    if let Some(metrics_request) = request.metrics {
-        match metrics_request {
-            CollectMetricsRequestType::normal => collect_metrics_int(false),
-            CollectMetricsRequestType::force => collect_metrics_int(true),
-        };
-    }
+    let events = instance::with_mut(|canistergeek| canistergeek.update_information(request));
+    alert::notify(&events);
 }
 
-pub fn get_information<'a>(request: GetInformationRequest) -> GetInformationResponse<'a> {
-    let version = if request.version {
-        Some(candid::Nat::from(API_VERSION))
-    } else {
-        None
-    };
+/// Like `update_information`, but only for callers allowed by `access_control`.
+pub fn update_information_authorized(request: UpdateInformationRequest) -> Result<(), AccessError> {
+    access_control::check_caller()?;
+    update_information(request);
+    Ok(())
+}
 
-    let status = request.status.map(monitor::get_status);
-    let metrics = request.metrics.map(|request| MetricsResponse {
-        metrics: get_metrics(&request.parameters),
-    });
-    let logs = logger::get_canister_log(request.logs);
+/// Like `get_information`, but only for callers allowed by `access_control`.
+pub fn get_information_authorized<'a>(
+    request: GetInformationRequest,
+) -> Result<GetInformationResponse<'a>, AccessError> {
+    access_control::check_caller()?;
+    Ok(get_information(request))
+}
+
+pub fn get_information<'a>(request: GetInformationRequest) -> GetInformationResponse<'a> {
+    instance::with(|canistergeek| canistergeek.get_information(request).into_owned())
+}
 
-    GetInformationResponse {
-        version,
-        status,
-        metrics,
-        logs,
+/// Like `with_information`, but only for callers allowed by `access_control`.
+pub fn with_information_authorized<R>(
+    request: GetInformationRequest,
+    f: impl FnOnce(Result<GetInformationResponse<'_>, AccessError>) -> R,
+) -> R {
+    match access_control::check_caller() {
+        Ok(()) => with_information(request, |response| f(Ok(response))),
+        Err(error) => f(Err(error)),
     }
 }
+
+/// Like `get_information`, but runs the function with the response borrowing the collected data,
+/// so the reply can be encoded without copying it (see the `manual_reply` query in README).
+pub fn with_information<R>(
+    request: GetInformationRequest,
+    f: impl FnOnce(GetInformationResponse<'_>) -> R,
+) -> R {
+    instance::with(|canistergeek| f(canistergeek.get_information(request)))
+}
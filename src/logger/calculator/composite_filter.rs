@@ -0,0 +1,30 @@
+use super::super::data_type::LogMessage;
+use super::Filter;
+
+/// Implementation Filter for matching message by all passed filters.
+/// Every filter analyzes each message, so analyze counters stay in sync.
+pub struct CompositeFilter {
+    filters: Vec<Box<dyn Filter>>,
+}
+
+impl CompositeFilter {
+    pub fn create(filters: Vec<Box<dyn Filter>>) -> CompositeFilter {
+        CompositeFilter { filters }
+    }
+}
+
+impl Filter for CompositeFilter {
+    fn check_match(&mut self, log_message: &LogMessage) -> bool {
+        let mut matched = true;
+        for filter in self.filters.iter_mut() {
+            if !filter.check_match(log_message) {
+                matched = false;
+            }
+        }
+        matched
+    }
+
+    fn is_stop(&self) -> bool {
+        self.filters.iter().any(|filter| filter.is_stop())
+    }
+}
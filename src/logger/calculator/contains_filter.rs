@@ -2,7 +2,6 @@ use super::super::data_type::LogMessage;
 use super::Filter;
 
 /// Implementation Filter for filter message by contains text matching
-
 pub struct MessageContainsFilter {
     contains_text: String,
     analyze_count: usize,
@@ -37,6 +36,7 @@ impl Filter for MessageContainsFilter {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::Filter;
     use crate::logger::data_type::LogMessage;
@@ -49,7 +49,8 @@ mod tests {
         assert_eq!(
             filter.check_match(&LogMessage {
                 timeNanos: 0,
-                message: String::from("mess aBc sss")
+                message: String::from("mess aBc sss"),
+                ..Default::default()
             }),
             true
         );
@@ -57,7 +58,8 @@ mod tests {
         assert_eq!(
             filter.check_match(&LogMessage {
                 timeNanos: 0,
-                message: String::from("aa abc bb")
+                message: String::from("aa abc bb"),
+                ..Default::default()
             }),
             true
         );
@@ -65,7 +67,8 @@ mod tests {
         assert_eq!(
             filter.check_match(&LogMessage {
                 timeNanos: 0,
-                message: String::from("aa ab bb")
+                message: String::from("aa ab bb"),
+                ..Default::default()
             }),
             false
         );
@@ -0,0 +1,264 @@
+use super::super::super::api_type::{
+    InformationError, LogField, LogFilterExpression, LogLevel, Nanos,
+};
+use super::super::data_type::LogMessage;
+use super::level_filter::DEFAULT_LOG_LEVEL;
+use super::Filter;
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+// the expression comes from a query argument, so its compilation is bounded
+const MAX_EXPRESSION_DEPTH: u32 = 16;
+const MAX_EXPRESSION_NODES: u32 = 128;
+
+/// Implementation Filter for filter message by boolean expression tree
+pub struct MessageExpressionFilter {
+    condition: Condition,
+    analyze_count: usize,
+    analyzed: usize,
+}
+
+/// Compiled expression node: texts are lowercased and regexes are built once.
+enum Condition {
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+    Not(Box<Condition>),
+    Contains(String),
+    #[cfg(feature = "regex")]
+    Regex(Regex),
+    MinLevel(LogLevel),
+    TimeRange(Option<Nanos>, Option<Nanos>),
+    FieldEquals(LogField),
+}
+
+impl MessageExpressionFilter {
+    pub fn create(
+        analyze_count: usize,
+        expression: LogFilterExpression,
+    ) -> Result<MessageExpressionFilter, InformationError> {
+        Ok(MessageExpressionFilter {
+            condition: Condition::compile(expression, 1, &mut 0)?,
+            analyze_count,
+            analyzed: 0,
+        })
+    }
+}
+
+impl Filter for MessageExpressionFilter {
+    fn check_match(&mut self, log_message: &LogMessage) -> bool {
+        self.analyzed += 1;
+        self.condition.matches(log_message)
+    }
+
+    fn is_stop(&self) -> bool {
+        self.analyzed >= self.analyze_count
+    }
+}
+
+impl Condition {
+    fn compile(
+        expression: LogFilterExpression,
+        depth: u32,
+        nodes: &mut u32,
+    ) -> Result<Condition, InformationError> {
+        *nodes += 1;
+        if depth > MAX_EXPRESSION_DEPTH || *nodes > MAX_EXPRESSION_NODES {
+            return Err(InformationError::expressionTooLarge {
+                maxDepth: MAX_EXPRESSION_DEPTH,
+                maxNodes: MAX_EXPRESSION_NODES,
+            });
+        }
+        match expression {
+            LogFilterExpression::and(expressions) => Ok(Condition::And(Condition::compile_all(
+                expressions,
+                depth,
+                nodes,
+            )?)),
+            LogFilterExpression::or(expressions) => Ok(Condition::Or(Condition::compile_all(
+                expressions,
+                depth,
+                nodes,
+            )?)),
+            LogFilterExpression::not(expression) => Ok(Condition::Not(Box::new(
+                Condition::compile(*expression, depth + 1, nodes)?,
+            ))),
+            LogFilterExpression::messageContains(text) => {
+                Ok(Condition::Contains(text.to_lowercase()))
+            }
+            #[cfg(feature = "regex")]
+            LogFilterExpression::messageRegex(regex_text) => match Regex::new(&regex_text) {
+                Ok(regex) => Ok(Condition::Regex(regex)),
+                Err(error) => Err(InformationError::invalidRegex(error.to_string())),
+            },
+            #[cfg(not(feature = "regex"))]
+            LogFilterExpression::messageRegex(_regex_text) => Err(
+                InformationError::unsupportedFilter("Regex filter not supported".to_string()),
+            ),
+            LogFilterExpression::minLevel(level) => Ok(Condition::MinLevel(level)),
+            LogFilterExpression::timeRange(range) => {
+                Ok(Condition::TimeRange(range.fromTimeNanos, range.toTimeNanos))
+            }
+            LogFilterExpression::fieldEquals(field) => Ok(Condition::FieldEquals(field)),
+        }
+    }
+
+    fn compile_all(
+        expressions: Vec<LogFilterExpression>,
+        depth: u32,
+        nodes: &mut u32,
+    ) -> Result<Vec<Condition>, InformationError> {
+        expressions
+            .into_iter()
+            .map(|expression| Condition::compile(expression, depth + 1, nodes))
+            .collect()
+    }
+
+    fn matches(&self, log_message: &LogMessage) -> bool {
+        match self {
+            Condition::And(conditions) => conditions.iter().all(|c| c.matches(log_message)),
+            Condition::Or(conditions) => conditions.iter().any(|c| c.matches(log_message)),
+            Condition::Not(condition) => !condition.matches(log_message),
+            Condition::Contains(text) => log_message.message.to_lowercase().contains(text),
+            #[cfg(feature = "regex")]
+            Condition::Regex(regex) => regex.is_match(&log_message.message),
+            Condition::MinLevel(level) => log_message.level.unwrap_or(DEFAULT_LOG_LEVEL) >= *level,
+            Condition::TimeRange(from, to) => {
+                log_message.timeNanos >= from.unwrap_or(0)
+                    && log_message.timeNanos <= to.unwrap_or(Nanos::MAX)
+            }
+            Condition::FieldEquals(field) => log_message
+                .fields
+                .as_ref()
+                .is_some_and(|fields| fields.contains(field)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filter;
+    use crate::api_type::{
+        InformationError, LogField, LogFieldValue, LogFilterExpression, LogLevel, LogTimeRange,
+    };
+    use crate::logger::data_type::LogMessage;
+
+    fn create_message(time_nanos: u64, message: &str, level: LogLevel) -> LogMessage {
+        LogMessage {
+            timeNanos: time_nanos,
+            message: String::from(message),
+            level: Some(level),
+            fields: Some(vec![LogField {
+                name: String::from("source"),
+                value: LogFieldValue::text(String::from("ledger")),
+            }]),
+            ..Default::default()
+        }
+    }
+
+    fn contains(text: &str) -> LogFilterExpression {
+        LogFilterExpression::messageContains(String::from(text))
+    }
+
+    #[test]
+    fn test() {
+        let mut filter = super::MessageExpressionFilter::create(
+            4,
+            LogFilterExpression::and(vec![
+                contains("Transfer"),
+                LogFilterExpression::not(Box::new(contains("ok"))),
+            ]),
+        )
+        .unwrap();
+
+        assert!(!filter.is_stop());
+        assert!(filter.check_match(&create_message(1, "transfer failed", LogLevel::error)));
+        assert!(!filter.check_match(&create_message(2, "transfer OK", LogLevel::info)));
+        assert!(!filter.check_match(&create_message(3, "approve failed", LogLevel::error)));
+        assert!(!filter.is_stop());
+        assert!(filter.check_match(&create_message(4, "TRANSFER retried", LogLevel::warn)));
+        assert!(filter.is_stop());
+    }
+
+    #[test]
+    fn test_or_level_time_field() {
+        let mut filter = super::MessageExpressionFilter::create(
+            10,
+            LogFilterExpression::or(vec![
+                LogFilterExpression::minLevel(LogLevel::warn),
+                LogFilterExpression::and(vec![
+                    LogFilterExpression::timeRange(LogTimeRange {
+                        fromTimeNanos: Some(20),
+                        toTimeNanos: Some(30),
+                    }),
+                    LogFilterExpression::fieldEquals(LogField {
+                        name: String::from("source"),
+                        value: LogFieldValue::text(String::from("ledger")),
+                    }),
+                ]),
+            ]),
+        )
+        .unwrap();
+
+        assert!(filter.check_match(&create_message(10, "a", LogLevel::error)));
+        assert!(!filter.check_match(&create_message(10, "b", LogLevel::info)));
+        assert!(filter.check_match(&create_message(20, "c", LogLevel::debug)));
+        assert!(filter.check_match(&create_message(30, "d", LogLevel::info)));
+        assert!(!filter.check_match(&create_message(31, "e", LogLevel::info)));
+        assert!(!filter.check_match(&LogMessage {
+            timeNanos: 25,
+            message: String::from("f"),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut filter =
+            super::MessageExpressionFilter::create(10, LogFilterExpression::and(vec![])).unwrap();
+        assert!(filter.check_match(&create_message(1, "a", LogLevel::info)));
+
+        let mut filter =
+            super::MessageExpressionFilter::create(10, LogFilterExpression::or(vec![])).unwrap();
+        assert!(!filter.check_match(&create_message(1, "a", LogLevel::info)));
+    }
+
+    fn nested(depth: u32) -> LogFilterExpression {
+        (1..depth).fold(contains("a"), |expression, _| {
+            LogFilterExpression::not(Box::new(expression))
+        })
+    }
+
+    fn flat(nodes: u32) -> LogFilterExpression {
+        LogFilterExpression::or((1..nodes).map(|_| contains("a")).collect())
+    }
+
+    #[test]
+    fn test_too_large() {
+        let too_large = Some(InformationError::expressionTooLarge {
+            maxDepth: super::MAX_EXPRESSION_DEPTH,
+            maxNodes: super::MAX_EXPRESSION_NODES,
+        });
+
+        let max_depth = super::MAX_EXPRESSION_DEPTH;
+        assert!(super::MessageExpressionFilter::create(10, nested(max_depth)).is_ok());
+        let result = super::MessageExpressionFilter::create(10, nested(max_depth + 1));
+        assert_eq!(result.err(), too_large);
+
+        let max_nodes = super::MAX_EXPRESSION_NODES;
+        assert!(super::MessageExpressionFilter::create(10, flat(max_nodes)).is_ok());
+        let result = super::MessageExpressionFilter::create(10, flat(max_nodes + 1));
+        assert_eq!(result.err(), too_large);
+    }
+
+    #[cfg(not(feature = "regex"))]
+    #[test]
+    fn test_regex_not_supported() {
+        let result = super::MessageExpressionFilter::create(
+            10,
+            LogFilterExpression::not(Box::new(LogFilterExpression::messageRegex(String::from(
+                "abc",
+            )))),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use super::super::super::api_type::{InformationError, LogField};
+use super::super::data_type::LogMessage;
+use super::Filter;
+
+/// Implementation Filter for filter message by equality of all passed fields
+pub struct MessageFieldsFilter {
+    fields: Vec<LogField>,
+    analyze_count: usize,
+    analyzed: usize,
+}
+
+impl MessageFieldsFilter {
+    pub fn create(
+        analyze_count: usize,
+        fields: Vec<LogField>,
+    ) -> Result<MessageFieldsFilter, InformationError> {
+        if fields.is_empty() {
+            return Err(InformationError::unsupportedFilter(
+                "Empty fields filter".to_string(),
+            ));
+        }
+
+        Ok(MessageFieldsFilter {
+            analyze_count,
+            fields,
+            analyzed: 0,
+        })
+    }
+}
+
+impl Filter for MessageFieldsFilter {
+    fn check_match(&mut self, log_message: &LogMessage) -> bool {
+        self.analyzed += 1;
+        match &log_message.fields {
+            None => false,
+            Some(message_fields) => self
+                .fields
+                .iter()
+                .all(|field| message_fields.contains(field)),
+        }
+    }
+
+    fn is_stop(&self) -> bool {
+        self.analyzed >= self.analyze_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filter;
+    use crate::api_type::{LogField, LogFieldValue};
+    use crate::logger::data_type::LogMessage;
+    use candid::{Nat, Principal};
+
+    #[test]
+    fn test() {
+        let user = Principal::from_text("aaaaa-aa").unwrap();
+        let mut filter = super::MessageFieldsFilter::create(
+            3,
+            vec![
+                LogField {
+                    name: String::from("user"),
+                    value: LogFieldValue::principal(user),
+                },
+                LogField {
+                    name: String::from("amount"),
+                    value: LogFieldValue::nat(Nat::from(123_u64)),
+                },
+            ],
+        )
+        .unwrap();
+
+        assert!(!filter.is_stop());
+        assert!(filter.check_match(&LogMessage {
+            timeNanos: 0,
+            message: String::from("transfer"),
+            fields: Some(vec![
+                LogField {
+                    name: String::from("amount"),
+                    value: LogFieldValue::nat(Nat::from(123_u64)),
+                },
+                LogField {
+                    name: String::from("success"),
+                    value: LogFieldValue::bool(true),
+                },
+                LogField {
+                    name: String::from("user"),
+                    value: LogFieldValue::principal(user),
+                },
+            ]),
+            ..Default::default()
+        }));
+        assert!(!filter.is_stop());
+        assert!(!filter.check_match(&LogMessage {
+            timeNanos: 0,
+            message: String::from("transfer"),
+            fields: Some(vec![
+                LogField {
+                    name: String::from("amount"),
+                    value: LogFieldValue::int(candid::Int::from(123_i64)),
+                },
+                LogField {
+                    name: String::from("user"),
+                    value: LogFieldValue::principal(user),
+                },
+            ]),
+            ..Default::default()
+        }));
+        assert!(!filter.is_stop());
+        assert!(!filter.check_match(&LogMessage {
+            timeNanos: 0,
+            message: String::from("user=aaaaa-aa amount=123"),
+            ..Default::default()
+        }));
+        assert!(filter.is_stop());
+
+        assert!(super::MessageFieldsFilter::create(3, vec![]).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use super::super::super::api_type::LogLevel;
+use super::super::data_type::LogMessage;
+use super::Filter;
+
+/// Messages stored before log levels were introduced are treated as `info`.
+pub const DEFAULT_LOG_LEVEL: LogLevel = LogLevel::info;
+
+/// Implementation Filter for filter message by minimal log level
+pub struct MessageLevelFilter {
+    min_level: LogLevel,
+    analyze_count: usize,
+    analyzed: usize,
+}
+
+impl MessageLevelFilter {
+    pub fn create(analyze_count: usize, min_level: LogLevel) -> MessageLevelFilter {
+        MessageLevelFilter {
+            analyze_count,
+            min_level,
+            analyzed: 0,
+        }
+    }
+}
+
+impl Filter for MessageLevelFilter {
+    fn check_match(&mut self, log_message: &LogMessage) -> bool {
+        self.analyzed += 1;
+        log_message.level.unwrap_or(DEFAULT_LOG_LEVEL) >= self.min_level
+    }
+
+    fn is_stop(&self) -> bool {
+        self.analyzed >= self.analyze_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filter;
+    use crate::api_type::LogLevel;
+    use crate::logger::data_type::LogMessage;
+
+    #[test]
+    fn test() {
+        let mut filter = super::MessageLevelFilter::create(4, LogLevel::warn);
+
+        assert!(!filter.is_stop());
+        assert!(filter.check_match(&LogMessage {
+            timeNanos: 0,
+            message: String::from("error"),
+            level: Some(LogLevel::error),
+            ..Default::default()
+        }));
+        assert!(!filter.is_stop());
+        assert!(filter.check_match(&LogMessage {
+            timeNanos: 0,
+            message: String::from("warn"),
+            level: Some(LogLevel::warn),
+            ..Default::default()
+        }));
+        assert!(!filter.is_stop());
+        assert!(!filter.check_match(&LogMessage {
+            timeNanos: 0,
+            message: String::from("debug"),
+            level: Some(LogLevel::debug),
+            ..Default::default()
+        }));
+        assert!(!filter.is_stop());
+        assert!(!filter.check_match(&LogMessage {
+            timeNanos: 0,
+            message: String::from("without level"),
+            ..Default::default()
+        }));
+        assert!(filter.is_stop());
+    }
+}
@@ -1,11 +1,18 @@
 use super::super::api_type::{
     CanisterLogMessages, CanisterLogMessagesInfo, GetLatestLogMessagesParameters,
-    GetLogMessagesFilter, GetLogMessagesParameters, LogMessageData, Nanos,
+    GetLogMessagesFilter, GetLogMessagesParameters, InformationError, LogLevel, LogMessageData,
+    Nanos,
 };
 use super::data_type::{LogMessage, LogMessagesSupplier};
+use std::borrow::Cow;
 
+mod composite_filter;
 mod contains_filter;
-// mod regex_filter;
+mod expression_filter;
+mod fields_filter;
+mod level_filter;
+#[cfg(feature = "regex")]
+mod regex_filter;
 
 const MAX_CHUNK_SIZE: usize = 1024;
 
@@ -18,20 +25,50 @@ pub fn get_log_messages_info(
             firstTimeNanos: None,
             lastTimeNanos: None,
             features: vec![],
+            usedBytes: Some(0),
+            maxBytes: log_message_supplier.get_max_log_messages_bytes(),
+            compressionRatio: log_message_supplier.get_compression_ratio(),
+            droppedMessages: None,
+            channels: None,
         },
         count => CanisterLogMessagesInfo {
             count,
             firstTimeNanos: log_message_supplier.get_first_log_message_time(),
             lastTimeNanos: log_message_supplier.get_last_log_message_time(),
             features: vec![],
+            usedBytes: Some(log_message_supplier.get_log_messages_bytes()),
+            maxBytes: log_message_supplier.get_max_log_messages_bytes(),
+            compressionRatio: log_message_supplier.get_compression_ratio(),
+            droppedMessages: None,
+            channels: None,
         },
     }
 }
 
+/// Counts stored messages of each level, messages without a level are counted as `info`.
+pub fn get_log_level_counts(
+    log_message_supplier: &dyn LogMessagesSupplier,
+) -> Vec<(LogLevel, u32)> {
+    let mut counts = vec![
+        (LogLevel::trace, 0),
+        (LogLevel::debug, 0),
+        (LogLevel::info, 0),
+        (LogLevel::warn, 0),
+        (LogLevel::error, 0),
+    ];
+    for log_message in log_message_supplier.get_log_messages(&None) {
+        let level = log_message.level.unwrap_or(level_filter::DEFAULT_LOG_LEVEL);
+        if let Some((_, count)) = counts.iter_mut().find(|(l, _)| *l == level) {
+            *count += 1;
+        }
+    }
+    counts
+}
+
 pub fn get_log_messages(
     log_message_supplier: &dyn LogMessagesSupplier,
     parameters: GetLogMessagesParameters,
-) -> Result<CanisterLogMessages<'_>, &'_ str> {
+) -> Result<CanisterLogMessages<'_>, InformationError> {
     iterate_log_messages_int(
         false,
         &parameters.fromTimeNanos,
@@ -44,7 +81,7 @@ pub fn get_log_messages(
 pub fn get_latest_log_messages(
     log_message_supplier: &dyn LogMessagesSupplier,
     parameters: GetLatestLogMessagesParameters,
-) -> Result<CanisterLogMessages<'_>, &'_ str> {
+) -> Result<CanisterLogMessages<'_>, InformationError> {
     iterate_log_messages_int(
         true,
         &parameters.upToTimeNanos,
@@ -60,9 +97,11 @@ fn iterate_log_messages_int<'a>(
     count: usize,
     filter: Option<GetLogMessagesFilter>,
     log_message_supplier: &'a dyn LogMessagesSupplier,
-) -> Result<CanisterLogMessages<'a>, &'a str> {
+) -> Result<CanisterLogMessages<'a>, InformationError> {
     if count == 0 || count > MAX_CHUNK_SIZE {
-        return Err("Wrong count number");
+        return Err(InformationError::invalidCount {
+            maxCount: MAX_CHUNK_SIZE as u32,
+        });
     }
 
     let mut iterator_box = if reverse {
@@ -73,7 +112,7 @@ fn iterate_log_messages_int<'a>(
 
     let iterator = iterator_box.as_mut();
 
-    let mut data: Vec<&'a LogMessageData> = Vec::with_capacity(count);
+    let mut data: Vec<Cow<'a, LogMessageData>> = Vec::with_capacity(count);
     let mut message_time_nanos: Option<Nanos> = None;
 
     match filter {
@@ -87,7 +126,7 @@ fn iterate_log_messages_int<'a>(
 
                 message_time_nanos = Some(message.timeNanos);
 
-                if !filter_trait.check_match(message) {
+                if !filter_trait.check_match(&message) {
                     continue;
                 }
 
@@ -121,23 +160,57 @@ trait Filter {
     fn is_stop(&self) -> bool;
 }
 
-fn build_filter<'a>(filter: GetLogMessagesFilter) -> Result<Box<dyn Filter>, &'a str> {
-    match &filter.messageContains {
-        Some(contains_text) => {
-            let contains_filter = contains_filter::MessageContainsFilter::create(
-                filter.analyzeCount as usize,
-                contains_text,
-            )
-            .unwrap();
-            Ok(Box::new(contains_filter))
+fn build_filter(filter: GetLogMessagesFilter) -> Result<Box<dyn Filter>, InformationError> {
+    let analyze_count = filter.analyzeCount as usize;
+    let mut filters: Vec<Box<dyn Filter>> = Vec::new();
+
+    // the regex is used over contains, so it is rejected without the `regex` feature
+    match (&filter.messageContains, &filter.messageRegex) {
+        #[cfg(feature = "regex")]
+        (_, Some(regex_text)) => {
+            let regex_filter = regex_filter::MessageRegexFilter::create(analyze_count, regex_text)?;
+            filters.push(Box::new(regex_filter));
         }
-        None => match &filter.messageRegex {
-            Some(_regex_text) => Err("Regex filter not supported"),
-            // {
-            // let regex_filter = regex_filter::MessageRegexFilter::create(filter.analyzeCount as usize, regex_text).unwrap();
-            // Ok(Box::new(regex_filter))
-            // }
-            None => Err("Empty filter"),
-        },
+        #[cfg(not(feature = "regex"))]
+        (_, Some(_regex_text)) => {
+            return Err(InformationError::unsupportedFilter(
+                "Regex filter not supported".to_string(),
+            ))
+        }
+        (Some(contains_text), _) => {
+            let contains_filter =
+                contains_filter::MessageContainsFilter::create(analyze_count, contains_text)
+                    .unwrap();
+            filters.push(Box::new(contains_filter));
+        }
+        (None, None) => {}
+    }
+
+    if let Some(min_level) = filter.minLevel {
+        filters.push(Box::new(level_filter::MessageLevelFilter::create(
+            analyze_count,
+            min_level,
+        )));
+    }
+
+    if let Some(fields) = filter.fieldsEqual {
+        filters.push(Box::new(fields_filter::MessageFieldsFilter::create(
+            analyze_count,
+            fields,
+        )?));
+    }
+
+    if let Some(expression) = filter.expression {
+        filters.push(Box::new(
+            expression_filter::MessageExpressionFilter::create(analyze_count, expression)?,
+        ));
+    }
+
+    match filters.len() {
+        0 => Err(InformationError::unsupportedFilter(
+            "Empty filter".to_string(),
+        )),
+        1 => Ok(filters.pop().unwrap()),
+        _ => Ok(Box::new(composite_filter::CompositeFilter::create(filters))),
     }
 }
@@ -1,9 +1,9 @@
+use super::super::super::api_type::InformationError;
 use super::super::data_type::LogMessage;
 use super::Filter;
 use regex::Regex;
 
 /// Implementation Filter for filter message by message Reqex matching
-
 pub struct MessageRegexFilter {
     regex: Regex,
     analyze_count: usize,
@@ -11,14 +11,17 @@ pub struct MessageRegexFilter {
 }
 
 impl MessageRegexFilter {
-    pub fn create(analyze_count: usize, regex_text: &str) -> Result<MessageRegexFilter, &str> {
+    pub fn create(
+        analyze_count: usize,
+        regex_text: &str,
+    ) -> Result<MessageRegexFilter, InformationError> {
         match Regex::new(regex_text) {
             Ok(regex) => Ok(MessageRegexFilter {
                 analyze_count,
                 regex,
                 analyzed: 0,
             }),
-            Err(_) => Err("Can not create regex filter"),
+            Err(error) => Err(InformationError::invalidRegex(error.to_string())),
         }
     }
 }
@@ -35,6 +38,7 @@ impl Filter for MessageRegexFilter {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::Filter;
     use crate::logger::data_type::LogMessage;
@@ -47,7 +51,8 @@ mod tests {
         assert_eq!(
             filter.check_match(&LogMessage {
                 timeNanos: 0,
-                message: String::from("mess abc sss")
+                message: String::from("mess abc sss"),
+                ..Default::default()
             }),
             true
         );
@@ -55,7 +60,8 @@ mod tests {
         assert_eq!(
             filter.check_match(&LogMessage {
                 timeNanos: 0,
-                message: String::from("aa abc bb")
+                message: String::from("aa abc bb"),
+                ..Default::default()
             }),
             true
         );
@@ -63,7 +69,8 @@ mod tests {
         assert_eq!(
             filter.check_match(&LogMessage {
                 timeNanos: 0,
-                message: String::from("aa ab bb")
+                message: String::from("aa ab bb"),
+                ..Default::default()
             }),
             false
         );
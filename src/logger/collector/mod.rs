@@ -1,19 +1,53 @@
+use super::super::api_type::{LogField, LogLevel};
 use super::super::ic_util;
 use super::data_type::{LogMessage, LogMessagesStorage};
 
+/// Stores the message, with the dedup window identical messages following each other are collapsed
+/// into the first one while they are logged within the window from its time.
 pub fn store_log_message(
     storage: &mut dyn LogMessagesStorage,
+    level: LogLevel,
     message: String,
     max_message_length: &usize,
+    dedup_window_nanos: &Option<u64>,
+) {
+    store_log_message_with_fields(
+        storage,
+        level,
+        message,
+        Vec::new(),
+        max_message_length,
+        dedup_window_nanos,
+    )
+}
+
+pub fn store_log_message_with_fields(
+    storage: &mut dyn LogMessagesStorage,
+    level: LogLevel,
+    message: String,
+    fields: Vec<LogField>,
+    max_message_length: &usize,
+    dedup_window_nanos: &Option<u64>,
 ) {
     let time_nanos = ic_util::get_ic_time_nanos();
-    store_log_message_int(storage, message, max_message_length, time_nanos)
+    store_log_message_int(
+        storage,
+        level,
+        message,
+        fields,
+        max_message_length,
+        dedup_window_nanos,
+        time_nanos,
+    )
 }
 
 fn store_log_message_int(
     storage: &mut dyn LogMessagesStorage,
+    level: LogLevel,
     message: String,
+    fields: Vec<LogField>,
     max_message_length: &usize,
+    dedup_window_nanos: &Option<u64>,
     time_nanos: u64,
 ) {
     let time_nanos = match storage.get_last_log_message_time() {
@@ -27,43 +61,116 @@ fn store_log_message_int(
         }
     };
 
-    let message = validate_message(message, max_message_length);
+    let (message, original_length) = validate_message(message, max_message_length);
 
     let log_message = LogMessage {
         timeNanos: time_nanos,
         message,
+        level: Some(level),
+        fields: if fields.is_empty() {
+            None
+        } else {
+            Some(fields)
+        },
+        truncated: Some(original_length.is_some()),
+        originalLength: original_length,
+        repeatCount: None,
+        lastTimeNanos: None,
     };
 
+    if let Some(dedup_window_nanos) = dedup_window_nanos {
+        if let Some(repeated_message) =
+            collapse_repeated_message(storage, &log_message, dedup_window_nanos)
+        {
+            storage.replace_last_log_message(repeated_message);
+            return;
+        }
+    }
+
     storage.store_log_message(log_message);
 }
 
-fn validate_message(message: String, max_length: &usize) -> String {
-    if message.len() > *max_length {
-        String::from(&message[0..*max_length])
-    } else {
-        message
+/// Returns the last stored message with the repeat counted, if the new message repeats it within the window.
+fn collapse_repeated_message(
+    storage: &dyn LogMessagesStorage,
+    log_message: &LogMessage,
+    dedup_window_nanos: &u64,
+) -> Option<LogMessage> {
+    let last_message = storage.get_last_log_message()?;
+    let is_repeated = last_message.message == log_message.message
+        && last_message.level == log_message.level
+        && last_message.fields == log_message.fields
+        && last_message.originalLength == log_message.originalLength
+        && log_message.timeNanos - last_message.timeNanos <= *dedup_window_nanos;
+    if !is_repeated {
+        return None;
+    }
+
+    let last_message = last_message.into_owned();
+    Some(LogMessage {
+        repeatCount: Some(last_message.repeatCount.unwrap_or(1) + 1),
+        lastTimeNanos: Some(log_message.timeNanos),
+        ..last_message
+    })
+}
+
+/// Truncates the message to the length in bytes on a character boundary,
+/// returns the original length of the truncated message.
+fn validate_message(mut message: String, max_length: &usize) -> (String, Option<u64>) {
+    if message.len() <= *max_length {
+        return (message, None);
+    }
+
+    let original_length = message.len() as u64;
+    let mut length = *max_length;
+    while !message.is_char_boundary(length) {
+        length -= 1;
     }
+    message.truncate(length);
+    (message, Some(original_length))
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::super::api_type::LogLevel;
+    use super::super::super::ic_util::memory::VecMemory;
     use super::super::super::logger::collector::validate_message;
     use super::super::data_type::{LogMessage, LogMessagesStorage};
+    use super::super::store::stable::{StableStorage, MIN_CAPACITY_IN_BYTES};
+    use super::super::store::Storage;
     use crate::logger::data_type::LogMessagesInfo;
+    use std::borrow::Cow;
 
     #[test]
     fn test_validate_message() {
         assert_eq!(
             validate_message(String::from("abcd"), &5),
-            String::from("abcd")
+            (String::from("abcd"), None)
         );
         assert_eq!(
             validate_message(String::from("abcd"), &4),
-            String::from("abcd")
+            (String::from("abcd"), None)
         );
         assert_eq!(
             validate_message(String::from("abcd"), &3),
-            String::from("abc")
+            (String::from("abc"), Some(4))
+        );
+        // two bytes characters are not split
+        assert_eq!(
+            validate_message(String::from("сообщение"), &5),
+            (String::from("со"), Some(18))
+        );
+        assert_eq!(
+            validate_message(String::from("сообщение"), &4),
+            (String::from("со"), Some(18))
+        );
+        assert_eq!(
+            validate_message(String::from("a😀b"), &4),
+            (String::from("a"), Some(6))
+        );
+        assert_eq!(
+            validate_message(String::from("😀"), &3),
+            (String::new(), Some(4))
         );
     }
 
@@ -80,6 +187,14 @@ mod tests {
                 self.messages_count += 1;
             }
 
+            fn get_last_log_message(&self) -> Option<Cow<'_, LogMessage>> {
+                panic!()
+            }
+
+            fn replace_last_log_message(&mut self, _log_message: LogMessage) {
+                panic!()
+            }
+
             fn set_max_messages_count(&mut self, _new_max_messages_count: usize) {
                 panic!()
             }
@@ -101,6 +216,18 @@ mod tests {
                     Some(self.last_time)
                 }
             }
+
+            fn get_log_messages_bytes(&self) -> u64 {
+                panic!()
+            }
+
+            fn get_max_log_messages_bytes(&self) -> Option<u64> {
+                panic!()
+            }
+
+            fn get_compression_ratio(&self) -> Option<f64> {
+                panic!()
+            }
         }
 
         let mut storage = FakeStorage {
@@ -108,20 +235,112 @@ mod tests {
             messages_count: 0,
         };
 
-        super::store_log_message_int(&mut storage, String::from("message1"), &20, 23);
+        super::store_log_message_int(
+            &mut storage,
+            LogLevel::info,
+            String::from("message1"),
+            Vec::new(),
+            &20,
+            &None,
+            23,
+        );
         assert_eq!(storage.messages_count, 1);
         assert_eq!(storage.last_time, 23);
 
-        super::store_log_message_int(&mut storage, String::from("message2"), &20, 23);
+        super::store_log_message_int(
+            &mut storage,
+            LogLevel::info,
+            String::from("message2"),
+            Vec::new(),
+            &20,
+            &None,
+            23,
+        );
         assert_eq!(storage.messages_count, 2);
         assert_eq!(storage.last_time, 24);
 
-        super::store_log_message_int(&mut storage, String::from("message2"), &20, 21);
+        super::store_log_message_int(
+            &mut storage,
+            LogLevel::info,
+            String::from("message2"),
+            Vec::new(),
+            &20,
+            &None,
+            21,
+        );
         assert_eq!(storage.messages_count, 3);
         assert_eq!(storage.last_time, 25);
 
-        super::store_log_message_int(&mut storage, String::from("message2"), &20, 27);
+        super::store_log_message_int(
+            &mut storage,
+            LogLevel::info,
+            String::from("message2"),
+            Vec::new(),
+            &20,
+            &None,
+            27,
+        );
         assert_eq!(storage.messages_count, 4);
         assert_eq!(storage.last_time, 27);
     }
+
+    #[test]
+    fn test_dedup() {
+        let storages: Vec<Box<dyn LogMessagesStorage>> = vec![
+            Box::new(Storage::new(10)),
+            Box::new(
+                StableStorage::init(Box::new(VecMemory::default()), 10, MIN_CAPACITY_IN_BYTES)
+                    .unwrap(),
+            ),
+        ];
+        for mut storage in storages {
+            let store = |storage: &mut dyn LogMessagesStorage, message: &str, time_nanos| {
+                super::store_log_message_int(
+                    storage,
+                    LogLevel::error,
+                    String::from(message),
+                    Vec::new(),
+                    &20,
+                    &Some(100),
+                    time_nanos,
+                )
+            };
+            store(storage.as_mut(), "failed", 1000);
+            store(storage.as_mut(), "failed", 1050);
+            store(storage.as_mut(), "failed", 1100);
+            assert_eq!(storage.get_log_messages_count(), 1);
+            let last_message = storage.get_last_log_message().unwrap().into_owned();
+            assert_eq!(last_message.timeNanos, 1000);
+            assert_eq!(last_message.repeatCount, Some(3));
+            assert_eq!(last_message.lastTimeNanos, Some(1100));
+
+            // out of the window
+            store(storage.as_mut(), "failed", 1101);
+            assert_eq!(storage.get_log_messages_count(), 2);
+            assert_eq!(storage.get_last_log_message().unwrap().repeatCount, None);
+
+            // another level or text breaks the repetition
+            store(storage.as_mut(), "failed again", 1102);
+            store(storage.as_mut(), "failed", 1103);
+            assert_eq!(storage.get_log_messages_count(), 4);
+            super::store_log_message_int(
+                storage.as_mut(),
+                LogLevel::warn,
+                String::from("failed"),
+                Vec::new(),
+                &20,
+                &Some(100),
+                1104,
+            );
+            assert_eq!(storage.get_log_messages_count(), 5);
+
+            // messages are compared after the truncation
+            store(storage.as_mut(), "a long message which is truncated", 1105);
+            store(storage.as_mut(), "a long message which is truncated", 1106);
+            assert_eq!(storage.get_log_messages_count(), 6);
+            let last_message = storage.get_last_log_message().unwrap().into_owned();
+            assert_eq!(last_message.repeatCount, Some(2));
+            assert_eq!(last_message.truncated, Some(true));
+        }
+    }
 }
@@ -1,4 +1,5 @@
 use super::super::api_type::{LogMessageData, Nanos};
+use std::borrow::Cow;
 
 pub type LogMessage = LogMessageData;
 
@@ -8,22 +9,34 @@ pub trait LogMessagesInfo {
     fn get_first_log_message_time(&self) -> Option<Nanos>;
 
     fn get_last_log_message_time(&self) -> Option<Nanos>;
+
+    fn get_log_messages_bytes(&self) -> u64;
+
+    fn get_max_log_messages_bytes(&self) -> Option<u64>;
+
+    /// Total length of the stored texts divided by their compressed length.
+    fn get_compression_ratio(&self) -> Option<f64>;
 }
 
 pub trait LogMessagesSupplier: LogMessagesInfo {
     fn get_log_messages(
         &self,
         from_time_nanos: &Option<Nanos>,
-    ) -> Box<dyn Iterator<Item = &'_ LogMessage> + '_>;
+    ) -> Box<dyn Iterator<Item = Cow<'_, LogMessage>> + '_>;
 
     fn get_log_messages_reverse(
         &self,
         up_to_time_nanos: &Option<Nanos>,
-    ) -> Box<dyn Iterator<Item = &'_ LogMessage> + '_>;
+    ) -> Box<dyn Iterator<Item = Cow<'_, LogMessage>> + '_>;
 }
 
 pub trait LogMessagesStorage: LogMessagesInfo {
     fn store_log_message(&mut self, log_message: LogMessage);
 
+    fn get_last_log_message(&self) -> Option<Cow<'_, LogMessage>>;
+
+    /// Replaces the newest message, used to collapse repeated messages.
+    fn replace_last_log_message(&mut self, log_message: LogMessage);
+
     fn set_max_messages_count(&mut self, new_max_messages_count: usize);
 }
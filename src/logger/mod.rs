@@ -1,106 +1,650 @@
 mod calculator;
 mod collector;
 mod data_type;
+mod rate_limiter;
 mod store;
 
+use super::alert;
 use super::api_type::{
     CanisterLogFeature, CanisterLogMessagesInfo, CanisterLogRequest, CanisterLogResponse,
+    DroppedLogMessages, InformationError, LogChannelInfo, LogField, LogFieldValue, LogLevel, Nanos,
 };
-use data_type::LogMessagesStorage;
+use super::ic_util;
+use super::ic_util::memory::Memory;
+use super::instance;
+use super::migration::{self, MigrationError, MigrationReport, MigrationStep};
+use data_type::{LogMessagesInfo, LogMessagesStorage, LogMessagesSupplier};
+use rate_limiter::RateLimiter;
+pub use rate_limiter::{RateLimit, SOURCE_FIELD_NAME};
+use std::collections::BTreeMap;
+use store::stable::StableStorage;
 
 pub type LogMessageStorage = store::Storage;
 
-pub type PreUpgradeStableData<'a> = (&'a u8, &'a LogMessageStorage);
-pub type PostUpgradeStableData = (u8, LogMessageStorage);
+/// Heap storages of the named channels by their names.
+pub type LogChannels = BTreeMap<String, LogMessageStorage>;
 
-const VERSION: u8 = 1;
+// channels are absent in the data saved before they were introduced
+pub type PreUpgradeStableData<'a> = (&'a u8, &'a LogMessageStorage, Option<&'a LogChannels>);
+pub type PostUpgradeStableData = (u8, LogMessageStorage, Option<LogChannels>);
+
+// `MIGRATIONS[i]` upgrades the log messages of version `i + 1` to `i + 2`.
+const MIGRATIONS: [MigrationStep<LogMessageStorage>; 1] = [
+    // version 2 tracks the size of the stored messages
+    |mut storage| {
+        storage.count_bytes();
+        Ok(storage)
+    },
+];
+const VERSION: u8 = MIGRATIONS.len() as u8 + 1;
 
 const DEFAULT_MAX_LOG_MESSAGES_COUNT: usize = 10_000;
 const DEFAULT_MAX_LOG_MESSAGE_LENGTH: usize = 4096;
 
-static mut STORAGE: Option<LogMessageStorage> = None;
+/// Logger state of a `Canistergeek` instance, the free functions of this module
+/// work with the instance of the current thread.
+pub struct Logger {
+    storage: LogMessageStorage,
+    channels: LogChannels,
+    stable_storage: Option<StableStorage>,
+    min_log_level: LogLevel,
+    max_message_length: usize,
+    dedup_window_in_seconds: Option<u32>,
+    rate_limiter: RateLimiter,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self {
+            storage: LogMessageStorage::new(DEFAULT_MAX_LOG_MESSAGES_COUNT),
+            channels: LogChannels::new(),
+            stable_storage: None,
+            min_log_level: LogLevel::trace,
+            max_message_length: DEFAULT_MAX_LOG_MESSAGE_LENGTH,
+            dedup_window_in_seconds: None,
+            rate_limiter: RateLimiter::default(),
+        }
+    }
+}
+
+impl Logger {
+    fn messages_storage(&mut self) -> &mut dyn LogMessagesStorage {
+        match &mut self.stable_storage {
+            Some(stable_storage) => stable_storage,
+            None => &mut self.storage,
+        }
+    }
+
+    fn messages_supplier(&self) -> &dyn LogMessagesSupplier {
+        match &self.stable_storage {
+            Some(stable_storage) => stable_storage,
+            None => &self.storage,
+        }
+    }
+
+    fn channel_messages_supplier(
+        &self,
+        channel: &Option<String>,
+    ) -> Result<&dyn LogMessagesSupplier, InformationError> {
+        match channel {
+            Some(channel) => match self.channels.get(channel) {
+                Some(storage) => Ok(storage),
+                None => Err(InformationError::unknownChannel(channel.clone())),
+            },
+            None => Ok(self.messages_supplier()),
+        }
+    }
 
-fn get_storage<'a>() -> &'a mut LogMessageStorage {
-    unsafe {
-        if let Some(s) = &mut *std::ptr::addr_of_mut!(STORAGE) {
-            s
+    /// Moves messages restored into the heap storage to the stable one.
+    fn migrate_to_stable_storage(&mut self) {
+        if let Some(stable_storage) = &mut self.stable_storage {
+            if self.storage.get_log_messages_count() == 0 {
+                return;
+            }
+
+            let last_time_nanos = stable_storage.get_last_log_message_time();
+            for message in self.storage.get_log_messages(&last_time_nanos) {
+                stable_storage.store_log_message(message.into_owned());
+            }
+
+            self.storage = self.storage.create_empty();
+        }
+    }
+
+    /// See `logger::init_stable_storage`.
+    pub fn init_stable_storage(
+        &mut self,
+        memory: Box<dyn Memory>,
+        capacity_in_bytes: u64,
+    ) -> Result<(), &'static str> {
+        let stable_storage = StableStorage::init(
+            memory,
+            self.storage.get_max_messages_count(),
+            capacity_in_bytes,
+        )?;
+        self.stable_storage = Some(stable_storage);
+        self.migrate_to_stable_storage();
+        Ok(())
+    }
+
+    pub fn pre_upgrade_stable_data(&self) -> PreUpgradeStableData<'_> {
+        (&VERSION, &self.storage, Some(&self.channels))
+    }
+
+    /// See `logger::post_upgrade_stable_data`.
+    pub fn post_upgrade_stable_data(
+        &mut self,
+        (version, log_message_storage, channels): PostUpgradeStableData,
+    ) -> Result<MigrationReport, MigrationError> {
+        let (log_message_storage, report) =
+            migration::migrate("logger", version, log_message_storage, &MIGRATIONS)?;
+        let mut migrated_channels = LogChannels::new();
+        for (name, storage) in channels.unwrap_or_default() {
+            let (storage, _) = migration::migrate("logger channel", version, storage, &MIGRATIONS)?;
+            migrated_channels.insert(name, storage);
+        }
+        self.storage = log_message_storage;
+        self.channels = migrated_channels;
+        self.migrate_to_stable_storage();
+        Ok(report)
+    }
+
+    /// See `logger::set_channel`.
+    pub fn set_channel(&mut self, name: &str, max_messages_count: u32) -> Result<(), &'static str> {
+        if name.is_empty() {
+            return Err("Log channel name should not be empty");
+        }
+        if max_messages_count == 0 {
+            return Err("Log channel should keep messages");
+        }
+        match self.channels.get_mut(name) {
+            Some(storage) => storage.set_max_messages_count(max_messages_count as usize),
+            None => {
+                let storage = LogMessageStorage::new(max_messages_count as usize);
+                self.channels.insert(name.to_string(), storage);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the channel with its messages, returns `false` if there is no such channel.
+    pub fn remove_channel(&mut self, name: &str) -> bool {
+        self.channels.remove(name).is_some()
+    }
+
+    pub fn get_channel_names(&self) -> Vec<String> {
+        self.channels.keys().cloned().collect()
+    }
+
+    pub fn set_max_messages_count(&mut self, limit: u32) {
+        assert!(limit > 0);
+        self.storage.set_max_messages_count(limit as usize);
+        if let Some(stable_storage) = &mut self.stable_storage {
+            stable_storage.set_max_messages_count(limit as usize);
+        }
+    }
+
+    /// See `logger::set_max_messages_bytes`.
+    pub fn set_max_messages_bytes(&mut self, max_bytes: Option<u64>) {
+        self.storage.set_max_bytes(max_bytes);
+    }
+
+    /// See `logger::set_messages_compression`.
+    pub fn set_messages_compression(&mut self, enabled: bool) {
+        self.storage.set_compression(enabled);
+    }
+
+    /// See `logger::set_min_log_level`.
+    pub fn set_min_log_level(&mut self, level: LogLevel) {
+        self.min_log_level = level;
+    }
+
+    pub fn get_min_log_level(&self) -> LogLevel {
+        self.min_log_level
+    }
+
+    /// See `logger::set_max_message_length`.
+    pub fn set_max_message_length(&mut self, max_length: u32) {
+        assert!(max_length > 0);
+        self.max_message_length = max_length as usize;
+    }
+
+    pub fn get_max_message_length(&self) -> u32 {
+        self.max_message_length as u32
+    }
+
+    /// See `logger::set_dedup_window`.
+    pub fn set_dedup_window(&mut self, window_in_seconds: Option<u32>) {
+        self.dedup_window_in_seconds = window_in_seconds;
+    }
+
+    pub fn get_dedup_window(&self) -> Option<u32> {
+        self.dedup_window_in_seconds
+    }
+
+    fn get_dedup_window_nanos(&self) -> Option<u64> {
+        self.dedup_window_in_seconds
+            .map(|window| window as u64 * 1_000_000_000)
+    }
+
+    /// See `logger::set_rate_limits`.
+    pub fn set_rate_limits(&mut self, limits: Vec<RateLimit>) -> Result<(), &'static str> {
+        self.rate_limiter
+            .set_limits(limits, ic_util::get_ic_time_nanos())
+    }
+
+    pub fn get_rate_limits(&self) -> Vec<RateLimit> {
+        self.rate_limiter.get_limits()
+    }
+
+    /// Checks the rate limit of the message source, the drop summaries are written before.
+    fn is_rate_limited(&mut self, fields: &[LogField], channel: Option<&str>) -> bool {
+        if !self.rate_limiter.has_limits() {
+            return false;
+        }
+        let time_nanos = ic_util::get_ic_time_nanos();
+        self.store_drop_summaries(time_nanos);
+        !self
+            .rate_limiter
+            .allow(rate_limiter::get_source(fields), channel, time_nanos)
+    }
+
+    /// Writes the summaries of the sources allowed again, so drops of sources which went quiet
+    /// are reported too. Called when metrics are collected.
+    pub(crate) fn flush_drop_summaries(&mut self) {
+        if self.rate_limiter.has_dropped_messages() {
+            self.store_drop_summaries(ic_util::get_ic_time_nanos());
+        }
+    }
+
+    /// Writes the summaries to the channels the messages were dropped in.
+    fn store_drop_summaries(&mut self, time_nanos: Nanos) {
+        let max_message_length = self.max_message_length;
+        for summary in self.rate_limiter.take_drop_summaries(time_nanos) {
+            let storage = match summary
+                .channel
+                .and_then(|channel| self.channels.get_mut(&channel))
+            {
+                Some(storage) => storage,
+                None => self.messages_storage(),
+            };
+            collector::store_log_message_with_fields(
+                storage,
+                LogLevel::warn,
+                format!("dropped {} messages from {}", summary.count, summary.source),
+                vec![LogField {
+                    name: SOURCE_FIELD_NAME.to_string(),
+                    value: LogFieldValue::text(summary.source),
+                }],
+                &max_message_length,
+                &None,
+            );
+        }
+    }
+
+    /// Returns `false` if the message is dropped by the minimal level or the rate limit.
+    pub(crate) fn log(&mut self, level: LogLevel, message: String) -> bool {
+        if level < self.min_log_level || self.is_rate_limited(&[], None) {
+            return false;
+        }
+
+        let max_message_length = self.max_message_length;
+        let dedup_window_nanos = self.get_dedup_window_nanos();
+        collector::store_log_message(
+            self.messages_storage(),
+            level,
+            message,
+            &max_message_length,
+            &dedup_window_nanos,
+        );
+        true
+    }
+
+    pub(crate) fn log_with_fields(
+        &mut self,
+        level: LogLevel,
+        message: String,
+        fields: Vec<LogField>,
+    ) -> bool {
+        if level < self.min_log_level || self.is_rate_limited(&fields, None) {
+            return false;
+        }
+
+        let max_message_length = self.max_message_length;
+        let dedup_window_nanos = self.get_dedup_window_nanos();
+        collector::store_log_message_with_fields(
+            self.messages_storage(),
+            level,
+            message,
+            fields,
+            &max_message_length,
+            &dedup_window_nanos,
+        );
+        true
+    }
+
+    /// Like `log_with_fields`, but stores the message in the named channel.
+    pub(crate) fn log_to_channel(
+        &mut self,
+        channel: &str,
+        level: LogLevel,
+        message: String,
+        fields: Vec<LogField>,
+    ) -> Result<bool, &'static str> {
+        if !self.channels.contains_key(channel) {
+            return Err("Unknown log channel");
+        }
+        if level < self.min_log_level || self.is_rate_limited(&fields, Some(channel)) {
+            return Ok(false);
+        }
+
+        let max_message_length = self.max_message_length;
+        let dedup_window_nanos = self.get_dedup_window_nanos();
+        if let Some(storage) = self.channels.get_mut(channel) {
+            collector::store_log_message_with_fields(
+                storage,
+                level,
+                message,
+                fields,
+                &max_message_length,
+                &dedup_window_nanos,
+            );
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn get_log_messages_info(&self) -> CanisterLogMessagesInfo {
+        let info = calculator::get_log_messages_info(self.messages_supplier());
+        let dropped_messages = if self.rate_limiter.has_limits() {
+            let dropped_messages = self
+                .rate_limiter
+                .get_dropped_counts()
+                .into_iter()
+                .map(|(source, count)| DroppedLogMessages { source, count })
+                .collect();
+            Some(dropped_messages)
+        } else {
+            None
+        };
+        let channels = if self.channels.is_empty() {
+            None
         } else {
-            STORAGE = Some(LogMessageStorage::new(DEFAULT_MAX_LOG_MESSAGES_COUNT));
-            get_storage()
+            let channels = self
+                .channels
+                .iter()
+                .map(|(name, storage)| LogChannelInfo {
+                    name: name.clone(),
+                    count: storage.get_log_messages_count(),
+                    maxCount: storage.get_max_messages_count() as u32,
+                    firstTimeNanos: storage.get_first_log_message_time(),
+                    lastTimeNanos: storage.get_last_log_message_time(),
+                })
+                .collect();
+            Some(channels)
+        };
+        CanisterLogMessagesInfo {
+            droppedMessages: dropped_messages,
+            channels,
+            ..info
+        }
+    }
+
+    pub(crate) fn get_log_level_counts(&self) -> Vec<(LogLevel, u32)> {
+        calculator::get_log_level_counts(self.messages_supplier())
+    }
+
+    pub fn get_canister_log(
+        &self,
+        request: Option<CanisterLogRequest>,
+    ) -> Option<CanisterLogResponse<'_>> {
+        request.and_then(|request| self.try_get_canister_log(request).ok())
+    }
+
+    /// Like `get_canister_log`, but tells why the request is rejected.
+    pub fn try_get_canister_log(
+        &self,
+        request: CanisterLogRequest,
+    ) -> Result<CanisterLogResponse<'_>, InformationError> {
+        match request {
+            CanisterLogRequest::getMessagesInfo => {
+                let info = self.get_log_messages_info();
+                let mut features = vec![
+                    Some(CanisterLogFeature::filterMessageByContains),
+                    Some(CanisterLogFeature::filterMessageByLevel),
+                    Some(CanisterLogFeature::filterMessageByFields),
+                    Some(CanisterLogFeature::filterMessageByExpression),
+                ];
+                if cfg!(feature = "regex") {
+                    features.push(Some(CanisterLogFeature::filterMessageByRegex));
+                }
+
+                Ok(CanisterLogResponse::messagesInfo(CanisterLogMessagesInfo {
+                    features,
+                    ..info
+                }))
+            }
+            CanisterLogRequest::getMessages(parameters) => {
+                let supplier = self.channel_messages_supplier(&parameters.channel)?;
+                calculator::get_log_messages(supplier, parameters)
+                    .map(CanisterLogResponse::messages)
+            }
+            CanisterLogRequest::getLatestMessages(parameters) => {
+                let supplier = self.channel_messages_supplier(&parameters.channel)?;
+                calculator::get_latest_log_messages(supplier, parameters)
+                    .map(CanisterLogResponse::messages)
+            }
         }
     }
 }
 
 // API
 
-pub fn pre_upgrade_stable_data<'a>() -> PreUpgradeStableData<'a> {
-    (&VERSION, get_storage())
+/// Switches logger to the storage which keeps messages directly in the passed memory
+/// (a region of the stable memory), so upgrades need no messages serialization.
+///
+/// Has to be called in `init` and `post_upgrade` hooks: the messages stored in the memory before are reopened,
+/// `capacity_in_bytes` is used only when the storage is created.
+/// Messages collected with the heap storage are moved to the stable one.
+pub fn init_stable_storage(
+    memory: Box<dyn Memory>,
+    capacity_in_bytes: u64,
+) -> Result<(), &'static str> {
+    instance::with_mut(|canistergeek| {
+        canistergeek
+            .logger_mut()
+            .init_stable_storage(memory, capacity_in_bytes)
+    })
 }
 
-pub fn post_upgrade_stable_data(data: PostUpgradeStableData) {
-    match data {
-        (VERSION, log_message_storage) => unsafe {
-            STORAGE = Some(log_message_storage);
-        },
-        _ => {
-            ic_cdk::api::debug_print(std::format!(
-                "Can not upgrade stable log messages data. Unsupported version {}",
-                data.0
-            ));
-        }
-    }
+/// Returns a copy of the messages kept in the heap storages.
+pub fn pre_upgrade_stable_data() -> PostUpgradeStableData {
+    with_pre_upgrade_stable_data(|(version, log_message_storage, channels)| {
+        (*version, log_message_storage.clone(), channels.cloned())
+    })
+}
+
+/// Like `pre_upgrade_stable_data`, but runs the function with the messages borrowed, so they are saved
+/// in `pre_upgrade` without copying: `with_pre_upgrade_stable_data(|data| ic_cdk::storage::stable_save((data,)))`.
+pub fn with_pre_upgrade_stable_data<R>(f: impl FnOnce(PreUpgradeStableData<'_>) -> R) -> R {
+    instance::with(|canistergeek| f(canistergeek.logger().pre_upgrade_stable_data()))
+}
+
+/// Restores the messages saved by `pre_upgrade_stable_data` of this or previous versions.
+/// Nothing is restored on error, trap to roll the upgrade back and keep the messages.
+pub fn post_upgrade_stable_data(
+    upgrade_data: PostUpgradeStableData,
+) -> Result<MigrationReport, MigrationError> {
+    instance::with_mut(|canistergeek| {
+        canistergeek
+            .logger_mut()
+            .post_upgrade_stable_data(upgrade_data)
+    })
 }
 
 pub fn set_max_messages_count(limit: u32) {
-    assert!(limit > 0);
-    get_storage().set_max_messages_count(limit as usize);
+    instance::with_mut(|canistergeek| canistergeek.logger_mut().set_max_messages_count(limit));
+}
+
+/// Limits the total size of the messages kept in the heap, the oldest messages are evicted
+/// to fit a new one. The limit is persisted with the messages, `None` removes it.
+/// The stable storage is limited by its capacity instead.
+pub fn set_max_messages_bytes(max_bytes: Option<u64>) {
+    instance::with_mut(|canistergeek| canistergeek.logger_mut().set_max_messages_bytes(max_bytes));
+}
+
+/// Keeps texts of new messages in the heap compressed against a dictionary of the first stored texts
+/// (up to 1Kb, not counted in the size of messages), they are decoded when read.
+/// The mode is persisted with the messages, the stable storage is not compressed.
+pub fn set_messages_compression(enabled: bool) {
+    instance::with_mut(|canistergeek| canistergeek.logger_mut().set_messages_compression(enabled));
+}
+
+/// Messages with a level below the minimal one are dropped without being stored.
+/// The level is not persisted, so it should be set in `init` and `post_upgrade`.
+pub fn set_min_log_level(level: LogLevel) {
+    instance::with_mut(|canistergeek| canistergeek.logger_mut().set_min_log_level(level));
+}
+
+pub fn get_min_log_level() -> LogLevel {
+    instance::with(|canistergeek| canistergeek.logger().get_min_log_level())
+}
+
+/// Longer messages are truncated to the length in bytes (on a character boundary) and marked as truncated,
+/// the default is 4096 bytes. The length is not persisted, so it should be set in `init` and `post_upgrade`.
+pub fn set_max_message_length(max_length: u32) {
+    instance::with_mut(|canistergeek| canistergeek.logger_mut().set_max_message_length(max_length));
+}
+
+pub fn get_max_message_length() -> u32 {
+    instance::with(|canistergeek| canistergeek.logger().get_max_message_length())
+}
+
+/// Collapses identical messages (text, level and fields) logged one after another within the window
+/// from the first of them into one entry with `repeatCount` and `lastTimeNanos`, `None` disables it.
+/// The window is not persisted, so it should be set in `init` and `post_upgrade`.
+pub fn set_dedup_window(window_in_seconds: Option<u32>) {
+    instance::with_mut(|canistergeek| {
+        canistergeek
+            .logger_mut()
+            .set_dedup_window(window_in_seconds)
+    });
+}
+
+pub fn get_dedup_window() -> Option<u32> {
+    instance::with(|canistergeek| canistergeek.logger().get_dedup_window())
+}
+
+/// Replaces the rate limits of log sources, the source of a message is its `source` text field.
+/// Dropped messages are counted in `getMessagesInfo` and, once the source is allowed again,
+/// written as one `warn` entry "dropped N messages from <source>".
+/// Limits and counts are not persisted, so the limits should be set in `init` and `post_upgrade`.
+pub fn set_rate_limits(limits: Vec<RateLimit>) -> Result<(), &'static str> {
+    instance::with_mut(|canistergeek| canistergeek.logger_mut().set_rate_limits(limits))
+}
+
+pub fn get_rate_limits() -> Vec<RateLimit> {
+    instance::with(|canistergeek| canistergeek.logger().get_rate_limits())
+}
+
+/// Creates the named channel with its own ring of messages, or changes the capacity of an existing one.
+/// Channels and their messages are persisted by `pre_upgrade_stable_data` in the heap,
+/// the stable memory storage keeps the messages of the default channel only.
+pub fn set_channel(name: &str, max_messages_count: u32) -> Result<(), &'static str> {
+    instance::with_mut(|canistergeek| {
+        canistergeek
+            .logger_mut()
+            .set_channel(name, max_messages_count)
+    })
+}
+
+pub fn remove_channel(name: &str) -> bool {
+    instance::with_mut(|canistergeek| canistergeek.logger_mut().remove_channel(name))
+}
+
+pub fn get_channel_names() -> Vec<String> {
+    instance::with(|canistergeek| canistergeek.logger().get_channel_names())
 }
 
 pub fn log_message(message: String) {
-    collector::store_log_message(get_storage(), message, &DEFAULT_MAX_LOG_MESSAGE_LENGTH);
+    log(LogLevel::info, message);
+}
+
+pub fn log_debug(message: String) {
+    log(LogLevel::debug, message);
+}
+
+pub fn log_info(message: String) {
+    log(LogLevel::info, message);
+}
+
+pub fn log_warn(message: String) {
+    log(LogLevel::warn, message);
+}
+
+pub fn log_error(message: String) {
+    log(LogLevel::error, message);
+}
+
+pub fn log(level: LogLevel, message: String) {
+    let events = instance::with_mut(|canistergeek| canistergeek.log(level, message));
+    alert::notify(&events);
+}
+
+/// Stores message together with typed key/value fields,
+/// which can be matched later by `fieldsEqual` filter option.
+pub fn log_with_fields(level: LogLevel, message: String, fields: Vec<LogField>) {
+    let events =
+        instance::with_mut(|canistergeek| canistergeek.log_with_fields(level, message, fields));
+    alert::notify(&events);
+}
+
+/// Stores the message in the named channel created by `set_channel`.
+pub fn log_to_channel(
+    channel: &str,
+    level: LogLevel,
+    message: String,
+    fields: Vec<LogField>,
+) -> Result<(), &'static str> {
+    let events = instance::with_mut(|canistergeek| {
+        canistergeek.log_to_channel(channel, level, message, fields)
+    })?;
+    alert::notify(&events);
+    Ok(())
 }
 
 pub fn get_canister_log<'a>(
     request: Option<CanisterLogRequest>,
 ) -> Option<CanisterLogResponse<'a>> {
-    match request {
-        Some(CanisterLogRequest::getMessagesInfo) => {
-            let info = calculator::get_log_messages_info(get_storage());
-            let features = vec![
-                Some(CanisterLogFeature::filterMessageByContains),
-                // Some(CanisterLogFeature::filterMessageByRegex),
-            ];
-
-            Some(CanisterLogResponse::messagesInfo(CanisterLogMessagesInfo {
-                features,
-                ..info
-            }))
-        }
-        Some(CanisterLogRequest::getMessages(parameters)) => {
-            match calculator::get_log_messages(get_storage(), parameters) {
-                Err(_) => None,
-                Ok(messages) => Some(CanisterLogResponse::messages(messages)),
-            }
-        }
-        Some(CanisterLogRequest::getLatestMessages(parameters)) => {
-            match calculator::get_latest_log_messages(get_storage(), parameters) {
-                Err(_) => None,
-                Ok(messages) => Some(CanisterLogResponse::messages(messages)),
-            }
-        }
-        None => None,
-    }
+    request.and_then(|request| try_get_canister_log(request).ok())
+}
+
+/// Like `get_canister_log`, but tells why the request is rejected.
+pub fn try_get_canister_log<'a>(
+    request: CanisterLogRequest,
+) -> Result<CanisterLogResponse<'a>, InformationError> {
+    instance::with(|canistergeek| {
+        canistergeek
+            .logger()
+            .try_get_canister_log(request)
+            .map(CanisterLogResponse::into_owned)
+    })
 }
 
 #[cfg(test)]
+#[allow(clippy::get_first)]
 mod tests {
     use super::super::api_type::{
-        GetLatestLogMessagesParameters, GetLogMessagesFilter, GetLogMessagesParameters,
+        CanisterLogRequest, CanisterLogResponse, GetLatestLogMessagesParameters,
+        GetLogMessagesFilter, GetLogMessagesParameters, InformationError, LogField, LogFieldValue,
+        LogFilterExpression, LogLevel,
     };
+    use super::super::ic_util::memory::VecMemory;
     use super::super::logger::calculator;
     use super::super::logger::collector;
-    use super::super::logger::data_type::LogMessagesInfo;
+    use super::super::logger::data_type::{LogMessagesInfo, LogMessagesSupplier};
+    use super::super::logger::store::stable::MIN_CAPACITY_IN_BYTES;
     use super::super::logger::store::Storage;
+    use candid::{Nat, Principal};
 
     #[test]
     fn test_empty_log_messages() {
@@ -110,26 +654,63 @@ mod tests {
             count: 10,
             filter: None,
             upToTimeNanos: None,
+            channel: None,
         };
 
         let result = calculator::get_latest_log_messages(&storage, params);
         let messages = result.expect("must zero elements");
         assert_eq!(messages.data.len(), 0);
+
+        let params = GetLatestLogMessagesParameters {
+            count: 0,
+            filter: None,
+            upToTimeNanos: None,
+            channel: None,
+        };
+        assert_eq!(
+            calculator::get_latest_log_messages(&storage, params).unwrap_err(),
+            InformationError::invalidCount { maxCount: 1024 }
+        );
     }
 
     #[test]
     fn test_chunk_log_messages() {
         let mut storage = Storage::new(4);
 
-        collector::store_log_message(&mut storage, String::from("1 message"), &10);
-        collector::store_log_message(&mut storage, String::from("2 message"), &10);
-        collector::store_log_message(&mut storage, String::from("3 message"), &10);
-        collector::store_log_message(&mut storage, String::from("4 message"), &3);
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("1 message"),
+            &10,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("2 message"),
+            &10,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("3 message"),
+            &10,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("4 message"),
+            &3,
+            &None,
+        );
 
         let params = GetLatestLogMessagesParameters {
             count: 10,
             filter: None,
             upToTimeNanos: None,
+            channel: None,
         };
 
         let result = calculator::get_latest_log_messages(&storage, params).unwrap();
@@ -149,6 +730,7 @@ mod tests {
             count: 2,
             filter: None,
             upToTimeNanos: None,
+            channel: None,
         };
 
         let result = calculator::get_latest_log_messages(&storage, params).unwrap();
@@ -166,6 +748,7 @@ mod tests {
             count: 1,
             filter: None,
             upToTimeNanos: Some(messages.get(1).unwrap().timeNanos),
+            channel: None,
         };
 
         let result = calculator::get_latest_log_messages(&storage, params).unwrap();
@@ -179,147 +762,249 @@ mod tests {
         assert_eq!(messages.get(0).unwrap().message, "2 message");
     }
 
-    // #[test]
-    // fn test_filter_log_messages_by_regex() {
-    //     let mut storage = Storage::new(4);
-    //
-    //     collector::store_log_message(&mut storage, String::from("message 1"), &1024);
-    //     collector::store_log_message(&mut storage, String::from("сообщение abc 2 "), &1024);
-    //     collector::store_log_message(&mut storage, String::from("message abc 3"), &1024);
-    //     collector::store_log_message(&mut storage, String::from("message 4"), &10);
-    //
-    //     let params = GetLogMessagesParameters {
-    //         count: 4,
-    //         filter: None,
-    //         fromTimeNanos: None,
-    //     };
-    //
-    //     let result = calculator::get_log_messages(&storage, params).unwrap();
-    //     let messages = result.data;
-    //     assert_eq!(messages.len(), 4);
-    //     assert_eq!(
-    //         result.lastAnalyzedMessageTimeNanos.unwrap(),
-    //         messages.get(3).unwrap().timeNanos
-    //     );
-    //
-    //     let message1 = messages.get(0).unwrap();
-    //     let message2 = messages.get(1).unwrap();
-    //     let message3 = messages.get(2).unwrap();
-    //     let message4 = messages.get(3).unwrap();
-    //     assert_eq!(message1.message, "message 1");
-    //     assert_eq!(message2.message, "сообщение abc 2 ");
-    //     assert_eq!(message3.message, "message abc 3");
-    //     assert_eq!(message4.message, "message 4");
-    //
-    //     let params = GetLatestLogMessagesParameters {
-    //         count: 1,
-    //         filter: Some(GetLogMessagesFilter {
-    //             messageRegex: Some(String::from("abc")),
-    //             messageContains: None,
-    //             analyzeCount: 10,
-    //         }),
-    //         upToTimeNanos: None,
-    //     };
-    //
-    //     let result = calculator::get_latest_log_messages(&storage, params).unwrap();
-    //     let messages = result.data;
-    //     assert_eq!(messages.len(), 1);
-    //     assert_eq!(
-    //         result.lastAnalyzedMessageTimeNanos.unwrap(),
-    //         messages.get(0).unwrap().timeNanos
-    //     );
-    //
-    //     assert_eq!(messages.get(0).unwrap().message, "message abc 3");
-    //
-    //     let params = GetLatestLogMessagesParameters {
-    //         count: 10,
-    //         filter: Some(GetLogMessagesFilter {
-    //             messageRegex: Some(String::from("abc")),
-    //             messageContains: None,
-    //             analyzeCount: 10,
-    //         }),
-    //         upToTimeNanos: None,
-    //     };
-    //
-    //     let result = calculator::get_latest_log_messages(&storage, params).unwrap();
-    //     let messages = result.data;
-    //     assert_eq!(messages.len(), 2);
-    //     assert_eq!(
-    //         result.lastAnalyzedMessageTimeNanos.unwrap(),
-    //         message1.timeNanos
-    //     );
-    //
-    //     assert_eq!(messages.get(0).unwrap().message, "message abc 3");
-    //     assert_eq!(messages.get(1).unwrap().message, "сообщение abc 2 ");
-    //
-    //     let params = GetLatestLogMessagesParameters {
-    //         count: 10,
-    //         filter: Some(GetLogMessagesFilter {
-    //             messageRegex: Some(String::from("abc")),
-    //             messageContains: None,
-    //             analyzeCount: 10,
-    //         }),
-    //         upToTimeNanos: Some(messages.get(0).unwrap().timeNanos),
-    //     };
-    //
-    //     let result = calculator::get_latest_log_messages(&storage, params).unwrap();
-    //     let messages = result.data;
-    //     assert_eq!(messages.len(), 1);
-    //     assert_eq!(
-    //         result.lastAnalyzedMessageTimeNanos.unwrap(),
-    //         message1.timeNanos
-    //     );
-    //
-    //     assert_eq!(messages.get(0).unwrap().message, "сообщение abc 2 ");
-    //
-    //     let params = GetLatestLogMessagesParameters {
-    //         count: 10,
-    //         filter: Some(GetLogMessagesFilter {
-    //             messageRegex: Some(String::from("mess.*")),
-    //             messageContains: Some(String::from("abC")),
-    //             analyzeCount: 3,
-    //         }),
-    //         upToTimeNanos: None,
-    //     };
-    //
-    //     let result = calculator::get_latest_log_messages(&storage, params).unwrap();
-    //     let messages = result.data;
-    //     assert_eq!(messages.len(), 2);
-    //     assert_eq!(
-    //         result.lastAnalyzedMessageTimeNanos.unwrap(),
-    //         message2.timeNanos
-    //     );
-    //
-    //     assert_eq!(messages.get(0).unwrap().message, "message 4");
-    //     assert_eq!(messages.get(1).unwrap().message, "message abc 3");
-    //
-    //     let params = GetLatestLogMessagesParameters {
-    //         count: 10,
-    //         filter: Some(GetLogMessagesFilter {
-    //             messageRegex: None,
-    //             messageContains: None,
-    //             analyzeCount: 3,
-    //         }),
-    //         upToTimeNanos: None,
-    //     };
-    //
-    //     let result = calculator::get_latest_log_messages(&storage, params);
-    //     assert_eq!(result.is_err(), true);
-    // }
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_filter_log_messages_by_regex() {
+        let mut storage = Storage::new(4);
+
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("message 1"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("сообщение abc 2 "),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("message abc 3"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("message 4"),
+            &10,
+            &None,
+        );
+
+        let params = GetLogMessagesParameters {
+            count: 4,
+            filter: None,
+            fromTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_log_messages(&storage, params).unwrap();
+        let messages = result.data;
+        assert_eq!(messages.len(), 4);
+        assert_eq!(
+            result.lastAnalyzedMessageTimeNanos.unwrap(),
+            messages.get(3).unwrap().timeNanos
+        );
+
+        let message1 = messages.get(0).unwrap();
+        let message2 = messages.get(1).unwrap();
+        let message3 = messages.get(2).unwrap();
+        let message4 = messages.get(3).unwrap();
+        assert_eq!(message1.message, "message 1");
+        assert_eq!(message2.message, "сообщение abc 2 ");
+        assert_eq!(message3.message, "message abc 3");
+        assert_eq!(message4.message, "message 4");
+
+        let params = GetLatestLogMessagesParameters {
+            count: 1,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: Some(String::from("abc")),
+                messageContains: None,
+                analyzeCount: 10,
+                minLevel: None,
+                fieldsEqual: None,
+                expression: None,
+            }),
+            upToTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_latest_log_messages(&storage, params).unwrap();
+        let messages = result.data;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            result.lastAnalyzedMessageTimeNanos.unwrap(),
+            messages.get(0).unwrap().timeNanos
+        );
+
+        assert_eq!(messages.get(0).unwrap().message, "message abc 3");
+
+        let params = GetLatestLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: Some(String::from("abc")),
+                messageContains: None,
+                analyzeCount: 10,
+                minLevel: None,
+                fieldsEqual: None,
+                expression: None,
+            }),
+            upToTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_latest_log_messages(&storage, params).unwrap();
+        let messages = result.data;
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            result.lastAnalyzedMessageTimeNanos.unwrap(),
+            message1.timeNanos
+        );
+
+        assert_eq!(messages.get(0).unwrap().message, "message abc 3");
+        assert_eq!(messages.get(1).unwrap().message, "сообщение abc 2 ");
+
+        let params = GetLatestLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: Some(String::from("abc")),
+                messageContains: None,
+                analyzeCount: 10,
+                minLevel: None,
+                fieldsEqual: None,
+                expression: None,
+            }),
+            upToTimeNanos: Some(messages.get(0).unwrap().timeNanos),
+            channel: None,
+        };
+
+        let result = calculator::get_latest_log_messages(&storage, params).unwrap();
+        let messages = result.data;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            result.lastAnalyzedMessageTimeNanos.unwrap(),
+            message1.timeNanos
+        );
+
+        assert_eq!(messages.get(0).unwrap().message, "сообщение abc 2 ");
+
+        let params = GetLatestLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: Some(String::from("mess.*")),
+                messageContains: Some(String::from("abC")),
+                analyzeCount: 3,
+                minLevel: None,
+                fieldsEqual: None,
+                expression: None,
+            }),
+            upToTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_latest_log_messages(&storage, params).unwrap();
+        let messages = result.data;
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            result.lastAnalyzedMessageTimeNanos.unwrap(),
+            message2.timeNanos
+        );
+
+        assert_eq!(messages.get(0).unwrap().message, "message 4");
+        assert_eq!(messages.get(1).unwrap().message, "message abc 3");
+
+        let params = GetLatestLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: None,
+                messageContains: None,
+                analyzeCount: 3,
+                minLevel: None,
+                fieldsEqual: None,
+                expression: None,
+            }),
+            upToTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_latest_log_messages(&storage, params);
+        assert!(result.is_err());
+        assert!(matches!(
+            result,
+            Err(InformationError::unsupportedFilter(_))
+        ));
+    }
+
+    #[cfg(not(feature = "regex"))]
+    #[test]
+    fn test_filter_log_messages_by_regex_not_supported() {
+        let storage = Storage::new(4);
+
+        // the regex is not replaced by the contains option
+        let params = GetLatestLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: Some(String::from("mess.*")),
+                messageContains: Some(String::from("abC")),
+                analyzeCount: 3,
+                minLevel: None,
+                fieldsEqual: None,
+                expression: None,
+            }),
+            upToTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_latest_log_messages(&storage, params);
+        assert!(matches!(
+            result,
+            Err(InformationError::unsupportedFilter(_))
+        ));
+    }
 
     #[test]
     fn test_filter_log_messages_by_contains() {
         let mut storage = Storage::new(4);
 
-        collector::store_log_message(&mut storage, String::from("meSSage 1"), &1024);
-        collector::store_log_message(&mut storage, String::from("сообщение Abc 2 "), &1024);
-        collector::store_log_message(&mut storage, String::from("MEssage aBc 3"), &1024);
-        collector::store_log_message(&mut storage, String::from("messaGE 4"), &10);
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("meSSage 1"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("сообщение Abc 2 "),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("MEssage aBc 3"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("messaGE 4"),
+            &10,
+            &None,
+        );
 
         let params = GetLogMessagesParameters {
             count: 4,
             filter: None,
             fromTimeNanos: None,
+            channel: None,
         };
 
         let result = calculator::get_log_messages(&storage, params).unwrap();
@@ -345,8 +1030,12 @@ mod tests {
                 messageRegex: None,
                 messageContains: Some(String::from("abC")),
                 analyzeCount: 10,
+                minLevel: None,
+                fieldsEqual: None,
+                expression: None,
             }),
             upToTimeNanos: None,
+            channel: None,
         };
 
         let result = calculator::get_latest_log_messages(&storage, params).unwrap();
@@ -365,8 +1054,12 @@ mod tests {
                 messageRegex: None,
                 messageContains: Some(String::from("abC")),
                 analyzeCount: 10,
+                minLevel: None,
+                fieldsEqual: None,
+                expression: None,
             }),
             upToTimeNanos: None,
+            channel: None,
         };
 
         let result = calculator::get_latest_log_messages(&storage, params).unwrap();
@@ -386,8 +1079,12 @@ mod tests {
                 messageRegex: None,
                 messageContains: Some(String::from("abC")),
                 analyzeCount: 10,
+                minLevel: None,
+                fieldsEqual: None,
+                expression: None,
             }),
             upToTimeNanos: Some(messages.get(0).unwrap().timeNanos),
+            channel: None,
         };
 
         let result = calculator::get_latest_log_messages(&storage, params).unwrap();
@@ -401,18 +1098,680 @@ mod tests {
         assert_eq!(messages.get(0).unwrap().message, message2.message);
     }
 
+    #[test]
+    fn test_filter_log_messages_by_level() {
+        let mut storage = Storage::new(5);
+
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::debug,
+            String::from("transfer started"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::error,
+            String::from("transfer failed"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("transfer retried"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::warn,
+            String::from("balance is low"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::trace,
+            String::from("transfer done"),
+            &1024,
+            &None,
+        );
+
+        let params = GetLatestLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: None,
+                messageContains: None,
+                analyzeCount: 10,
+                minLevel: Some(LogLevel::warn),
+                fieldsEqual: None,
+                expression: None,
+            }),
+            upToTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_latest_log_messages(&storage, params).unwrap();
+        let messages = result.data;
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages.get(0).unwrap().message, "balance is low");
+        assert_eq!(messages.get(0).unwrap().level, Some(LogLevel::warn));
+        assert_eq!(messages.get(1).unwrap().message, "transfer failed");
+        assert_eq!(messages.get(1).unwrap().level, Some(LogLevel::error));
+
+        let params = GetLatestLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: None,
+                messageContains: Some(String::from("transfer")),
+                analyzeCount: 10,
+                minLevel: Some(LogLevel::info),
+                fieldsEqual: None,
+                expression: None,
+            }),
+            upToTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_latest_log_messages(&storage, params).unwrap();
+        let messages = result.data;
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages.get(0).unwrap().message, "transfer retried");
+        assert_eq!(messages.get(0).unwrap().level, Some(LogLevel::info));
+        assert_eq!(messages.get(1).unwrap().message, "transfer failed");
+
+        let params = GetLatestLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: None,
+                messageContains: Some(String::from("transfer")),
+                analyzeCount: 2,
+                minLevel: Some(LogLevel::info),
+                fieldsEqual: None,
+                expression: None,
+            }),
+            upToTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_latest_log_messages(&storage, params).unwrap();
+        let messages = result.data;
+        assert_eq!(messages.len(), 0);
+        assert_eq!(
+            result.lastAnalyzedMessageTimeNanos.unwrap(),
+            storage.get_log_messages(&None).nth(3).unwrap().timeNanos
+        );
+
+        assert_eq!(
+            calculator::get_log_level_counts(&storage),
+            vec![
+                (LogLevel::trace, 1),
+                (LogLevel::debug, 1),
+                (LogLevel::info, 1),
+                (LogLevel::warn, 1),
+                (LogLevel::error, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_log_messages_by_fields() {
+        let mut storage = Storage::new(4);
+        let user = Principal::from_text("aaaaa-aa").unwrap();
+        let user_field = LogField {
+            name: String::from("user"),
+            value: LogFieldValue::principal(user),
+        };
+
+        collector::store_log_message_with_fields(
+            &mut storage,
+            LogLevel::info,
+            String::from("transfer"),
+            vec![
+                user_field.clone(),
+                LogField {
+                    name: String::from("amount"),
+                    value: LogFieldValue::nat(Nat::from(123_u64)),
+                },
+            ],
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("tick"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message_with_fields(
+            &mut storage,
+            LogLevel::info,
+            String::from("transfer"),
+            vec![
+                user_field.clone(),
+                LogField {
+                    name: String::from("amount"),
+                    value: LogFieldValue::nat(Nat::from(5_u64)),
+                },
+            ],
+            &1024,
+            &None,
+        );
+
+        let params = GetLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: None,
+                messageContains: None,
+                analyzeCount: 10,
+                minLevel: None,
+                fieldsEqual: Some(vec![user_field.clone()]),
+                expression: None,
+            }),
+            fromTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_log_messages(&storage, params).unwrap();
+        let messages = result.data;
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages.get(0).unwrap().fields.as_ref().unwrap().len(), 2);
+        assert_eq!(messages.get(1).unwrap().fields.as_ref().unwrap().len(), 2);
+
+        let params = GetLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: None,
+                messageContains: None,
+                analyzeCount: 10,
+                minLevel: None,
+                fieldsEqual: Some(vec![
+                    LogField {
+                        name: String::from("amount"),
+                        value: LogFieldValue::nat(Nat::from(123_u64)),
+                    },
+                    user_field,
+                ]),
+                expression: None,
+            }),
+            fromTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_log_messages(&storage, params).unwrap();
+        let messages = result.data;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            messages.get(0).unwrap().fields.as_ref().unwrap().get(1),
+            Some(&LogField {
+                name: String::from("amount"),
+                value: LogFieldValue::nat(Nat::from(123_u64)),
+            })
+        );
+    }
+
+    #[test]
+    fn test_filter_log_messages_by_expression() {
+        let mut storage = Storage::new(4);
+
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("transfer ok"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::error,
+            String::from("transfer failed"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("approve ok"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("transfer Ok"),
+            &1024,
+            &None,
+        );
+
+        let params = GetLatestLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: None,
+                messageContains: None,
+                analyzeCount: 10,
+                minLevel: None,
+                fieldsEqual: None,
+                expression: Some(LogFilterExpression::and(vec![
+                    LogFilterExpression::messageContains(String::from("transfer")),
+                    LogFilterExpression::not(Box::new(LogFilterExpression::messageContains(
+                        String::from("ok"),
+                    ))),
+                ])),
+            }),
+            upToTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_latest_log_messages(&storage, params).unwrap();
+        let messages = result.data;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages.get(0).unwrap().message, "transfer failed");
+        assert_eq!(
+            result.lastAnalyzedMessageTimeNanos.unwrap(),
+            storage.get_log_messages(&None).next().unwrap().timeNanos
+        );
+
+        let params = GetLatestLogMessagesParameters {
+            count: 10,
+            filter: Some(GetLogMessagesFilter {
+                messageRegex: None,
+                messageContains: Some(String::from("transfer")),
+                analyzeCount: 2,
+                minLevel: None,
+                fieldsEqual: None,
+                expression: Some(LogFilterExpression::or(vec![
+                    LogFilterExpression::minLevel(LogLevel::error),
+                    LogFilterExpression::messageContains(String::from("approve")),
+                ])),
+            }),
+            upToTimeNanos: None,
+            channel: None,
+        };
+
+        let result = calculator::get_latest_log_messages(&storage, params).unwrap();
+        assert_eq!(result.data.len(), 0);
+        assert_eq!(
+            result.lastAnalyzedMessageTimeNanos.unwrap(),
+            storage.get_log_messages(&None).nth(2).unwrap().timeNanos
+        );
+    }
+
+    #[test]
+    fn test_migrate_to_stable_storage() {
+        super::set_max_messages_count(3);
+        super::log_message(String::from("message 1"));
+        super::log_warn(String::from("message 2"));
+        super::log_error(String::from("message 3"));
+        super::log_error(String::from("message 4"));
+
+        let memory = VecMemory::default();
+        super::init_stable_storage(Box::new(memory.clone()), MIN_CAPACITY_IN_BYTES).unwrap();
+        super::instance::with(|canistergeek| {
+            assert_eq!(canistergeek.logger().storage.get_log_messages_count(), 0)
+        });
+
+        super::log_debug(String::from("message 5"));
+
+        let upgrade_data =
+            super::with_pre_upgrade_stable_data(|(version, heap_storage, channels)| {
+                assert_eq!(heap_storage.get_log_messages_count(), 0);
+                let heap_storage = Storage::new(heap_storage.get_max_messages_count());
+                (*version, heap_storage, channels.cloned())
+            });
+
+        // upgrade: reopen the storage in the same memory
+        super::post_upgrade_stable_data(upgrade_data).unwrap();
+        super::init_stable_storage(Box::new(memory), MIN_CAPACITY_IN_BYTES).unwrap();
+
+        let response = super::get_canister_log(Some(CanisterLogRequest::getLatestMessages(
+            GetLatestLogMessagesParameters {
+                count: 10,
+                filter: None,
+                upToTimeNanos: None,
+                channel: None,
+            },
+        )));
+        let messages = match response {
+            Some(CanisterLogResponse::messages(messages)) => messages.data,
+            _ => panic!(),
+        };
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages.get(0).unwrap().message, "message 5");
+        assert_eq!(messages.get(0).unwrap().level, Some(LogLevel::debug));
+        assert_eq!(messages.get(1).unwrap().message, "message 4");
+        assert_eq!(messages.get(2).unwrap().message, "message 3");
+    }
+
     #[test]
     fn test_log_messages_info() {
         let mut storage = Storage::new(4);
         assert_eq!(storage.get_log_messages_count(), 0);
 
-        collector::store_log_message(&mut storage, String::from("message 1"), &1024);
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("message 1"),
+            &1024,
+            &None,
+        );
         assert_eq!(storage.get_log_messages_count(), 1);
 
-        collector::store_log_message(&mut storage, String::from("сообщение abc 2 "), &1024);
-        collector::store_log_message(&mut storage, String::from("message abc 3"), &1024);
-        collector::store_log_message(&mut storage, String::from("message 4"), &10);
-        collector::store_log_message(&mut storage, String::from("message 5"), &10);
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("сообщение abc 2 "),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("message abc 3"),
+            &1024,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("message 4"),
+            &10,
+            &None,
+        );
+        collector::store_log_message(
+            &mut storage,
+            LogLevel::info,
+            String::from("message 5"),
+            &10,
+            &None,
+        );
         assert_eq!(storage.get_log_messages_count(), 4);
     }
+
+    #[test]
+    fn test_upgrade_data_v1() {
+        use candid::Decode;
+        let bytes = include_bytes!("../migration/fixtures/upgrade_data_v1.bin");
+        let (_, logger_data) = Decode!(
+            bytes,
+            crate::monitor::PostUpgradeStableData,
+            super::PostUpgradeStableData
+        )
+        .unwrap();
+        let (version, storage, channels) = logger_data;
+        // channels are absent in the data saved by version 1
+        assert!(channels.is_none());
+        let (storage, report) =
+            crate::migration::migrate("logger", version, storage, &super::MIGRATIONS).unwrap();
+        assert_eq!(report.from_version, 1);
+        assert_eq!(report.to_version, super::VERSION);
+
+        // the ring of 3 messages has been overflowed by the fourth one
+        assert_eq!(storage.get_max_messages_count(), 3);
+        let messages: Vec<_> = storage.get_log_messages(&None).collect();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].message, "second");
+        assert_eq!(messages[0].timeNanos, 1_643_241_600_000_000_001);
+        assert_eq!(messages[0].level, None);
+        assert_eq!(messages[2].message, "fourth");
+        assert_eq!(
+            storage.get_last_log_message_time(),
+            Some(1_643_241_600_000_000_003)
+        );
+        // version 2 counts the size of the restored messages
+        let bytes: u64 = messages
+            .iter()
+            .map(|message| super::store::get_log_message_size(message))
+            .sum();
+        assert!(bytes > 0);
+        assert_eq!(storage.get_log_messages_bytes(), bytes);
+        assert_eq!(storage.get_max_log_messages_bytes(), None);
+    }
+
+    #[test]
+    fn test_truncated_messages() {
+        let mut logger = super::Logger::default();
+        logger.set_max_message_length(7);
+        assert_eq!(logger.get_max_message_length(), 7);
+        logger.log(LogLevel::info, String::from("сообщение"));
+        logger.log(LogLevel::info, String::from("message"));
+
+        let response = logger.get_canister_log(Some(CanisterLogRequest::getLatestMessages(
+            GetLatestLogMessagesParameters {
+                count: 10,
+                filter: None,
+                upToTimeNanos: None,
+                channel: None,
+            },
+        )));
+        let messages = match response {
+            Some(CanisterLogResponse::messages(messages)) => messages.data,
+            _ => panic!(),
+        };
+        assert_eq!(messages[0].message, "message");
+        assert_eq!(messages[0].truncated, Some(false));
+        assert_eq!(messages[0].originalLength, None);
+        // the cut does not split the two bytes characters
+        assert_eq!(messages[1].message, "соо");
+        assert_eq!(messages[1].truncated, Some(true));
+        assert_eq!(messages[1].originalLength, Some(18));
+    }
+
+    #[test]
+    fn test_dedup_window() {
+        let mut logger = super::Logger::default();
+        logger.set_dedup_window(Some(60));
+        assert_eq!(logger.get_dedup_window(), Some(60));
+        for _ in 0..1000 {
+            logger.log(LogLevel::error, String::from("failed"));
+        }
+        logger.log(LogLevel::info, String::from("recovered"));
+
+        let response = logger.get_canister_log(Some(CanisterLogRequest::getLatestMessages(
+            GetLatestLogMessagesParameters {
+                count: 10,
+                filter: None,
+                upToTimeNanos: None,
+                channel: None,
+            },
+        )));
+        let messages = match response {
+            Some(CanisterLogResponse::messages(messages)) => messages.data,
+            _ => panic!(),
+        };
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].message, "recovered");
+        assert_eq!(messages[0].repeatCount, None);
+        assert_eq!(messages[1].message, "failed");
+        assert_eq!(messages[1].repeatCount, Some(1000));
+        assert!(messages[1].lastTimeNanos.unwrap() > messages[1].timeNanos);
+
+        logger.set_dedup_window(None);
+        logger.log(LogLevel::info, String::from("recovered"));
+        assert_eq!(logger.get_log_messages_info().count, 3);
+    }
+
+    #[test]
+    fn test_rate_limits() {
+        use super::super::api_type::{LogField, LogFieldValue};
+        let source = |source: &str| {
+            vec![LogField {
+                name: super::SOURCE_FIELD_NAME.to_string(),
+                value: LogFieldValue::text(source.to_string()),
+            }]
+        };
+        let mut logger = super::Logger::default();
+        assert_eq!(logger.get_log_messages_info().droppedMessages, None);
+        logger
+            .set_rate_limits(vec![super::RateLimit {
+                source: "http".to_string(),
+                max_messages: 2,
+                period_in_seconds: 60,
+            }])
+            .unwrap();
+        assert_eq!(logger.get_rate_limits().len(), 1);
+
+        for _ in 0..5 {
+            logger.log_with_fields(LogLevel::info, String::from("request"), source("http"));
+        }
+        assert!(!logger.log_with_fields(LogLevel::info, String::from("request"), source("http")));
+        assert!(logger.log_with_fields(LogLevel::info, String::from("query"), source("db")));
+        assert!(logger.log(LogLevel::info, String::from("no source")));
+        let info = logger.get_log_messages_info();
+        assert_eq!(info.count, 4);
+        assert_eq!(
+            info.droppedMessages,
+            Some(vec![super::DroppedLogMessages {
+                source: "http".to_string(),
+                count: 4,
+            }])
+        );
+
+        // the summary is written once the source is allowed again
+        logger.store_drop_summaries(crate::ic_util::get_ic_time_nanos() + 60_000_000_000);
+        let response = logger.get_canister_log(Some(CanisterLogRequest::getLatestMessages(
+            GetLatestLogMessagesParameters {
+                count: 1,
+                filter: None,
+                upToTimeNanos: None,
+                channel: None,
+            },
+        )));
+        let messages = match response {
+            Some(CanisterLogResponse::messages(messages)) => messages.data,
+            _ => panic!(),
+        };
+        assert_eq!(messages[0].message, "dropped 4 messages from http");
+        assert_eq!(messages[0].level, Some(LogLevel::warn));
+        assert_eq!(messages[0].fields, Some(source("http")));
+        // the total count is kept
+        assert_eq!(
+            logger.get_log_messages_info().droppedMessages.unwrap()[0].count,
+            4
+        );
+
+        // drops are summarized in the channel the messages were logged to
+        logger.set_channel("debug", 10).unwrap();
+        for _ in 0..3 {
+            logger
+                .log_to_channel(
+                    "debug",
+                    LogLevel::info,
+                    String::from("request"),
+                    source("http"),
+                )
+                .unwrap();
+        }
+        // the source is not allowed yet
+        logger.flush_drop_summaries();
+        assert_eq!(logger.get_log_messages_info().count, 5);
+        logger.store_drop_summaries(crate::ic_util::get_ic_time_nanos() + 120_000_000_000);
+        assert_eq!(logger.get_log_messages_info().count, 5);
+        let response = logger.try_get_canister_log(CanisterLogRequest::getLatestMessages(
+            GetLatestLogMessagesParameters {
+                count: 1,
+                filter: None,
+                upToTimeNanos: None,
+                channel: Some("debug".to_string()),
+            },
+        ));
+        match response {
+            Ok(CanisterLogResponse::messages(messages)) => {
+                assert_eq!(messages.data[0].message, "dropped 1 messages from http")
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_channels() {
+        use candid::{Decode, Encode};
+        let latest_messages = |channel: Option<&str>| {
+            CanisterLogRequest::getLatestMessages(GetLatestLogMessagesParameters {
+                count: 10,
+                filter: None,
+                upToTimeNanos: None,
+                channel: channel.map(str::to_string),
+            })
+        };
+        let messages = |logger: &super::Logger, channel: Option<&str>| -> Vec<String> {
+            match logger.try_get_canister_log(latest_messages(channel)) {
+                Ok(CanisterLogResponse::messages(messages)) => messages
+                    .data
+                    .iter()
+                    .map(|message| message.message.clone())
+                    .collect(),
+                _ => panic!(),
+            }
+        };
+
+        let mut logger = super::Logger::default();
+        assert!(logger.set_channel("", 10).is_err());
+        assert!(logger.set_channel("audit", 0).is_err());
+        logger.set_channel("audit", 2).unwrap();
+        logger.set_channel("debug", 10).unwrap();
+        assert_eq!(logger.get_channel_names(), vec!["audit", "debug"]);
+        assert!(logger
+            .log_to_channel("errors", LogLevel::error, String::from("lost"), Vec::new())
+            .is_err());
+
+        for index in 1..=3 {
+            logger
+                .log_to_channel(
+                    "audit",
+                    LogLevel::info,
+                    format!("audit {}", index),
+                    Vec::new(),
+                )
+                .unwrap();
+        }
+        for index in 1..=5 {
+            logger
+                .log_to_channel(
+                    "debug",
+                    LogLevel::debug,
+                    format!("debug {}", index),
+                    Vec::new(),
+                )
+                .unwrap();
+        }
+        logger.log(LogLevel::info, String::from("default"));
+
+        // noisy channels do not evict messages of the others
+        assert_eq!(messages(&logger, Some("audit")), vec!["audit 3", "audit 2"]);
+        assert_eq!(messages(&logger, Some("debug")).len(), 5);
+        assert_eq!(messages(&logger, None), vec!["default"]);
+        assert_eq!(
+            logger
+                .try_get_canister_log(latest_messages(Some("errors")))
+                .err(),
+            Some(InformationError::unknownChannel("errors".to_string()))
+        );
+
+        let info = logger.get_log_messages_info();
+        assert_eq!(info.count, 1);
+        let channels = info.channels.unwrap();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0].name, "audit");
+        assert_eq!(channels[0].count, 2);
+        assert_eq!(channels[0].maxCount, 2);
+        assert!(channels[0].firstTimeNanos < channels[0].lastTimeNanos);
+        assert_eq!(channels[1].count, 5);
+
+        // channels are persisted with their messages
+        let bytes = Encode!(&logger.pre_upgrade_stable_data()).unwrap();
+        let upgrade_data = Decode!(&bytes, super::PostUpgradeStableData).unwrap();
+        let mut restored = super::Logger::default();
+        restored.post_upgrade_stable_data(upgrade_data).unwrap();
+        assert_eq!(
+            messages(&restored, Some("audit")),
+            vec!["audit 3", "audit 2"]
+        );
+        assert_eq!(messages(&restored, None), vec!["default"]);
+
+        assert!(restored.remove_channel("debug"));
+        assert!(!restored.remove_channel("debug"));
+        assert_eq!(restored.get_channel_names(), vec!["audit"]);
+    }
 }
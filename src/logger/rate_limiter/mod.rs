@@ -0,0 +1,298 @@
+use super::super::api_type::{LogField, LogFieldValue, Nanos};
+
+/// Name of the text field the rate limits are keyed by.
+pub const SOURCE_FIELD_NAME: &str = "source";
+
+const SECOND_NANOS: u128 = 1_000_000_000;
+
+/// Allows `max_messages` messages of the source per period, unused allowance is accumulated
+/// up to `max_messages`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimit {
+    pub source: String,
+    pub max_messages: u32,
+    pub period_in_seconds: u32,
+}
+
+// a message takes `period_nanos` tokens, every nanosecond adds `max_messages` tokens
+struct Bucket {
+    limit: RateLimit,
+    tokens: u128,
+    updated_nanos: Nanos,
+    // messages dropped since the last summary by the channel they were logged to
+    dropped: Vec<(Option<String>, u64)>,
+    total_dropped: u64,
+}
+
+impl Bucket {
+    fn get_message_tokens(&self) -> u128 {
+        self.limit.period_in_seconds as u128 * SECOND_NANOS
+    }
+
+    fn refill(&mut self, time_nanos: Nanos) {
+        if time_nanos > self.updated_nanos {
+            let capacity = self.limit.max_messages as u128 * self.get_message_tokens();
+            let added = (time_nanos - self.updated_nanos) as u128 * self.limit.max_messages as u128;
+            self.tokens = capacity.min(self.tokens + added);
+            self.updated_nanos = time_nanos;
+        }
+    }
+
+    fn has_message_tokens(&self) -> bool {
+        self.tokens >= self.get_message_tokens()
+    }
+}
+
+/// Messages of the source dropped in the channel (`None` for the default one) since the previous summary.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DropSummary {
+    pub source: String,
+    pub channel: Option<String>,
+    pub count: u64,
+}
+
+/// Token buckets of the log sources with the counts of dropped messages.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Vec<Bucket>,
+}
+
+/// Returns the value of the source field, if the message has one.
+pub fn get_source(fields: &[LogField]) -> Option<&str> {
+    fields
+        .iter()
+        .find(|field| field.name == SOURCE_FIELD_NAME)
+        .and_then(|field| match &field.value {
+            LogFieldValue::text(source) => Some(source.as_str()),
+            _ => None,
+        })
+}
+
+impl RateLimiter {
+    /// Replaces the limits, drop counts of the sources still limited are kept.
+    pub fn set_limits(
+        &mut self,
+        limits: Vec<RateLimit>,
+        time_nanos: Nanos,
+    ) -> Result<(), &'static str> {
+        for (index, limit) in limits.iter().enumerate() {
+            if limit.max_messages == 0 || limit.period_in_seconds == 0 {
+                return Err("Rate limit has to allow messages");
+            }
+            if limits[..index]
+                .iter()
+                .any(|other| other.source == limit.source)
+            {
+                return Err("Duplicate rate limit source");
+            }
+        }
+
+        let mut buckets: Vec<Bucket> = limits
+            .into_iter()
+            .map(|limit| Bucket {
+                tokens: limit.max_messages as u128 * limit.period_in_seconds as u128 * SECOND_NANOS,
+                limit,
+                updated_nanos: time_nanos,
+                dropped: Vec::new(),
+                total_dropped: 0,
+            })
+            .collect();
+        for bucket in buckets.iter_mut() {
+            if let Some(previous) = self
+                .buckets
+                .iter()
+                .find(|previous| previous.limit.source == bucket.limit.source)
+            {
+                bucket.dropped = previous.dropped.clone();
+                bucket.total_dropped = previous.total_dropped;
+            }
+        }
+        self.buckets = buckets;
+        Ok(())
+    }
+
+    pub fn get_limits(&self) -> Vec<RateLimit> {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.limit.clone())
+            .collect()
+    }
+
+    pub fn has_limits(&self) -> bool {
+        !self.buckets.is_empty()
+    }
+
+    /// Takes a token of the source, returns `false` if the message of the channel (`None` for the default one)
+    /// has to be dropped. Messages of sources without a limit are always allowed.
+    pub fn allow(
+        &mut self,
+        source: Option<&str>,
+        channel: Option<&str>,
+        time_nanos: Nanos,
+    ) -> bool {
+        let bucket = match source.and_then(|source| {
+            self.buckets
+                .iter_mut()
+                .find(|bucket| bucket.limit.source == source)
+        }) {
+            Some(bucket) => bucket,
+            None => return true,
+        };
+
+        bucket.refill(time_nanos);
+        if bucket.has_message_tokens() {
+            bucket.tokens -= bucket.get_message_tokens();
+            true
+        } else {
+            match bucket
+                .dropped
+                .iter_mut()
+                .find(|(dropped_channel, _)| dropped_channel.as_deref() == channel)
+            {
+                Some((_, count)) => *count += 1,
+                None => bucket.dropped.push((channel.map(str::to_string), 1)),
+            }
+            bucket.total_dropped += 1;
+            false
+        }
+    }
+
+    /// Returns the sources with dropped messages which are allowed again, the channels
+    /// and the counts dropped since the previous summary.
+    pub fn take_drop_summaries(&mut self, time_nanos: Nanos) -> Vec<DropSummary> {
+        let mut summaries = Vec::new();
+        for bucket in self
+            .buckets
+            .iter_mut()
+            .filter(|bucket| !bucket.dropped.is_empty())
+        {
+            bucket.refill(time_nanos);
+            if bucket.has_message_tokens() {
+                for (channel, count) in bucket.dropped.drain(..) {
+                    summaries.push(DropSummary {
+                        source: bucket.limit.source.clone(),
+                        channel,
+                        count,
+                    });
+                }
+            }
+        }
+        summaries
+    }
+
+    pub fn has_dropped_messages(&self) -> bool {
+        self.buckets.iter().any(|bucket| !bucket.dropped.is_empty())
+    }
+
+    /// Total counts of dropped messages by the limited sources.
+    pub fn get_dropped_counts(&self) -> Vec<(String, u64)> {
+        self.buckets
+            .iter()
+            .map(|bucket| (bucket.limit.source.clone(), bucket.total_dropped))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Nanos = 1_000_000_000;
+
+    fn http_limit() -> RateLimit {
+        RateLimit {
+            source: "http".to_string(),
+            max_messages: 2,
+            period_in_seconds: 60,
+        }
+    }
+
+    fn drop_summary(channel: Option<&str>, count: u64) -> DropSummary {
+        DropSummary {
+            source: "http".to_string(),
+            channel: channel.map(str::to_string),
+            count,
+        }
+    }
+
+    #[test]
+    fn test_set_limits() {
+        let mut rate_limiter = RateLimiter::default();
+        assert!(rate_limiter
+            .set_limits(vec![http_limit(), http_limit()], 0)
+            .is_err());
+        let mut limit = http_limit();
+        limit.max_messages = 0;
+        assert!(rate_limiter.set_limits(vec![limit], 0).is_err());
+        assert!(!rate_limiter.has_limits());
+
+        rate_limiter.set_limits(vec![http_limit()], 0).unwrap();
+        assert_eq!(rate_limiter.get_limits(), vec![http_limit()]);
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let mut rate_limiter = RateLimiter::default();
+        rate_limiter.set_limits(vec![http_limit()], 0).unwrap();
+
+        assert!(rate_limiter.allow(Some("http"), None, SECOND));
+        assert!(rate_limiter.allow(Some("http"), None, SECOND));
+        assert!(!rate_limiter.allow(Some("http"), None, 2 * SECOND));
+        assert!(!rate_limiter.allow(Some("http"), None, 3 * SECOND));
+        // other sources are not limited
+        assert!(rate_limiter.allow(Some("db"), None, 3 * SECOND));
+        assert!(rate_limiter.allow(None, None, 3 * SECOND));
+        assert!(rate_limiter.take_drop_summaries(20 * SECOND).is_empty());
+
+        // a token is added every 30 seconds
+        assert_eq!(
+            rate_limiter.take_drop_summaries(31 * SECOND),
+            vec![drop_summary(None, 2)]
+        );
+        assert!(rate_limiter.take_drop_summaries(32 * SECOND).is_empty());
+        assert!(rate_limiter.allow(Some("http"), None, 32 * SECOND));
+        assert!(!rate_limiter.allow(Some("http"), None, 33 * SECOND));
+
+        // the allowance is accumulated up to the limit
+        assert!(rate_limiter.allow(Some("http"), None, 1000 * SECOND));
+        assert!(rate_limiter.allow(Some("http"), None, 1000 * SECOND));
+        assert!(!rate_limiter.allow(Some("http"), None, 1000 * SECOND));
+
+        assert_eq!(
+            rate_limiter.get_dropped_counts(),
+            vec![("http".to_string(), 4)]
+        );
+        // counts are kept when the limits are replaced
+        rate_limiter
+            .set_limits(vec![http_limit()], 1000 * SECOND)
+            .unwrap();
+        assert_eq!(
+            rate_limiter.take_drop_summaries(1000 * SECOND),
+            vec![drop_summary(None, 2)]
+        );
+        assert_eq!(
+            rate_limiter.get_dropped_counts(),
+            vec![("http".to_string(), 4)]
+        );
+    }
+
+    #[test]
+    fn test_channels() {
+        let mut rate_limiter = RateLimiter::default();
+        rate_limiter.set_limits(vec![http_limit()], 0).unwrap();
+        assert!(!rate_limiter.has_dropped_messages());
+        for _ in 0..3 {
+            rate_limiter.allow(Some("http"), Some("audit"), SECOND);
+        }
+        rate_limiter.allow(Some("http"), None, SECOND);
+        rate_limiter.allow(Some("http"), Some("audit"), SECOND);
+        assert!(rate_limiter.has_dropped_messages());
+
+        // the drops are summarized by the channels
+        assert_eq!(
+            rate_limiter.take_drop_summaries(31 * SECOND),
+            vec![drop_summary(Some("audit"), 2), drop_summary(None, 1)]
+        );
+        assert!(!rate_limiter.has_dropped_messages());
+    }
+}
@@ -0,0 +1,211 @@
+//! LZ77 compression of message texts against a shared dictionary.
+//!
+//! The dictionary is filled with the texts of the first compressed messages and only grows.
+//! Distances are counted from the end of the dictionary, so a text has to be decoded against
+//! the same dictionary prefix it was compressed with. Repeated prefixes and fragments of
+//! messages become references into the dictionary or into the already decoded part of the text.
+//!
+//! Encoded text is a sequence of tokens: a control byte below `0x80` is followed by
+//! `control + 1` literal bytes, a control byte `0x80 | (length - MIN_MATCH)` is followed by
+//! the LEB128 distance back from the current position (the dictionary precedes the text).
+
+pub const MAX_DICTIONARY_SIZE: usize = 1024;
+
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = MIN_MATCH + 0x7f;
+const MAX_LITERALS: usize = 0x80;
+const HASH_BITS: u32 = 12;
+const MAX_CHAIN_LENGTH: usize = 16;
+const NONE: u32 = u32::MAX;
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    (value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+/// Positions of `buffer` by the hash of their first `MIN_MATCH` bytes.
+struct MatchIndex {
+    head: Vec<u32>,
+    previous: Vec<u32>,
+}
+
+impl MatchIndex {
+    fn new(size: usize) -> Self {
+        Self {
+            head: vec![NONE; 1 << HASH_BITS],
+            previous: vec![NONE; size],
+        }
+    }
+
+    fn insert(&mut self, buffer: &[u8], position: usize) {
+        if position + MIN_MATCH <= buffer.len() {
+            let hash = hash(&buffer[position..]);
+            self.previous[position] = self.head[hash];
+            self.head[hash] = position as u32;
+        }
+    }
+
+    /// Returns the length and the distance of the longest match found.
+    fn find(&self, buffer: &[u8], position: usize) -> (usize, usize) {
+        let mut best = (0, 0);
+        if position + MIN_MATCH > buffer.len() {
+            return best;
+        }
+        let max_length = MAX_MATCH.min(buffer.len() - position);
+        let mut candidate = self.head[hash(&buffer[position..])];
+        let mut chain_length = 0;
+        while candidate != NONE && chain_length < MAX_CHAIN_LENGTH {
+            let start = candidate as usize;
+            let length = (0..max_length)
+                .take_while(|offset| buffer[start + offset] == buffer[position + offset])
+                .count();
+            if length > best.0 {
+                best = (length, position - start);
+                if length == max_length {
+                    break;
+                }
+            }
+            candidate = self.previous[start];
+            chain_length += 1;
+        }
+        best
+    }
+}
+
+fn write_literals(output: &mut Vec<u8>, literals: &[u8]) {
+    for chunk in literals.chunks(MAX_LITERALS) {
+        output.push((chunk.len() - 1) as u8);
+        output.extend_from_slice(chunk);
+    }
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        output.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn read_varint(data: &[u8], position: &mut usize) -> Result<usize, &'static str> {
+    let mut value = 0_usize;
+    for shift in (0..usize::BITS).step_by(7) {
+        let byte = *data
+            .get(*position)
+            .ok_or("Unexpected end of compressed data")?;
+        *position += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte < 0x80 {
+            return Ok(value);
+        }
+    }
+    Err("Too long distance in compressed data")
+}
+
+pub fn compress(dictionary: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(dictionary.len() + data.len());
+    buffer.extend_from_slice(dictionary);
+    buffer.extend_from_slice(data);
+
+    let mut index = MatchIndex::new(buffer.len());
+    for position in 0..dictionary.len() {
+        index.insert(&buffer, position);
+    }
+
+    let mut output = Vec::new();
+    let mut literals_start = dictionary.len();
+    let mut position = dictionary.len();
+    while position < buffer.len() {
+        let (length, distance) = index.find(&buffer, position);
+        if length >= MIN_MATCH {
+            write_literals(&mut output, &buffer[literals_start..position]);
+            output.push(0x80 | (length - MIN_MATCH) as u8);
+            write_varint(&mut output, distance);
+            for matched_position in position..(position + length) {
+                index.insert(&buffer, matched_position);
+            }
+            position += length;
+            literals_start = position;
+        } else {
+            index.insert(&buffer, position);
+            position += 1;
+        }
+    }
+    write_literals(&mut output, &buffer[literals_start..]);
+    output
+}
+
+pub fn decompress(dictionary: &[u8], data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut output: Vec<u8> = Vec::new();
+    let mut position = 0;
+    while position < data.len() {
+        let control = data[position] as usize;
+        position += 1;
+        if control < 0x80 {
+            let end = position + control + 1;
+            let literals = data
+                .get(position..end)
+                .ok_or("Unexpected end of compressed data")?;
+            output.extend_from_slice(literals);
+            position = end;
+        } else {
+            let length = (control & 0x7f) + MIN_MATCH;
+            let distance = read_varint(data, &mut position)?;
+            let decoded = dictionary.len() + output.len();
+            if distance == 0 || distance > decoded {
+                return Err("Invalid distance in compressed data");
+            }
+            // the match may overlap the bytes it produces
+            for source in (decoded - distance)..(decoded - distance + length) {
+                let byte = if source < dictionary.len() {
+                    dictionary[source]
+                } else {
+                    output[source - dictionary.len()]
+                };
+                output.push(byte);
+            }
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(dictionary: &[u8], data: &[u8]) -> usize {
+        let compressed = compress(dictionary, data);
+        assert_eq!(decompress(dictionary, &compressed).unwrap(), data);
+        compressed.len()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(check(b"", b""), 0);
+        check(b"", b"abc");
+        check(b"abc", b"abc");
+        check(b"", "сообщение 🦀 сообщение 🦀".as_bytes());
+        let long: Vec<u8> = (0..1000).map(|i| (i * 7 % 251) as u8).collect();
+        check(b"", &long);
+        check(&long, &long);
+
+        // repetitions are encoded by overlapping references
+        assert!(check(b"", &[b'a'; 1000]) < 30);
+    }
+
+    #[test]
+    fn test_dictionary() {
+        let dictionary = br#"{"event":"transfer","from":"aaaaa-aa","amount":"#;
+        let message = br#"{"event":"transfer","from":"aaaaa-aa","amount":100}"#;
+        let without_dictionary = check(b"", message);
+        let with_dictionary = check(dictionary, message);
+        assert!(with_dictionary < without_dictionary / 4);
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        assert!(decompress(b"", &[3, b'a']).is_err());
+        assert!(decompress(b"ab", &[0x80, 3]).is_err());
+        assert!(decompress(b"", &[0x80, 0x80]).is_err());
+    }
+}
@@ -1,16 +1,58 @@
-use super::super::api_type::Nanos;
+use super::super::api_type::{LogField, LogFieldValue, Nanos};
 use super::data_type::{LogMessage, LogMessagesInfo, LogMessagesStorage, LogMessagesSupplier};
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+mod compression;
+pub mod stable;
 
 pub type LogMessageQueue = Vec<LogMessage>;
 
-#[derive(Debug, CandidType, Deserialize, Serialize)]
+#[derive(Debug, Clone, CandidType, Deserialize, Serialize)]
 pub struct Storage {
     queue: LogMessageQueue,
     max_count: usize,
     next: usize,
     full: bool,
+    // fields below are absent in the data saved by version 1
+    max_bytes: Option<u64>,
+    bytes: Option<u64>,
+    // number of the oldest slots emptied to fit the bytes budget
+    evicted: Option<usize>,
+    // fields below are absent in the data saved before the compression
+    compression: Option<bool>,
+    dictionary: Option<Vec<u8>>,
+    // compressed texts by the queue slots, the text of the slot message is empty then
+    compressed_texts: Option<Vec<Option<CompressedText>>>,
+}
+
+#[derive(Debug, Clone, CandidType, Deserialize, Serialize)]
+struct CompressedText {
+    data: Vec<u8>,
+    length: u64,
+    // the text is decoded against the dictionary prefix of this length
+    dictionary_length: u64,
+}
+
+/// Approximate heap size of the message.
+pub fn get_log_message_size(log_message: &LogMessage) -> u64 {
+    let fields_size: usize = log_message
+        .fields
+        .iter()
+        .flatten()
+        .map(|field| {
+            let value_size = match &field.value {
+                LogFieldValue::text(value) => value.len(),
+                LogFieldValue::nat(value) => value.0.bits().div_ceil(8) as usize,
+                LogFieldValue::int(value) => value.0.bits().div_ceil(8) as usize,
+                LogFieldValue::principal(value) => value.as_slice().len(),
+                LogFieldValue::bool(_) => 0,
+            };
+            std::mem::size_of::<LogField>() + field.name.len() + value_size
+        })
+        .sum();
+    (std::mem::size_of::<LogMessage>() + log_message.message.len() + fields_size) as u64
 }
 
 impl Storage {
@@ -19,15 +61,200 @@ impl Storage {
     }
 
     pub fn init(queue: LogMessageQueue, max_count: usize, next: usize, full: bool) -> Self {
-        Self {
+        let mut storage = Self {
             queue,
             max_count,
             next,
             full,
+            max_bytes: None,
+            bytes: None,
+            evicted: None,
+            compression: None,
+            dictionary: None,
+            compressed_texts: None,
+        };
+        storage.count_bytes();
+        storage
+    }
+
+    pub fn get_max_messages_count(&self) -> usize {
+        self.max_count
+    }
+
+    /// Sets the budget of the messages total size, the oldest messages are evicted to fit it.
+    /// A message bigger than the budget is kept alone.
+    pub fn set_max_bytes(&mut self, max_bytes: Option<u64>) {
+        self.max_bytes = max_bytes;
+        if let Some(max_bytes) = max_bytes {
+            while self.get_count() > 0 && self.get_bytes() > max_bytes {
+                self.evict_first();
+            }
         }
     }
 
-    fn get_count(&self) -> usize {
+    pub fn get_max_bytes(&self) -> Option<u64> {
+        self.max_bytes
+    }
+
+    /// Texts of messages stored with the compression are kept compressed and decoded when read.
+    pub fn set_compression(&mut self, enabled: bool) {
+        self.compression = Some(enabled);
+    }
+
+    pub fn is_compression_enabled(&self) -> bool {
+        self.compression.unwrap_or(false)
+    }
+
+    /// Returns an empty storage with the same limits and compression mode.
+    pub fn create_empty(&self) -> Self {
+        let mut storage = Storage::new(self.max_count);
+        storage.max_bytes = self.max_bytes;
+        storage.compression = self.compression;
+        storage
+    }
+
+    /// Counts the size of the stored messages, which is not saved by version 1.
+    pub fn count_bytes(&mut self) {
+        let bytes = LogMessageIterator::create(self, &None)
+            .map(|index| self.get_slot_size(index))
+            .sum();
+        self.bytes = Some(bytes);
+    }
+
+    fn get_compressed_text(&self, index: usize) -> Option<&CompressedText> {
+        self.compressed_texts
+            .as_ref()
+            .and_then(|texts| texts.get(index))
+            .and_then(Option::as_ref)
+    }
+
+    fn set_compressed_text(&mut self, index: usize, compressed_text: Option<CompressedText>) {
+        if compressed_text.is_none() && self.compressed_texts.is_none() {
+            return;
+        }
+        let texts = self.compressed_texts.get_or_insert_with(Vec::new);
+        texts.resize(self.queue.len(), None);
+        texts[index] = compressed_text;
+    }
+
+    fn get_slot_size(&self, index: usize) -> u64 {
+        let compressed_size = self
+            .get_compressed_text(index)
+            .map_or(0, |text| text.data.len());
+        get_log_message_size(&self.queue[index]) + compressed_size as u64
+    }
+
+    fn get_message(&self, index: usize) -> Cow<'_, LogMessage> {
+        let message = &self.queue[index];
+        match self.get_compressed_text(index) {
+            Some(text) => {
+                let dictionary = self.dictionary.as_deref().unwrap_or_default();
+                let dictionary = &dictionary[..text.dictionary_length as usize];
+                let bytes = compression::decompress(dictionary, &text.data)
+                    .expect("Corrupted compressed log message");
+                Cow::Owned(LogMessage {
+                    message: String::from_utf8(bytes).expect("Corrupted compressed log message"),
+                    ..message.clone()
+                })
+            }
+            None => Cow::Borrowed(message),
+        }
+    }
+
+    /// Moves the text into the compressed one, if it gets shorter.
+    fn compress(&mut self, mut log_message: LogMessage) -> (LogMessage, Option<CompressedText>) {
+        if !self.is_compression_enabled() || log_message.message.is_empty() {
+            return (log_message, None);
+        }
+        let dictionary = self.dictionary.get_or_insert_with(Vec::new);
+        let text = log_message.message.as_bytes();
+        let dictionary_length = dictionary.len() as u64;
+        let data = compression::compress(dictionary, text);
+        // the dictionary only grows, so its prefix used by the stored texts stays the same
+        let free = compression::MAX_DICTIONARY_SIZE - dictionary.len();
+        dictionary.extend_from_slice(&text[..free.min(text.len())]);
+
+        if data.len() >= text.len() {
+            return (log_message, None);
+        }
+        let compressed_text = CompressedText {
+            data,
+            length: text.len() as u64,
+            dictionary_length,
+        };
+        log_message.message = String::new();
+        (log_message, Some(compressed_text))
+    }
+
+    fn get_compression_ratio(&self) -> Option<f64> {
+        if !self.is_compression_enabled() && self.compressed_texts.is_none() {
+            return None;
+        }
+        let (length, stored_length) = LogMessageIterator::create(self, &None)
+            .map(|index| match self.get_compressed_text(index) {
+                Some(text) => (text.length, text.data.len() as u64),
+                None => {
+                    let length = self.queue[index].message.len() as u64;
+                    (length, length)
+                }
+            })
+            .fold(
+                (0, 0),
+                |(length, stored_length), (text_length, text_stored_length)| {
+                    (length + text_length, stored_length + text_stored_length)
+                },
+            );
+        if stored_length == 0 {
+            Some(1.0)
+        } else {
+            Some(length as f64 / stored_length as f64)
+        }
+    }
+
+    fn store_slot(&mut self, log_message: LogMessage, compressed_text: Option<CompressedText>) {
+        let size = get_log_message_size(&log_message)
+            + compressed_text
+                .as_ref()
+                .map_or(0, |text| text.data.len() as u64);
+        if let Some(max_bytes) = self.max_bytes {
+            while self.get_count() > 0 && self.get_bytes() + size > max_bytes {
+                self.evict_first();
+            }
+        }
+
+        let index = self.next;
+        if self.full {
+            // the slot of the oldest message is reused
+            if self.get_evicted() > 0 {
+                self.evicted = Some(self.get_evicted() - 1);
+            } else {
+                let overwritten_size = self.get_slot_size(index);
+                self.bytes = Some(self.get_bytes() - overwritten_size);
+            }
+            self.queue[index] = log_message;
+        } else {
+            self.queue.push(log_message);
+        }
+        self.set_compressed_text(index, compressed_text);
+        self.bytes = Some(self.get_bytes() + size);
+
+        self.next += 1;
+
+        if self.next == self.max_count {
+            self.full = true;
+            self.next = 0;
+        }
+    }
+
+    fn get_bytes(&self) -> u64 {
+        self.bytes.unwrap_or(0)
+    }
+
+    fn get_evicted(&self) -> usize {
+        self.evicted.unwrap_or(0)
+    }
+
+    fn get_slot_count(&self) -> usize {
         if self.full {
             self.max_count
         } else {
@@ -35,52 +262,97 @@ impl Storage {
         }
     }
 
+    fn get_count(&self) -> usize {
+        self.get_slot_count() - self.get_evicted()
+    }
+
     fn get_first_index(&self) -> usize {
-        if self.full {
-            self.next
-        } else {
-            0
+        let first_slot_index = if self.full { self.next } else { 0 };
+        first_slot_index + self.get_evicted()
+    }
+
+    fn evict_first(&mut self) {
+        let index = self.get_first_index() % self.max_count;
+        let size = self.get_slot_size(index);
+        // the slot is kept till it is reused, so only the message data is released
+        let message = &mut self.queue[index];
+        message.message = String::new();
+        message.fields = None;
+        self.set_compressed_text(index, None);
+        self.bytes = Some(self.get_bytes() - size);
+        self.evicted = Some(self.get_evicted() + 1);
+
+        if self.get_count() == 0 {
+            self.queue.clear();
+            self.compressed_texts = None;
+            self.next = 0;
+            self.full = false;
+            self.evicted = None;
         }
     }
 }
 
 impl LogMessagesInfo for Storage {
     fn get_log_messages_count(&self) -> u32 {
-        self.queue.len() as u32
+        self.get_count() as u32
     }
 
     fn get_first_log_message_time(&self) -> Option<u64> {
-        if self.queue.is_empty() {
+        if self.get_count() == 0 {
             None
         } else {
-            let index = if self.full { self.next } else { 0 };
-            Some(self.queue[index].timeNanos)
+            Some(self.queue[self.get_first_index() % self.max_count].timeNanos)
         }
     }
 
     fn get_last_log_message_time(&self) -> Option<Nanos> {
-        if self.queue.is_empty() {
+        if self.get_count() == 0 {
             None
         } else {
             Some(self.queue[(self.max_count + self.next - 1) % self.max_count].timeNanos)
         }
     }
+
+    fn get_log_messages_bytes(&self) -> u64 {
+        self.get_bytes()
+    }
+
+    fn get_max_log_messages_bytes(&self) -> Option<u64> {
+        self.max_bytes
+    }
+
+    fn get_compression_ratio(&self) -> Option<f64> {
+        Storage::get_compression_ratio(self)
+    }
 }
 
 impl LogMessagesStorage for Storage {
     fn store_log_message(&mut self, log_message: LogMessage) {
-        if self.full {
-            self.queue[self.next] = log_message;
+        let (log_message, compressed_text) = self.compress(log_message);
+        self.store_slot(log_message, compressed_text);
+    }
+
+    fn get_last_log_message(&self) -> Option<Cow<'_, LogMessage>> {
+        if self.get_count() == 0 {
+            None
         } else {
-            self.queue.push(log_message);
+            Some(self.get_message((self.max_count + self.next - 1) % self.max_count))
         }
+    }
 
-        self.next += 1;
-
-        if self.next == self.max_count {
-            self.full = true;
-            self.next = 0;
+    fn replace_last_log_message(&mut self, log_message: LogMessage) {
+        if self.get_count() == 0 {
+            return self.store_log_message(log_message);
         }
+        let index = (self.max_count + self.next - 1) % self.max_count;
+        let (log_message, compressed_text) = self.compress(log_message);
+        let size = get_log_message_size(&log_message)
+            + compressed_text
+                .as_ref()
+                .map_or(0, |text| text.data.len() as u64);
+        self.bytes = Some(self.get_bytes() - self.get_slot_size(index) + size);
+        self.queue[index] = log_message;
+        self.set_compressed_text(index, compressed_text);
     }
 
     fn set_max_messages_count(&mut self, new_max_messages_count: usize) {
@@ -88,7 +360,10 @@ impl LogMessagesStorage for Storage {
             return;
         }
 
-        let mut new_storage = Storage::new(new_max_messages_count);
+        let mut new_storage = self.create_empty();
+        new_storage.max_count = new_max_messages_count;
+        // compressed texts are moved as they are
+        new_storage.dictionary = self.dictionary.clone();
 
         let count = self.get_count();
         let first_index = self.get_first_index();
@@ -101,13 +376,14 @@ impl LogMessagesStorage for Storage {
         };
 
         for i in range {
-            new_storage.store_log_message(self.queue.get(i % self.max_count).unwrap().clone());
+            let index = i % self.max_count;
+            new_storage.store_slot(
+                self.queue[index].clone(),
+                self.get_compressed_text(index).cloned(),
+            );
         }
 
-        self.queue = new_storage.queue;
-        self.max_count = new_storage.max_count;
-        self.next = new_storage.next;
-        self.full = new_storage.full;
+        *self = new_storage;
     }
 }
 
@@ -115,15 +391,20 @@ impl LogMessagesSupplier for Storage {
     fn get_log_messages(
         &self,
         from_time_nanos: &Option<Nanos>,
-    ) -> Box<dyn Iterator<Item = &'_ LogMessage> + '_> {
-        Box::new(LogMessageIterator::create(self, from_time_nanos))
+    ) -> Box<dyn Iterator<Item = Cow<'_, LogMessage>> + '_> {
+        Box::new(
+            LogMessageIterator::create(self, from_time_nanos).map(|index| self.get_message(index)),
+        )
     }
 
     fn get_log_messages_reverse(
         &self,
         up_to_time_nanos: &Option<Nanos>,
-    ) -> Box<dyn Iterator<Item = &'_ LogMessage> + '_> {
-        Box::new(LogMessageIterator::create_reverse(self, up_to_time_nanos))
+    ) -> Box<dyn Iterator<Item = Cow<'_, LogMessage>> + '_> {
+        Box::new(
+            LogMessageIterator::create_reverse(self, up_to_time_nanos)
+                .map(|index| self.get_message(index)),
+        )
     }
 }
 
@@ -136,7 +417,7 @@ struct LogMessageIterator<'a> {
 
 impl<'a> LogMessageIterator<'a> {
     fn create(storage: &'a Storage, from_time_nanos: &Option<Nanos>) -> LogMessageIterator<'a> {
-        if storage.queue.is_empty() {
+        if storage.get_count() == 0 {
             return LogMessageIterator {
                 storage,
                 index: 0,
@@ -145,27 +426,16 @@ impl<'a> LogMessageIterator<'a> {
             };
         }
 
-        let mut iterator = if storage.full {
-            LogMessageIterator {
-                storage,
-                index: storage.next,
-                delta: 1,
-                next_count: storage.max_count,
-            }
-        } else {
-            LogMessageIterator {
-                storage,
-                index: 0,
-                delta: 1,
-                next_count: storage.next,
-            }
+        let mut iterator = LogMessageIterator {
+            storage,
+            index: storage.get_first_index(),
+            delta: 1,
+            next_count: storage.get_count(),
         };
 
         if from_time_nanos.is_some() {
             let from_time_nanos = from_time_nanos.unwrap();
-            while !iterator.is_done()
-                && iterator.get_current_message().unwrap().timeNanos <= from_time_nanos
-            {
+            while !iterator.is_done() && iterator.get_current_time() <= from_time_nanos {
                 iterator.shift_to_next();
             }
         }
@@ -177,7 +447,7 @@ impl<'a> LogMessageIterator<'a> {
         storage: &'a Storage,
         up_to_time_nanos: &Option<Nanos>,
     ) -> LogMessageIterator<'a> {
-        if storage.queue.is_empty() {
+        if storage.get_count() == 0 {
             return LogMessageIterator {
                 storage,
                 index: 0,
@@ -186,27 +456,16 @@ impl<'a> LogMessageIterator<'a> {
             };
         }
 
-        let mut iterator = if storage.full {
-            LogMessageIterator {
-                storage,
-                index: storage.max_count + storage.next - 1,
-                delta: -1,
-                next_count: storage.max_count,
-            }
-        } else {
-            LogMessageIterator {
-                storage,
-                index: storage.next - 1,
-                delta: -1,
-                next_count: storage.next,
-            }
+        let mut iterator = LogMessageIterator {
+            storage,
+            index: storage.max_count + storage.next - 1,
+            delta: -1,
+            next_count: storage.get_count(),
         };
 
         if up_to_time_nanos.is_some() {
             let up_to_time_nanos = up_to_time_nanos.unwrap();
-            while !iterator.is_done()
-                && iterator.get_current_message().unwrap().timeNanos >= up_to_time_nanos
-            {
+            while !iterator.is_done() && iterator.get_current_time() >= up_to_time_nanos {
                 iterator.shift_to_next();
             }
         }
@@ -214,8 +473,13 @@ impl<'a> LogMessageIterator<'a> {
         iterator
     }
 
-    fn get_current_message(&self) -> Option<&'a LogMessage> {
-        self.storage.queue.get(self.index % self.storage.max_count)
+    fn get_current_index(&self) -> usize {
+        self.index % self.storage.max_count
+    }
+
+    // messages are decoded by the storage, only the time is needed to position the iterator
+    fn get_current_time(&self) -> Nanos {
+        self.storage.queue[self.get_current_index()].timeNanos
     }
 
     fn is_done(&self) -> bool {
@@ -228,434 +492,685 @@ impl<'a> LogMessageIterator<'a> {
     }
 }
 
-impl<'a> Iterator for LogMessageIterator<'a> {
-    type Item = &'a LogMessage;
+/// Iterates indexes of the queue slots.
+impl Iterator for LogMessageIterator<'_> {
+    type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.is_done() {
             None
         } else {
-            let message = self.get_current_message();
+            let index = self.get_current_index();
             self.shift_to_next();
-            message
+            Some(index)
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::super::super::api_type::Nanos;
+    use super::super::super::ic_util::memory::VecMemory;
     use super::super::data_type::{
         LogMessage, LogMessagesInfo, LogMessagesStorage, LogMessagesSupplier,
     };
-    use super::super::store::Storage;
-
-    #[test]
-    fn test_empty() {
-        let storage = Storage::new(4);
-
-        let mut iterator_box = storage.get_log_messages(&None);
-        let iterator = iterator_box.as_mut();
-        assert_eq!(iterator.next().is_none(), true);
-
-        let mut iterator_box = storage.get_log_messages_reverse(&None);
-        let iterator = iterator_box.as_mut();
-        assert_eq!(iterator.next().is_none(), true);
+    use super::super::store::{get_log_message_size, Storage};
+    use super::stable::{StableStorage, MIN_CAPACITY_IN_BYTES};
+    use std::borrow::Cow;
+
+    trait TestStorage: LogMessagesStorage + LogMessagesSupplier {}
+
+    impl<T: LogMessagesStorage + LogMessagesSupplier> TestStorage for T {}
+
+    /// Heap and stable storages must behave the same way.
+    fn create_storages(max_messages_count: usize) -> Vec<Box<dyn TestStorage>> {
+        vec![
+            Box::new(Storage::new(max_messages_count)),
+            Box::new(
+                StableStorage::init(
+                    Box::new(VecMemory::default()),
+                    max_messages_count,
+                    MIN_CAPACITY_IN_BYTES,
+                )
+                .unwrap(),
+            ),
+        ]
     }
 
     #[test]
-    fn test_cyclic() {
-        let mut storage = Storage::new(4);
-
-        {
-            storage.store_log_message(LogMessage {
-                timeNanos: 10,
-                message: String::from("time 10"),
-            });
-
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 10, "time 10");
-            assert_eq!(iterator.next().is_none(), true);
-
-            let mut iterator_box = storage.get_log_messages_reverse(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 10, "time 10");
-            assert_eq!(iterator.next().is_none(), true);
-        }
-
-        {
-            storage.store_log_message(LogMessage {
-                timeNanos: 20,
-                message: String::from("time 20"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 30,
-                message: String::from("time 30"),
-            });
-
+    fn test_empty() {
+        for storage in create_storages(4) {
             let mut iterator_box = storage.get_log_messages(&None);
             let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 10, "time 10");
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            validate_message(iterator.next().unwrap(), 30, "time 30");
             assert_eq!(iterator.next().is_none(), true);
 
             let mut iterator_box = storage.get_log_messages_reverse(&None);
             let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            validate_message(iterator.next().unwrap(), 10, "time 10");
             assert_eq!(iterator.next().is_none(), true);
         }
+    }
 
-        {
-            storage.store_log_message(LogMessage {
-                timeNanos: 40,
-                message: String::from("time 40"),
-            });
-
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 10, "time 10");
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            assert_eq!(iterator.next().is_none(), true);
-
-            let mut iterator_box = storage.get_log_messages_reverse(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            validate_message(iterator.next().unwrap(), 10, "time 10");
-            assert_eq!(iterator.next().is_none(), true);
-        }
+    #[test]
+    fn test_cyclic() {
+        for mut storage in create_storages(4) {
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 10,
+                    message: String::from("time 10"),
+                    ..Default::default()
+                });
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 10, "time 10");
+                assert_eq!(iterator.next().is_none(), true);
+
+                let mut iterator_box = storage.get_log_messages_reverse(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 10, "time 10");
+                assert_eq!(iterator.next().is_none(), true);
+            }
 
-        {
-            storage.store_log_message(LogMessage {
-                timeNanos: 50,
-                message: String::from("time 50"),
-            });
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 20,
+                    message: String::from("time 20"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 30,
+                    message: String::from("time 30"),
+                    ..Default::default()
+                });
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 10, "time 10");
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                assert_eq!(iterator.next().is_none(), true);
+
+                let mut iterator_box = storage.get_log_messages_reverse(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                validate_message(iterator.next().unwrap(), 10, "time 10");
+                assert_eq!(iterator.next().is_none(), true);
+            }
 
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            validate_message(iterator.next().unwrap(), 50, "time 50");
-            assert_eq!(iterator.next().is_none(), true);
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 40,
+                    message: String::from("time 40"),
+                    ..Default::default()
+                });
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 10, "time 10");
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                assert_eq!(iterator.next().is_none(), true);
+
+                let mut iterator_box = storage.get_log_messages_reverse(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                validate_message(iterator.next().unwrap(), 10, "time 10");
+                assert_eq!(iterator.next().is_none(), true);
+            }
 
-            let mut iterator_box = storage.get_log_messages_reverse(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 50, "time 50");
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            assert_eq!(iterator.next().is_none(), true);
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 50,
+                    message: String::from("time 50"),
+                    ..Default::default()
+                });
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                validate_message(iterator.next().unwrap(), 50, "time 50");
+                assert_eq!(iterator.next().is_none(), true);
+
+                let mut iterator_box = storage.get_log_messages_reverse(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 50, "time 50");
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                assert_eq!(iterator.next().is_none(), true);
+            }
         }
     }
 
     #[test]
     fn test_cyclic_with_from() {
-        let mut storage = Storage::new(4);
-
-        {
-            storage.store_log_message(LogMessage {
-                timeNanos: 10,
-                message: String::from("time 10"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 20,
-                message: String::from("time 20"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 30,
-                message: String::from("time 30"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 40,
-                message: String::from("time 40"),
-            });
-
-            let mut iterator_box = storage.get_log_messages(&Some(20));
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            assert_eq!(iterator.next().is_none(), true);
-
-            let mut iterator_box = storage.get_log_messages_reverse(&Some(20));
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 10, "time 10");
-            assert_eq!(iterator.next().is_none(), true);
+        for mut storage in create_storages(4) {
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 10,
+                    message: String::from("time 10"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 20,
+                    message: String::from("time 20"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 30,
+                    message: String::from("time 30"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 40,
+                    message: String::from("time 40"),
+                    ..Default::default()
+                });
+
+                let mut iterator_box = storage.get_log_messages(&Some(20));
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                assert_eq!(iterator.next().is_none(), true);
+
+                let mut iterator_box = storage.get_log_messages_reverse(&Some(20));
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 10, "time 10");
+                assert_eq!(iterator.next().is_none(), true);
+            }
         }
     }
 
     #[test]
     fn test_set_max_size() {
-        let mut storage = Storage::new(2);
+        for mut storage in create_storages(2) {
+            {
+                storage.set_max_messages_count(3);
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                assert_eq!(iterator.next().is_none(), true);
+            }
 
-        {
-            storage.set_max_messages_count(3);
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            assert_eq!(iterator.next().is_none(), true);
-        }
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 10,
+                    message: String::from("time 10"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 20,
+                    message: String::from("time 20"),
+                    ..Default::default()
+                });
+                storage.set_max_messages_count(4);
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 10, "time 10");
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                assert_eq!(iterator.next().is_none(), true);
+            }
 
-        {
-            storage.store_log_message(LogMessage {
-                timeNanos: 10,
-                message: String::from("time 10"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 20,
-                message: String::from("time 20"),
-            });
-            storage.set_max_messages_count(4);
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 30,
+                    message: String::from("time 30"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 40,
+                    message: String::from("time 40"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 50,
+                    message: String::from("time 50"),
+                    ..Default::default()
+                });
+                storage.set_max_messages_count(5);
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                validate_message(iterator.next().unwrap(), 50, "time 50");
+                assert_eq!(iterator.next().is_none(), true);
+            }
 
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 10, "time 10");
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            assert_eq!(iterator.next().is_none(), true);
-        }
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 60,
+                    message: String::from("time 60"),
+                    ..Default::default()
+                });
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                validate_message(iterator.next().unwrap(), 50, "time 50");
+                validate_message(iterator.next().unwrap(), 60, "time 60");
+                assert_eq!(iterator.next().is_none(), true);
+            }
 
-        {
-            storage.store_log_message(LogMessage {
-                timeNanos: 30,
-                message: String::from("time 30"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 40,
-                message: String::from("time 40"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 50,
-                message: String::from("time 50"),
-            });
-            storage.set_max_messages_count(5);
+            {
+                storage.set_max_messages_count(3);
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                validate_message(iterator.next().unwrap(), 50, "time 50");
+                validate_message(iterator.next().unwrap(), 60, "time 60");
+                assert_eq!(iterator.next().is_none(), true);
+            }
 
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            validate_message(iterator.next().unwrap(), 50, "time 50");
-            assert_eq!(iterator.next().is_none(), true);
+            {
+                storage.set_max_messages_count(6);
+                storage.set_max_messages_count(3);
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                validate_message(iterator.next().unwrap(), 50, "time 50");
+                validate_message(iterator.next().unwrap(), 60, "time 60");
+                assert_eq!(iterator.next().is_none(), true);
+            }
         }
+    }
 
-        {
-            storage.store_log_message(LogMessage {
-                timeNanos: 60,
-                message: String::from("time 60"),
-            });
+    #[test]
+    fn test_set_max_size_not_full_less() {
+        for mut storage in create_storages(4) {
+            storage.set_max_messages_count(5);
 
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            validate_message(iterator.next().unwrap(), 50, "time 50");
-            validate_message(iterator.next().unwrap(), 60, "time 60");
-            assert_eq!(iterator.next().is_none(), true);
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 10,
+                    message: String::from("time 10"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 20,
+                    message: String::from("time 20"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 30,
+                    message: String::from("time 30"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 40,
+                    message: String::from("time 40"),
+                    ..Default::default()
+                });
+                storage.set_max_messages_count(3);
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                assert_eq!(iterator.next().is_none(), true);
+            }
         }
+    }
 
-        {
-            storage.set_max_messages_count(3);
-
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            validate_message(iterator.next().unwrap(), 50, "time 50");
-            validate_message(iterator.next().unwrap(), 60, "time 60");
-            assert_eq!(iterator.next().is_none(), true);
+    #[test]
+    fn test_set_max_size_not_full_more() {
+        for mut storage in create_storages(5) {
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 10,
+                    message: String::from("time 10"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 20,
+                    message: String::from("time 20"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 30,
+                    message: String::from("time 30"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 40,
+                    message: String::from("time 40"),
+                    ..Default::default()
+                });
+                storage.set_max_messages_count(6);
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 10, "time 10");
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                assert_eq!(iterator.next().is_none(), true);
+            }
         }
+    }
 
-        {
-            storage.set_max_messages_count(6);
-            storage.set_max_messages_count(3);
-
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            validate_message(iterator.next().unwrap(), 50, "time 50");
-            validate_message(iterator.next().unwrap(), 60, "time 60");
-            assert_eq!(iterator.next().is_none(), true);
+    #[test]
+    fn test_set_max_size_full_less() {
+        for mut storage in create_storages(4) {
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 10,
+                    message: String::from("time 10"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 20,
+                    message: String::from("time 20"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 30,
+                    message: String::from("time 30"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 40,
+                    message: String::from("time 40"),
+                    ..Default::default()
+                });
+                storage.set_max_messages_count(3);
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                assert_eq!(iterator.next().is_none(), true);
+            }
         }
     }
 
     #[test]
-    fn test_set_max_size_not_full_less() {
-        let mut storage = Storage::new(4);
-        storage.set_max_messages_count(5);
-
-        {
-            storage.store_log_message(LogMessage {
-                timeNanos: 10,
-                message: String::from("time 10"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 20,
-                message: String::from("time 20"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 30,
-                message: String::from("time 30"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 40,
-                message: String::from("time 40"),
-            });
-            storage.set_max_messages_count(3);
-
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            assert_eq!(iterator.next().is_none(), true);
+    fn test_set_max_size_full_more() {
+        for mut storage in create_storages(3) {
+            {
+                storage.store_log_message(LogMessage {
+                    timeNanos: 10,
+                    message: String::from("time 10"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 20,
+                    message: String::from("time 20"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 30,
+                    message: String::from("time 30"),
+                    ..Default::default()
+                });
+                storage.store_log_message(LogMessage {
+                    timeNanos: 40,
+                    message: String::from("time 40"),
+                    ..Default::default()
+                });
+                storage.set_max_messages_count(5);
+
+                let mut iterator_box = storage.get_log_messages(&None);
+                let iterator = iterator_box.as_mut();
+                validate_message(iterator.next().unwrap(), 20, "time 20");
+                validate_message(iterator.next().unwrap(), 30, "time 30");
+                validate_message(iterator.next().unwrap(), 40, "time 40");
+                assert_eq!(iterator.next().is_none(), true);
+            }
         }
     }
 
     #[test]
-    fn test_set_max_size_not_full_more() {
-        let mut storage = Storage::new(5);
+    fn test_info() {
+        for mut storage in create_storages(2) {
+            assert_eq!(storage.get_log_messages_count(), 0);
+            assert_eq!(storage.get_first_log_message_time().is_none(), true);
+            assert_eq!(storage.get_last_log_message_time().is_none(), true);
+            assert_eq!(storage.get_log_messages_bytes(), 0);
 
-        {
             storage.store_log_message(LogMessage {
                 timeNanos: 10,
                 message: String::from("time 10"),
+                ..Default::default()
             });
+            assert_eq!(storage.get_log_messages_count(), 1);
+            assert_eq!(storage.get_first_log_message_time().unwrap(), 10_u64);
+            assert_eq!(storage.get_last_log_message_time().unwrap(), 10_u64);
+            let bytes = storage.get_log_messages_bytes();
+            assert!(bytes > 0);
+            assert!(bytes <= storage.get_max_log_messages_bytes().unwrap_or(u64::MAX));
+
             storage.store_log_message(LogMessage {
                 timeNanos: 20,
                 message: String::from("time 20"),
+                ..Default::default()
             });
+            assert_eq!(storage.get_log_messages_count(), 2);
+            assert_eq!(storage.get_first_log_message_time().unwrap(), 10_u64);
+            assert_eq!(storage.get_last_log_message_time().unwrap(), 20_u64);
+
             storage.store_log_message(LogMessage {
                 timeNanos: 30,
                 message: String::from("time 30"),
+                ..Default::default()
             });
+            assert_eq!(storage.get_log_messages_count(), 2);
+            assert_eq!(storage.get_first_log_message_time().unwrap(), 20_u64);
+            assert_eq!(storage.get_last_log_message_time().unwrap(), 30_u64);
+
             storage.store_log_message(LogMessage {
                 timeNanos: 40,
                 message: String::from("time 40"),
+                ..Default::default()
             });
-            storage.set_max_messages_count(6);
+            assert_eq!(storage.get_log_messages_count(), 2);
+            assert_eq!(storage.get_first_log_message_time().unwrap(), 30_u64);
+            assert_eq!(storage.get_last_log_message_time().unwrap(), 40_u64);
+        }
+    }
 
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 10, "time 10");
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            assert_eq!(iterator.next().is_none(), true);
+    fn validate_message(message: Cow<'_, LogMessage>, nanos: Nanos, text: &str) {
+        assert_eq!(message.timeNanos, nanos);
+        assert_eq!(message.message, text);
+    }
+
+    fn create_message(time_nanos: Nanos, message: &str) -> LogMessage {
+        LogMessage {
+            timeNanos: time_nanos,
+            message: String::from(message),
+            ..Default::default()
         }
     }
 
+    fn get_times(storage: &Storage) -> Vec<Nanos> {
+        storage
+            .get_log_messages(&None)
+            .map(|message| message.timeNanos)
+            .collect()
+    }
+
+    fn get_times_reverse(storage: &Storage) -> Vec<Nanos> {
+        storage
+            .get_log_messages_reverse(&None)
+            .map(|message| message.timeNanos)
+            .collect()
+    }
+
     #[test]
-    fn test_set_max_size_full_less() {
+    fn test_max_bytes() {
+        let size = get_log_message_size(&create_message(10, "time 10"));
         let mut storage = Storage::new(4);
+        storage.set_max_bytes(Some(size * 2));
+        assert_eq!(storage.get_log_messages_bytes(), 0);
+        assert_eq!(storage.get_max_log_messages_bytes(), Some(size * 2));
+
+        storage.store_log_message(create_message(10, "time 10"));
+        storage.store_log_message(create_message(20, "time 20"));
+        storage.store_log_message(create_message(30, "time 30"));
+        assert_eq!(get_times(&storage), vec![20, 30]);
+        assert_eq!(get_times_reverse(&storage), vec![30, 20]);
+        assert_eq!(storage.get_log_messages_count(), 2);
+        assert_eq!(storage.get_first_log_message_time(), Some(20));
+        assert_eq!(storage.get_last_log_message_time(), Some(30));
+        assert_eq!(storage.get_log_messages_bytes(), size * 2);
+
+        // slots emptied by the budget are reused after the ring wraps
+        storage.store_log_message(create_message(40, "time 40"));
+        storage.store_log_message(create_message(50, "time 50"));
+        storage.store_log_message(create_message(60, "time 60"));
+        assert_eq!(get_times(&storage), vec![50, 60]);
+        assert_eq!(get_times_reverse(&storage), vec![60, 50]);
+        assert_eq!(storage.get_log_messages_bytes(), size * 2);
+
+        // a longer message evicts as many messages as needed
+        storage.store_log_message(create_message(70, "time 70 time 70"));
+        assert_eq!(get_times(&storage), vec![70]);
+        let long_size = get_log_message_size(&create_message(70, "time 70 time 70"));
+        assert_eq!(storage.get_log_messages_bytes(), long_size);
+
+        // a message bigger than the budget is kept alone
+        storage.set_max_bytes(Some(size / 2));
+        assert_eq!(storage.get_log_messages_count(), 0);
+        assert_eq!(storage.get_log_messages_bytes(), 0);
+        storage.store_log_message(create_message(80, "time 80"));
+        storage.store_log_message(create_message(90, "time 90"));
+        assert_eq!(get_times(&storage), vec![90]);
+
+        storage.set_max_bytes(None);
+        storage.store_log_message(create_message(100, "time 100"));
+        assert_eq!(get_times(&storage), vec![90, 100]);
+        assert_eq!(storage.get_max_log_messages_bytes(), None);
+    }
 
-        {
-            storage.store_log_message(LogMessage {
-                timeNanos: 10,
-                message: String::from("time 10"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 20,
-                message: String::from("time 20"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 30,
-                message: String::from("time 30"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 40,
-                message: String::from("time 40"),
-            });
-            storage.set_max_messages_count(3);
-
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            assert_eq!(iterator.next().is_none(), true);
+    #[test]
+    fn test_max_bytes_set_max_size() {
+        let size = get_log_message_size(&create_message(10, "time 10"));
+        let mut storage = Storage::new(4);
+        storage.set_max_bytes(Some(size * 3));
+        for time in 1..=6 {
+            storage.store_log_message(create_message(time * 10, &format!("time {}", time * 10)));
         }
+        assert_eq!(get_times(&storage), vec![40, 50, 60]);
+
+        // only kept messages are moved, the newest ones win
+        storage.set_max_messages_count(2);
+        assert_eq!(get_times(&storage), vec![50, 60]);
+        assert_eq!(storage.get_log_messages_bytes(), size * 2);
+        storage.set_max_messages_count(8);
+        assert_eq!(get_times(&storage), vec![50, 60]);
+        assert_eq!(storage.get_max_log_messages_bytes(), Some(size * 3));
+
+        storage.store_log_message(create_message(70, "time 70"));
+        storage.store_log_message(create_message(80, "time 80"));
+        assert_eq!(get_times(&storage), vec![60, 70, 80]);
+        assert_eq!(get_times_reverse(&storage), vec![80, 70, 60]);
     }
 
     #[test]
-    fn test_set_max_size_full_more() {
-        let mut storage = Storage::new(3);
-
-        {
-            storage.store_log_message(LogMessage {
-                timeNanos: 10,
-                message: String::from("time 10"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 20,
-                message: String::from("time 20"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 30,
-                message: String::from("time 30"),
-            });
-            storage.store_log_message(LogMessage {
-                timeNanos: 40,
-                message: String::from("time 40"),
-            });
-            storage.set_max_messages_count(5);
-
-            let mut iterator_box = storage.get_log_messages(&None);
-            let iterator = iterator_box.as_mut();
-            validate_message(iterator.next().unwrap(), 20, "time 20");
-            validate_message(iterator.next().unwrap(), 30, "time 30");
-            validate_message(iterator.next().unwrap(), 40, "time 40");
-            assert_eq!(iterator.next().is_none(), true);
+    fn test_compression() {
+        let mut storage = Storage::new(4);
+        assert_eq!(storage.get_compression_ratio(), None);
+        storage.store_log_message(create_message(10, "plain"));
+        storage.set_compression(true);
+        assert!(storage.is_compression_enabled());
+        for time in 2..=5 {
+            let text = format!(
+                r#"{{"event":"transfer","from":"aaaaa-aa","time":{}}}"#,
+                time
+            );
+            storage.store_log_message(create_message(time * 10, &text));
         }
+        // texts are decoded when read
+        let messages: Vec<_> = storage.get_log_messages(&None).collect();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(
+            messages[3].message,
+            r#"{"event":"transfer","from":"aaaaa-aa","time":5}"#
+        );
+        assert!(matches!(messages[3], Cow::Owned(_)));
+        let messages: Vec<_> = storage.get_log_messages_reverse(&Some(50)).collect();
+        assert_eq!(
+            messages[0].message,
+            r#"{"event":"transfer","from":"aaaaa-aa","time":4}"#
+        );
+        assert!(storage.get_compression_ratio().unwrap() > 3.0);
+
+        // compressed texts are moved as they are
+        storage.set_max_messages_count(2);
+        assert_eq!(get_times(&storage), vec![40, 50]);
+        let messages: Vec<_> = storage.get_log_messages(&None).collect();
+        assert_eq!(
+            messages[0].message,
+            r#"{"event":"transfer","from":"aaaaa-aa","time":4}"#
+        );
+        let bytes = storage.get_log_messages_bytes();
+        storage.count_bytes();
+        assert_eq!(storage.get_log_messages_bytes(), bytes);
+
+        // stored texts stay compressed after the compression is disabled
+        storage.set_compression(false);
+        storage.store_log_message(create_message(60, "plain"));
+        let messages: Vec<_> = storage.get_log_messages(&None).collect();
+        assert_eq!(
+            messages[0].message,
+            r#"{"event":"transfer","from":"aaaaa-aa","time":5}"#
+        );
+        assert!(matches!(messages[1], Cow::Borrowed(_)));
+        assert!(storage.get_compression_ratio().unwrap() > 1.0);
     }
 
     #[test]
-    fn test_info() {
-        let mut storage = Storage::new(2);
-        assert_eq!(storage.get_log_messages_count(), 0);
-        assert_eq!(storage.get_first_log_message_time().is_none(), true);
-        assert_eq!(storage.get_last_log_message_time().is_none(), true);
-
-        storage.store_log_message(LogMessage {
-            timeNanos: 10,
-            message: String::from("time 10"),
-        });
-        assert_eq!(storage.get_log_messages_count(), 1);
-        assert_eq!(storage.get_first_log_message_time().unwrap(), 10_u64);
-        assert_eq!(storage.get_last_log_message_time().unwrap(), 10_u64);
-
-        storage.store_log_message(LogMessage {
-            timeNanos: 20,
-            message: String::from("time 20"),
-        });
-        assert_eq!(storage.get_log_messages_count(), 2);
-        assert_eq!(storage.get_first_log_message_time().unwrap(), 10_u64);
-        assert_eq!(storage.get_last_log_message_time().unwrap(), 20_u64);
-
-        storage.store_log_message(LogMessage {
-            timeNanos: 30,
-            message: String::from("time 30"),
-        });
-        assert_eq!(storage.get_log_messages_count(), 2);
-        assert_eq!(storage.get_first_log_message_time().unwrap(), 20_u64);
-        assert_eq!(storage.get_last_log_message_time().unwrap(), 30_u64);
-
-        storage.store_log_message(LogMessage {
-            timeNanos: 40,
-            message: String::from("time 40"),
-        });
-        assert_eq!(storage.get_log_messages_count(), 2);
-        assert_eq!(storage.get_first_log_message_time().unwrap(), 30_u64);
-        assert_eq!(storage.get_last_log_message_time().unwrap(), 40_u64);
+    fn test_compression_different_lengths() {
+        let mut storage = Storage::new(10);
+        storage.set_compression(true);
+        let texts = [
+            "transfer from alice to bob amount 100",
+            "transfer from carol to dave amount 2500",
+            "перевод от алисы к бобу 🦀",
+            "transfer from eve to alice amount 7",
+            "перевод от боба к алисе 🦀🦀",
+            "transfer from carol to dave amount 2500",
+        ];
+        for (time, text) in texts.iter().enumerate() {
+            storage.store_log_message(create_message(time as u64, text));
+        }
+        // older texts are decoded after the dictionary has grown
+        let messages: Vec<_> = storage
+            .get_log_messages(&None)
+            .map(|message| message.message.clone())
+            .collect();
+        assert_eq!(messages, texts);
+        assert!(storage.compressed_texts.iter().flatten().flatten().count() > 1);
     }
 
-    fn validate_message(message: &LogMessage, nanos: Nanos, text: &str) {
-        assert_eq!(message.timeNanos, nanos);
-        assert_eq!(message.message, text);
+    #[test]
+    fn test_compression_upgrade() {
+        use candid::{Decode, Encode};
+        let mut storage = Storage::new(3);
+        storage.set_compression(true);
+        storage.set_max_bytes(Some(1000));
+        for time in 1..=5 {
+            storage.store_log_message(create_message(time, &format!("message number {}", time)));
+        }
+        let bytes = Encode!(&storage).unwrap();
+        let storage = Decode!(&bytes, Storage).unwrap();
+        assert!(storage.is_compression_enabled());
+        assert_eq!(get_times(&storage), vec![3, 4, 5]);
+        let messages: Vec<_> = storage.get_log_messages(&None).collect();
+        assert_eq!(messages[2].message, "message number 5");
     }
 }
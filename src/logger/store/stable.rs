@@ -0,0 +1,537 @@
+use super::super::super::api_type::Nanos;
+use super::super::super::ic_util::memory::{ensure_memory_size, Memory};
+use super::super::data_type::{
+    LogMessage, LogMessagesInfo, LogMessagesStorage, LogMessagesSupplier,
+};
+use std::borrow::Cow;
+
+/// Log messages ring buffer which keeps variable-length records directly in the (stable) memory,
+/// so nothing has to be serialized on canister upgrade.
+///
+/// Memory layout: header followed by the ring of `capacity` bytes.
+/// Each record is `[payload length: u32][time nanos: u64][candid payload][payload length: u32]`,
+/// the trailing length allows to iterate records backwards.
+/// A record never wraps: if it does not fit till the end of the ring,
+/// it is written at the beginning and `wrap_end` remembers where the upper part ends.
+pub struct StableStorage {
+    memory: Box<dyn Memory>,
+    header: Header,
+}
+
+const MAGIC: &[u8; 4] = b"CGLG";
+const LAYOUT_VERSION: u8 = 1;
+const HEADER_SIZE: u64 = 128;
+const RECORD_OVERHEAD: u64 = 4 + 8 + 4;
+
+pub const MIN_CAPACITY_IN_BYTES: u64 = 16 * 1024;
+
+#[derive(Clone, Copy)]
+struct Header {
+    max_count: u64,
+    capacity: u64,
+    // offset of the oldest record
+    head: u64,
+    // offset to write next record at
+    tail: u64,
+    // offset of the newest record
+    last: u64,
+    // end of the records in the upper part of the ring, valid when `wrapped`
+    wrap_end: u64,
+    count: u64,
+    wrapped: bool,
+}
+
+impl Header {
+    fn to_bytes(self) -> [u8; HEADER_SIZE as usize] {
+        let mut bytes = [0_u8; HEADER_SIZE as usize];
+        bytes[0..4].copy_from_slice(MAGIC);
+        bytes[4] = LAYOUT_VERSION;
+        bytes[5] = self.wrapped as u8;
+        let values = [
+            self.max_count,
+            self.capacity,
+            self.head,
+            self.tail,
+            self.last,
+            self.wrap_end,
+            self.count,
+        ];
+        for (index, value) in values.iter().enumerate() {
+            let offset = 8 + index * 8;
+            bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; HEADER_SIZE as usize]) -> Result<Header, &'static str> {
+        if &bytes[0..4] != MAGIC {
+            return Err("Memory does not contain log messages storage");
+        }
+        if bytes[4] != LAYOUT_VERSION {
+            return Err("Unsupported log messages storage layout version");
+        }
+        let value = |index: usize| {
+            let offset = 8 + index * 8;
+            u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+        };
+        Ok(Header {
+            wrapped: bytes[5] != 0,
+            max_count: value(0),
+            capacity: value(1),
+            head: value(2),
+            tail: value(3),
+            last: value(4),
+            wrap_end: value(5),
+            count: value(6),
+        })
+    }
+}
+
+impl StableStorage {
+    /// Opens the storage previously created in the memory, or creates a new one.
+    /// The capacity of an already created storage is not changed.
+    pub fn init(
+        memory: Box<dyn Memory>,
+        max_messages_count: usize,
+        capacity_in_bytes: u64,
+    ) -> Result<Self, &'static str> {
+        let header = if memory.size() > 0 {
+            let mut bytes = [0_u8; HEADER_SIZE as usize];
+            memory.read(0, &mut bytes);
+            if &bytes[0..4] == MAGIC {
+                Some(Header::from_bytes(&bytes)?)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        match header {
+            Some(header) => Ok(Self { memory, header }),
+            None => {
+                if capacity_in_bytes < MIN_CAPACITY_IN_BYTES {
+                    return Err("Too small log messages storage capacity");
+                }
+                ensure_memory_size(memory.as_ref(), HEADER_SIZE + capacity_in_bytes)?;
+                let storage = Self {
+                    memory,
+                    header: Header {
+                        max_count: max_messages_count as u64,
+                        capacity: capacity_in_bytes,
+                        head: 0,
+                        tail: 0,
+                        last: 0,
+                        wrap_end: 0,
+                        count: 0,
+                        wrapped: false,
+                    },
+                };
+                storage.save_header();
+                Ok(storage)
+            }
+        }
+    }
+
+    fn save_header(&self) {
+        self.memory.write(0, &self.header.to_bytes());
+    }
+
+    fn read_u32(&self, offset: u64) -> u64 {
+        let mut bytes = [0_u8; 4];
+        self.memory.read(HEADER_SIZE + offset, &mut bytes);
+        u32::from_le_bytes(bytes) as u64
+    }
+
+    fn read_time(&self, offset: u64) -> Nanos {
+        let mut bytes = [0_u8; 8];
+        self.memory.read(HEADER_SIZE + offset + 4, &mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn read_message(&self, offset: u64) -> LogMessage {
+        let length = self.read_u32(offset);
+        let mut payload = vec![0_u8; length as usize];
+        self.memory.read(HEADER_SIZE + offset + 12, &mut payload);
+        candid::decode_one(&payload).expect("Can not decode stored log message")
+    }
+
+    fn record_size(&self, offset: u64) -> u64 {
+        self.read_u32(offset) + RECORD_OVERHEAD
+    }
+
+    fn next_offset(&self, offset: u64) -> u64 {
+        let next = offset + self.record_size(offset);
+        if self.header.wrapped && next == self.header.wrap_end {
+            0
+        } else {
+            next
+        }
+    }
+
+    fn previous_offset(&self, offset: u64) -> u64 {
+        let end = if offset == 0 && self.header.wrapped {
+            self.header.wrap_end
+        } else {
+            offset
+        };
+        let length = self.read_u32(end - 4);
+        end - length - RECORD_OVERHEAD
+    }
+
+    fn reset_if_empty(&mut self) {
+        if self.header.count == 0 {
+            self.header.head = 0;
+            self.header.tail = 0;
+            self.header.last = 0;
+            self.header.wrap_end = 0;
+            self.header.wrapped = false;
+        }
+    }
+
+    fn evict_first(&mut self) {
+        let next = self.header.head + self.record_size(self.header.head);
+        self.header.count -= 1;
+        if self.header.wrapped && next == self.header.wrap_end {
+            self.header.head = 0;
+            self.header.wrapped = false;
+        } else {
+            self.header.head = next;
+        }
+        self.reset_if_empty();
+    }
+
+    fn remove_last(&mut self) {
+        let offset = self.header.last;
+        self.header.count -= 1;
+        if self.header.count > 0 {
+            self.header.last = self.previous_offset(offset);
+        }
+        if self.header.wrapped && offset == 0 {
+            // the lower part of the ring gets empty
+            self.header.tail = self.header.wrap_end;
+            self.header.wrapped = false;
+        } else {
+            self.header.tail = offset;
+        }
+        self.reset_if_empty();
+    }
+
+    /// Evicts the oldest records until `size` bytes fit and returns the offset to write at.
+    fn allocate(&mut self, size: u64) -> u64 {
+        loop {
+            if self.header.wrapped {
+                if self.header.tail + size <= self.header.head {
+                    return self.header.tail;
+                }
+            } else if self.header.tail + size <= self.header.capacity {
+                return self.header.tail;
+            } else if self.header.count > 0 && size <= self.header.head {
+                self.header.wrap_end = self.header.tail;
+                self.header.wrapped = true;
+                self.header.tail = 0;
+                return 0;
+            }
+            self.evict_first();
+        }
+    }
+
+    fn iterate(&self, reverse: bool) -> StableLogMessageIterator<'_> {
+        StableLogMessageIterator {
+            storage: self,
+            offset: if reverse {
+                self.header.last
+            } else {
+                self.header.head
+            },
+            reverse,
+            next_count: self.header.count,
+        }
+    }
+}
+
+impl LogMessagesInfo for StableStorage {
+    fn get_log_messages_count(&self) -> u32 {
+        self.header.count as u32
+    }
+
+    fn get_first_log_message_time(&self) -> Option<Nanos> {
+        if self.header.count == 0 {
+            None
+        } else {
+            Some(self.read_time(self.header.head))
+        }
+    }
+
+    fn get_last_log_message_time(&self) -> Option<Nanos> {
+        if self.header.count == 0 {
+            None
+        } else {
+            Some(self.read_time(self.header.last))
+        }
+    }
+
+    fn get_log_messages_bytes(&self) -> u64 {
+        if self.header.wrapped {
+            self.header.wrap_end - self.header.head + self.header.tail
+        } else {
+            self.header.tail - self.header.head
+        }
+    }
+
+    fn get_max_log_messages_bytes(&self) -> Option<u64> {
+        Some(self.header.capacity)
+    }
+
+    fn get_compression_ratio(&self) -> Option<f64> {
+        None
+    }
+}
+
+impl LogMessagesStorage for StableStorage {
+    fn store_log_message(&mut self, log_message: LogMessage) {
+        let payload = candid::encode_one(&log_message).expect("Can not encode log message");
+        let size = payload.len() as u64 + RECORD_OVERHEAD;
+        if size > self.header.capacity {
+            return;
+        }
+
+        while self.header.count >= self.header.max_count {
+            self.evict_first();
+        }
+
+        let offset = self.allocate(size);
+        let length = (payload.len() as u32).to_le_bytes();
+        let mut record = Vec::with_capacity(size as usize);
+        record.extend_from_slice(&length);
+        record.extend_from_slice(&log_message.timeNanos.to_le_bytes());
+        record.extend_from_slice(&payload);
+        record.extend_from_slice(&length);
+        self.memory.write(HEADER_SIZE + offset, &record);
+
+        self.header.last = offset;
+        self.header.tail = offset + size;
+        self.header.count += 1;
+        self.save_header();
+    }
+
+    fn get_last_log_message(&self) -> Option<Cow<'_, LogMessage>> {
+        if self.header.count == 0 {
+            None
+        } else {
+            Some(Cow::Owned(self.read_message(self.header.last)))
+        }
+    }
+
+    fn replace_last_log_message(&mut self, log_message: LogMessage) {
+        if self.header.count > 0 {
+            self.remove_last();
+        }
+        self.store_log_message(log_message);
+    }
+
+    fn set_max_messages_count(&mut self, new_max_messages_count: usize) {
+        self.header.max_count = new_max_messages_count as u64;
+        while self.header.count > self.header.max_count {
+            self.evict_first();
+        }
+        self.save_header();
+    }
+}
+
+impl LogMessagesSupplier for StableStorage {
+    fn get_log_messages(
+        &self,
+        from_time_nanos: &Option<Nanos>,
+    ) -> Box<dyn Iterator<Item = Cow<'_, LogMessage>> + '_> {
+        let mut iterator = self.iterate(false);
+        if let Some(from_time_nanos) = from_time_nanos {
+            iterator.skip_while_time(|time| time <= *from_time_nanos);
+        }
+        Box::new(iterator)
+    }
+
+    fn get_log_messages_reverse(
+        &self,
+        up_to_time_nanos: &Option<Nanos>,
+    ) -> Box<dyn Iterator<Item = Cow<'_, LogMessage>> + '_> {
+        let mut iterator = self.iterate(true);
+        if let Some(up_to_time_nanos) = up_to_time_nanos {
+            iterator.skip_while_time(|time| time >= *up_to_time_nanos);
+        }
+        Box::new(iterator)
+    }
+}
+
+struct StableLogMessageIterator<'a> {
+    storage: &'a StableStorage,
+    offset: u64,
+    reverse: bool,
+    next_count: u64,
+}
+
+impl StableLogMessageIterator<'_> {
+    fn skip_while_time<P>(&mut self, predicate: P)
+    where
+        P: Fn(Nanos) -> bool,
+    {
+        while self.next_count > 0 && predicate(self.storage.read_time(self.offset)) {
+            self.shift_to_next();
+        }
+    }
+
+    fn shift_to_next(&mut self) {
+        self.next_count -= 1;
+        if self.next_count > 0 {
+            self.offset = if self.reverse {
+                self.storage.previous_offset(self.offset)
+            } else {
+                self.storage.next_offset(self.offset)
+            };
+        }
+    }
+}
+
+impl<'a> Iterator for StableLogMessageIterator<'a> {
+    type Item = Cow<'a, LogMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_count == 0 {
+            None
+        } else {
+            let message = self.storage.read_message(self.offset);
+            self.shift_to_next();
+            Some(Cow::Owned(message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::super::api_type::LogLevel;
+    use super::super::super::super::ic_util::memory::VecMemory;
+    use super::super::super::data_type::{
+        LogMessage, LogMessagesInfo, LogMessagesStorage, LogMessagesSupplier,
+    };
+    use super::{StableStorage, MIN_CAPACITY_IN_BYTES, RECORD_OVERHEAD};
+
+    fn create_message(time_nanos: u64, size: usize) -> LogMessage {
+        LogMessage {
+            timeNanos: time_nanos,
+            message: "x".repeat(size),
+            level: Some(LogLevel::info),
+            ..Default::default()
+        }
+    }
+
+    fn times(storage: &StableStorage, reverse: bool) -> Vec<u64> {
+        let iterator = if reverse {
+            storage.get_log_messages_reverse(&None)
+        } else {
+            storage.get_log_messages(&None)
+        };
+        iterator.map(|message| message.timeNanos).collect()
+    }
+
+    #[test]
+    fn test_too_small_capacity() {
+        let memory = VecMemory::default();
+        assert!(StableStorage::init(Box::new(memory), 10, MIN_CAPACITY_IN_BYTES - 1).is_err());
+    }
+
+    #[test]
+    fn test_wrap_by_bytes() {
+        let mut storage =
+            StableStorage::init(Box::new(VecMemory::default()), 1000, MIN_CAPACITY_IN_BYTES)
+                .unwrap();
+
+        // every record takes a bit more than 5000 bytes, so only 3 records fit
+        for time in 1..=10 {
+            storage.store_log_message(create_message(time, 5000));
+            let expected_first = if time > 3 { time - 2 } else { 1 };
+            let expected: Vec<u64> = (expected_first..=time).collect();
+            assert_eq!(times(&storage, false), expected);
+            assert_eq!(
+                times(&storage, true),
+                expected.iter().rev().copied().collect::<Vec<u64>>()
+            );
+            assert_eq!(storage.get_first_log_message_time(), Some(expected_first));
+            assert_eq!(storage.get_last_log_message_time(), Some(time));
+        }
+
+        // big message evicts several small ones
+        storage.store_log_message(create_message(11, 12000));
+        assert_eq!(times(&storage, false), vec![11]);
+        storage.store_log_message(create_message(12, 100));
+        assert_eq!(times(&storage, false), vec![11, 12]);
+        assert_eq!(times(&storage, true), vec![12, 11]);
+
+        // message bigger than capacity is skipped
+        storage.store_log_message(create_message(13, MIN_CAPACITY_IN_BYTES as usize));
+        assert_eq!(times(&storage, false), vec![11, 12]);
+    }
+
+    #[test]
+    fn test_replace_last() {
+        let mut storage =
+            StableStorage::init(Box::new(VecMemory::default()), 1000, MIN_CAPACITY_IN_BYTES)
+                .unwrap();
+        storage.replace_last_log_message(create_message(1, 100));
+        assert_eq!(times(&storage, false), vec![1]);
+        storage.replace_last_log_message(create_message(2, 5000));
+        assert_eq!(times(&storage, false), vec![2]);
+        let payload = candid::encode_one(create_message(2, 5000)).unwrap();
+        assert_eq!(
+            storage.get_log_messages_bytes(),
+            payload.len() as u64 + RECORD_OVERHEAD
+        );
+
+        // the last record is written at the beginning of the ring
+        for time in 3..=5 {
+            storage.store_log_message(create_message(time, 5000));
+        }
+        assert_eq!(times(&storage, false), vec![3, 4, 5]);
+        assert!(storage.header.wrapped);
+        let mut message = create_message(5, 4000);
+        message.repeatCount = Some(2);
+        storage.replace_last_log_message(message);
+        assert_eq!(times(&storage, false), vec![3, 4, 5]);
+        assert_eq!(times(&storage, true), vec![5, 4, 3]);
+        assert!(storage.header.wrapped);
+        assert_eq!(storage.get_last_log_message().unwrap().repeatCount, Some(2));
+
+        // the grown record does not fit before the oldest one any more
+        storage.replace_last_log_message(create_message(5, 5100));
+        assert_eq!(times(&storage, false), vec![4, 5]);
+        assert_eq!(times(&storage, true), vec![5, 4]);
+
+        storage.store_log_message(create_message(6, 100));
+        assert_eq!(times(&storage, false), vec![4, 5, 6]);
+    }
+
+    #[test]
+    fn test_reopen() {
+        let memory = VecMemory::default();
+        {
+            let mut storage =
+                StableStorage::init(Box::new(memory.clone()), 3, MIN_CAPACITY_IN_BYTES).unwrap();
+            for time in 1..=5 {
+                storage.store_log_message(create_message(time, 10));
+            }
+        }
+
+        let mut storage =
+            StableStorage::init(Box::new(memory), 100, 10 * MIN_CAPACITY_IN_BYTES).unwrap();
+        assert_eq!(storage.header.capacity, MIN_CAPACITY_IN_BYTES);
+        assert_eq!(storage.get_log_messages_count(), 3);
+        assert_eq!(times(&storage, false), vec![3, 4, 5]);
+
+        storage.store_log_message(create_message(6, 10));
+        assert_eq!(times(&storage, true), vec![6, 5, 4]);
+
+        let message = storage.get_log_messages(&Some(5)).next().unwrap();
+        assert_eq!(message.timeNanos, 6);
+        assert_eq!(message.message, "x".repeat(10));
+        assert_eq!(message.level, Some(LogLevel::info));
+    }
+}
@@ -0,0 +1,149 @@
+//! Upgrade data restored in `post_upgrade` is migrated step by step from the version it has been saved with.
+//!
+//! Steps transform data decoded into the current types: fields added to the upgrade data are optional,
+//! so data saved by older versions still decodes. `steps[i]` upgrades version `i + 1` to `i + 2`,
+//! the current version is `steps.len() + 1`.
+
+use std::fmt;
+
+pub type MigrationStep<T> = fn(T) -> Result<T, &'static str>;
+
+/// Describes the restored upgrade data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    pub data: &'static str,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+/// The upgrade data is not restored, trap in `post_upgrade` to roll the upgrade back and keep the data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationError {
+    UnsupportedVersion {
+        data: &'static str,
+        version: u8,
+    },
+    StepFailed {
+        data: &'static str,
+        version: u8,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::UnsupportedVersion { data, version } => {
+                write!(
+                    f,
+                    "Can not upgrade {} data. Unsupported version {}",
+                    data, version
+                )
+            }
+            MigrationError::StepFailed {
+                data,
+                version,
+                reason,
+            } => write!(
+                f,
+                "Can not upgrade {} data from version {}: {}",
+                data, version, reason
+            ),
+        }
+    }
+}
+
+/// Applies the steps from `version` up to the current version.
+pub fn migrate<T>(
+    data_name: &'static str,
+    version: u8,
+    mut data: T,
+    steps: &[MigrationStep<T>],
+) -> Result<(T, MigrationReport), MigrationError> {
+    let current_version = steps.len() + 1;
+    if version == 0 || version as usize > current_version {
+        return Err(MigrationError::UnsupportedVersion {
+            data: data_name,
+            version,
+        });
+    }
+
+    for (index, step) in steps.iter().enumerate().skip(version as usize - 1) {
+        data = step(data).map_err(|reason| MigrationError::StepFailed {
+            data: data_name,
+            version: index as u8 + 1,
+            reason,
+        })?;
+    }
+
+    Ok((
+        data,
+        MigrationReport {
+            data: data_name,
+            from_version: version,
+            to_version: current_version as u8,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEPS: [MigrationStep<Vec<u8>>; 2] = [
+        |mut data| {
+            data.push(2);
+            Ok(data)
+        },
+        |mut data| {
+            if data.is_empty() {
+                return Err("Empty data");
+            }
+            data.push(3);
+            Ok(data)
+        },
+    ];
+
+    #[test]
+    fn test_migrate() {
+        let (data, report) = migrate("test", 1, vec![1], &STEPS).unwrap();
+        assert_eq!(data, vec![1, 2, 3]);
+        assert_eq!(
+            report,
+            MigrationReport {
+                data: "test",
+                from_version: 1,
+                to_version: 3,
+            }
+        );
+
+        let (data, _) = migrate("test", 2, vec![1, 2], &STEPS).unwrap();
+        assert_eq!(data, vec![1, 2, 3]);
+        let (data, _) = migrate("test", 3, vec![1, 2, 3], &STEPS).unwrap();
+        assert_eq!(data, vec![1, 2, 3]);
+
+        assert_eq!(
+            migrate("test", 4, vec![], &STEPS).unwrap_err(),
+            MigrationError::UnsupportedVersion {
+                data: "test",
+                version: 4,
+            }
+        );
+        assert!(migrate("test", 0, vec![], &STEPS).is_err());
+        assert_eq!(
+            migrate::<Vec<u8>>("test", 3, vec![], &[])
+                .unwrap_err()
+                .to_string(),
+            "Can not upgrade test data. Unsupported version 3"
+        );
+        // the second step fails
+        assert_eq!(
+            migrate("test", 2, vec![], &STEPS).unwrap_err(),
+            MigrationError::StepFailed {
+                data: "test",
+                version: 2,
+                reason: "Empty data",
+            }
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use super::super::super::api_type;
+use super::super::data_type::{DayDataInfo, DayDataInfoSupplier};
+use super::day_iterator::DayIterator;
+use super::DAILY_MAX_DAYS;
+use chrono::prelude::*;
+
+const DEFAULT_PERIOD_DAYS: u32 = 7;
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+/// Derives the burn rate from consecutive cycles samples of the period ending at `now_millis`
+/// and projects the runway. Balance increases are counted as deposits, not as negative burn,
+/// and the burn rate is derived from the time of the intervals without them.
+pub fn get_cycles_forecast(
+    request: &api_type::CyclesForecastRequest,
+    data_supplier: &dyn DayDataInfoSupplier,
+    now_millis: u64,
+) -> Option<api_type::CyclesForecast> {
+    let period_days = request
+        .periodDays
+        .unwrap_or(DEFAULT_PERIOD_DAYS)
+        .clamp(1, DAILY_MAX_DAYS as u32);
+    let from_millis = now_millis.saturating_sub((period_days as u64 - 1) * DAY_MILLIS);
+    let mut days: Vec<_> = DayIterator::new_reverse(from_millis as i64, now_millis as i64)
+        .ok()?
+        .collect();
+    days.reverse();
+
+    // time and balance of the intervals with collected cycles
+    let mut samples: Vec<(u64, u64)> = Vec::new();
+    for date in days {
+        if let Some(data) =
+            data_supplier.get_day_data_info(&date.year(), &date.month(), &date.day())
+        {
+            let cycles_data = data.get_canister_cycles_data();
+            let cell_millis = DAY_MILLIS / cycles_data.len() as u64;
+            let day_millis = date.timestamp_millis() as u64;
+            for (cell, cycles) in cycles_data.iter().enumerate() {
+                if *cycles > 0 {
+                    samples.push((day_millis + cell as u64 * cell_millis, *cycles));
+                }
+            }
+        }
+    }
+
+    let (from_time_millis, _) = *samples.first()?;
+    let (to_time_millis, cycles) = *samples.last()?;
+    if to_time_millis == from_time_millis {
+        return None;
+    }
+
+    let mut burned_cycles: u64 = 0;
+    let mut deposited_cycles: u64 = 0;
+    // burn of the top-up intervals is unknown, so their time is not counted either
+    let mut burn_duration_millis: u128 = 0;
+    for pair in samples.windows(2) {
+        let ((previous_time_millis, previous), (time_millis, current)) = (pair[0], pair[1]);
+        if current > previous {
+            deposited_cycles += current - previous;
+        } else {
+            burned_cycles += previous - current;
+            burn_duration_millis += (time_millis - previous_time_millis) as u128;
+        }
+    }
+
+    let burned_cycles_per_day = (burned_cycles as u128 * DAY_MILLIS as u128)
+        .checked_div(burn_duration_millis)
+        .unwrap_or(0) as u64;
+    let remaining_cycles = cycles.saturating_sub(request.freezingThresholdCycles.unwrap_or(0));
+    let (runway_days, freeze_time_millis) = if burned_cycles == 0 {
+        (None, None)
+    } else {
+        let runway_millis = remaining_cycles as u128 * burn_duration_millis / burned_cycles as u128;
+        (
+            Some((runway_millis / DAY_MILLIS as u128) as u64),
+            Some(candid::Int::from(to_time_millis as u128 + runway_millis)),
+        )
+    };
+
+    Some(api_type::CyclesForecast {
+        fromTimeMillis: candid::Int::from(from_time_millis),
+        toTimeMillis: candid::Int::from(to_time_millis),
+        cycles,
+        burnedCycles: burned_cycles,
+        depositedCycles: deposited_cycles,
+        burnedCyclesPerDay: burned_cycles_per_day,
+        runwayDays: runway_days,
+        freezeTimeMillis: freeze_time_millis,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::data_type::{DayData, DayDataStorage};
+    use super::super::super::store::Storage;
+    use super::*;
+    use crate::api_type::CyclesForecastRequest;
+
+    fn now_millis() -> u64 {
+        Utc.with_ymd_and_hms(2022, 1, 28, 12, 0, 0)
+            .unwrap()
+            .timestamp_millis() as u64
+    }
+
+    #[test]
+    fn test_no_samples() {
+        let mut storage = Storage::default();
+        let request = CyclesForecastRequest {
+            periodDays: None,
+            freezingThresholdCycles: None,
+        };
+        assert!(get_cycles_forecast(&request, &storage, now_millis()).is_none());
+
+        let mut day_data = DayData::new(&24);
+        day_data.store(&1, 1, 0, 0, 1000);
+        storage.store_day_data(&2022, &1, &28, day_data);
+        assert!(get_cycles_forecast(&request, &storage, now_millis()).is_none());
+
+        // top-ups only
+        let mut day_data = DayData::new(&24);
+        day_data.store(&1, 1, 0, 0, 1000);
+        day_data.store(&2, 1, 0, 0, 2000);
+        storage.store_day_data(&2022, &1, &28, day_data);
+        let forecast = get_cycles_forecast(&request, &storage, now_millis()).unwrap();
+        assert_eq!(forecast.burnedCyclesPerDay, 0);
+        assert_eq!(forecast.runwayDays, None);
+    }
+
+    #[test]
+    fn test_forecast() {
+        let mut storage = Storage::default();
+        // 600 cycles burned every 6 hours with a top-up, burn of the top-up interval is unknown
+        let mut day_data = DayData::new(&24);
+        for (cell, cycles) in [(0, 5000), (6, 4400), (12, 13800), (18, 13200)] {
+            day_data.store(&cell, 1, 0, 0, cycles);
+        }
+        storage.store_day_data(&2022, &1, &27, day_data);
+        let mut day_data = DayData::new(&24);
+        day_data.store(&0, 1, 0, 0, 12600);
+        storage.store_day_data(&2022, &1, &28, day_data);
+        // outside of the period
+        let mut day_data = DayData::new(&24);
+        day_data.store(&0, 1, 0, 0, 100);
+        storage.store_day_data(&2022, &1, &20, day_data);
+
+        let request = CyclesForecastRequest {
+            periodDays: Some(2),
+            freezingThresholdCycles: Some(3600),
+        };
+        let forecast = get_cycles_forecast(&request, &storage, now_millis()).unwrap();
+        assert_eq!(forecast.cycles, 12600);
+        assert_eq!(forecast.burnedCycles, 1800);
+        assert_eq!(forecast.depositedCycles, 9400);
+        // 1800 cycles burned in 18 hours without the top-up interval
+        assert_eq!(forecast.burnedCyclesPerDay, 2400);
+        assert_eq!(forecast.runwayDays, Some(3));
+        let to_time_millis = Utc
+            .with_ymd_and_hms(2022, 1, 28, 0, 0, 0)
+            .unwrap()
+            .timestamp_millis();
+        assert_eq!(forecast.toTimeMillis, candid::Int::from(to_time_millis));
+        assert_eq!(
+            forecast.freezeTimeMillis,
+            Some(candid::Int::from(to_time_millis + 90 * 60 * 60 * 1000))
+        );
+    }
+}
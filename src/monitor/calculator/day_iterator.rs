@@ -24,16 +24,26 @@ impl DayIterator {
             return Err("From date more to date");
         }
 
+        let from_day = Utc
+            .timestamp_millis_opt(from_millis)
+            .single()
+            .ok_or("From date out of range")?;
+        let to_day = Utc
+            .timestamp_millis_opt(to_millis)
+            .single()
+            .ok_or("To date out of range")?;
+
         Ok(DayIterator {
-            from_day: Utc.timestamp_millis_opt(from_millis).unwrap(),
-            day: Utc
-                .timestamp_millis_opt(to_millis).unwrap()
-                .date_naive()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                .and_utc(),
+            from_day,
+            day: to_day.date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc(),
         })
     }
+
+    /// Returns the number of days left to iterate.
+    pub fn day_count(&self) -> usize {
+        let days = (self.day.date_naive() - self.from_day.date_naive()).num_days() + 1;
+        days.max(0) as usize
+    }
 }
 
 impl Iterator for DayIterator {
@@ -64,10 +74,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn out_of_range() {
+        assert!(DayIterator::new_reverse(0_i64, i64::MAX).is_err());
+    }
+
     #[test]
     fn single() {
         let result = DayIterator::new_reverse(23_i64, 23_i64);
         let mut iter = result.unwrap();
+        assert_eq!(iter.day_count(), 1);
         assert_eq!(iter.next().unwrap().timestamp(), 0_i64);
         assert_eq!(iter.next(), None);
     }
@@ -0,0 +1,116 @@
+use super::super::super::api_type::CustomMetricKind;
+use super::super::data_type::{DayDataInfo, DayDataInfoSupplier};
+use chrono::prelude::*;
+use chrono::Duration;
+
+const DAY_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// Values of the latest interval with collected metrics.
+#[derive(Debug, PartialEq)]
+pub struct LatestInterval {
+    pub start_time_nanos: u64,
+    pub interval_in_seconds: u64,
+    pub update_calls: u64,
+    pub heap_memory_size: u64,
+    pub memory_size: u64,
+    pub cycles: u64,
+    pub custom_metrics: Vec<(String, CustomMetricKind, u64)>,
+    pub method_calls: Vec<(String, u64)>,
+}
+
+/// Looks for the latest collected interval of the current and the previous day.
+pub fn get_latest_interval(
+    data_supplier: &dyn DayDataInfoSupplier,
+    now_nanos: u64,
+) -> Option<LatestInterval> {
+    let now = Utc.timestamp_nanos(now_nanos as i64).date_naive();
+    for date in [now, now - Duration::days(1)] {
+        let data = match data_supplier.get_day_data_info(&date.year(), &date.month(), &date.day()) {
+            Some(data) => data,
+            None => continue,
+        };
+        let cycles_data = data.get_canister_cycles_data();
+        let cell = match (0..cycles_data.len())
+            .rev()
+            .find(|cell| cycles_data[*cell] > 0 || data.get_update_calls_data()[*cell] > 0)
+        {
+            Some(cell) => cell,
+            None => continue,
+        };
+        let cell_nanos = DAY_NANOS / cycles_data.len() as u64;
+        let day_nanos = date.and_hms_opt(0, 0, 0)?.and_utc().timestamp_nanos_opt()? as u64;
+        return Some(LatestInterval {
+            start_time_nanos: day_nanos + cell as u64 * cell_nanos,
+            interval_in_seconds: cell_nanos / 1_000_000_000,
+            update_calls: data.get_update_calls_data()[cell],
+            heap_memory_size: data.get_canister_heap_memory_size_data()[cell],
+            memory_size: data.get_canister_memory_size_data()[cell],
+            cycles: cycles_data[cell],
+            custom_metrics: data
+                .get_custom_metrics_data()
+                .iter()
+                .map(|metric| (metric.name.clone(), metric.kind, metric.values[cell]))
+                .collect(),
+            method_calls: data
+                .get_method_calls_data()
+                .iter()
+                .map(|method| (method.name.clone(), method.values[cell]))
+                .collect(),
+        });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::collector::{self, CanisterInfo};
+    use super::super::super::data_type::RetentionPolicy;
+    use super::super::super::store::Storage;
+    use super::*;
+
+    #[test]
+    fn test_latest_interval() {
+        let mut storage = Storage::default();
+        let day_nanos = Utc
+            .with_ymd_and_hms(2024, 3, 10, 0, 0, 0)
+            .unwrap()
+            .timestamp_nanos_opt()
+            .unwrap() as u64;
+        let minute_nanos = 60 * 1_000_000_000;
+        assert_eq!(get_latest_interval(&storage, day_nanos), None);
+
+        let collect = |storage: &mut Storage, time_nanos: u64, cycles: u64| {
+            collector::collect_canister_metrics(
+                storage,
+                time_nanos,
+                false,
+                288,
+                &RetentionPolicy::default(),
+                || CanisterInfo {
+                    heap_memory_size: 10,
+                    memory_size: 20,
+                    cycles,
+                },
+            );
+        };
+        collect(&mut storage, day_nanos - minute_nanos, 1000);
+        collector::collect_method_call(&mut storage, day_nanos - minute_nanos, 288, "doThis", 5);
+
+        // the latest interval of the previous day
+        let interval = get_latest_interval(&storage, day_nanos + minute_nanos).unwrap();
+        assert_eq!(interval.start_time_nanos, day_nanos - 5 * minute_nanos);
+        assert_eq!(interval.interval_in_seconds, 300);
+        assert_eq!(interval.cycles, 1000);
+        assert_eq!(interval.method_calls, vec![("doThis".to_string(), 1)]);
+
+        collect(&mut storage, day_nanos + 7 * minute_nanos, 900);
+        collect(&mut storage, day_nanos + 8 * minute_nanos, 800);
+        let interval = get_latest_interval(&storage, day_nanos + 9 * minute_nanos).unwrap();
+        assert_eq!(interval.start_time_nanos, day_nanos + 5 * minute_nanos);
+        assert_eq!(interval.update_calls, 2);
+        assert_eq!(interval.cycles, 900);
+        assert_eq!(interval.heap_memory_size, 10);
+        assert_eq!(interval.memory_size, 20);
+        assert!(interval.method_calls.is_empty());
+    }
+}
@@ -1,9 +1,13 @@
 use super::super::api_type;
 use super::data_type;
+use super::data_type::{DayData, DayDataInfo};
 use chrono::prelude::*;
 use num_traits::ToPrimitive;
+use std::borrow::Cow;
 
+pub mod cycles_forecast;
 mod day_iterator;
+pub mod latest_interval;
 
 const HOURLY_MAX_DAYS: usize = 9;
 const DAILY_MAX_DAYS: usize = 365;
@@ -11,11 +15,26 @@ const DAILY_MAX_DAYS: usize = 365;
 pub fn get_canister_metrics<'a>(
     parameters: &api_type::GetMetricsParameters,
     data_supplier: &'a dyn data_type::DayDataInfoSupplier,
-) -> Result<api_type::CanisterMetricsData<'a>, &'a str> {
-    let date_from = parameters.dateFromMillis.0.to_u64().unwrap() as i64;
-    let date_to = parameters.dateToMillis.0.to_u64().unwrap() as i64;
+) -> Result<api_type::CanisterMetricsData<'a>, api_type::InformationError> {
+    let date_from = parameters.dateFromMillis.0.to_i64();
+    let date_to = parameters.dateToMillis.0.to_i64();
 
-    let iterator = day_iterator::DayIterator::new_reverse(date_from, date_to)?;
+    let iterator = match (date_from, date_to) {
+        (Some(date_from), Some(date_to)) => {
+            day_iterator::DayIterator::new_reverse(date_from, date_to)
+                .map_err(|_| api_type::InformationError::invalidDateRange)?
+        }
+        _ => return Err(api_type::InformationError::invalidDateRange),
+    };
+    let max_days = match parameters.granularity {
+        api_type::MetricsGranularity::hourly => HOURLY_MAX_DAYS,
+        api_type::MetricsGranularity::daily => DAILY_MAX_DAYS,
+    };
+    if iterator.day_count() > max_days {
+        return Err(api_type::InformationError::tooManyDays {
+            maxDays: max_days as u32,
+        });
+    }
 
     match parameters.granularity {
         api_type::MetricsGranularity::hourly => Ok(api_type::CanisterMetricsData::hourly(
@@ -26,10 +45,22 @@ pub fn get_canister_metrics<'a>(
                         .get_day_data_info(&date.year(), &date.month(), &date.day())
                         .map(|data| api_type::HourlyMetricsData {
                             timeMillis: candid::Int::from(date.timestamp_millis()),
-                            canisterCycles: data.get_canister_cycles_data(),
-                            canisterHeapMemorySize: data.get_canister_heap_memory_size_data(),
-                            canisterMemorySize: data.get_canister_memory_size_data(),
-                            updateCalls: data.get_update_calls_data(),
+                            canisterCycles: to_aggregated_data(
+                                &data,
+                                DayData::get_canister_cycles_data,
+                            ),
+                            canisterHeapMemorySize: to_aggregated_data(
+                                &data,
+                                DayData::get_canister_heap_memory_size_data,
+                            ),
+                            canisterMemorySize: to_aggregated_data(
+                                &data,
+                                DayData::get_canister_memory_size_data,
+                            ),
+                            updateCalls: to_aggregated_data(&data, DayData::get_update_calls_data),
+                            customMetrics: to_hourly_custom_metrics_data(&data),
+                            methodCalls: to_hourly_method_calls_data(&data),
+                            callInstructions: to_hourly_call_instructions_data(&data),
                         })
                 })
                 .collect(),
@@ -52,6 +83,9 @@ pub fn get_canister_metrics<'a>(
                                 data.get_canister_memory_size_data(),
                             ),
                             updateCalls: data.get_update_calls_data().iter().sum(),
+                            customMetrics: to_daily_custom_metrics_data(&data),
+                            methodCalls: to_daily_method_calls_data(&data),
+                            callInstructions: to_daily_call_instructions_data(&data),
                         })
                 })
                 .collect(),
@@ -59,7 +93,186 @@ pub fn get_canister_metrics<'a>(
     }
 }
 
-fn calculate_numeric_metrics_entity(arr: &Vec<u64>) -> api_type::NumericEntity {
+/// Borrows the series from the heap storage and copies it from the data read out of the stable one.
+fn to_aggregated_data<'a>(
+    data: &Cow<'a, DayData>,
+    series_getter: fn(&DayData) -> &Vec<u64>,
+) -> Cow<'a, [u64]> {
+    match data {
+        Cow::Borrowed(data) => Cow::Borrowed(series_getter(data)),
+        Cow::Owned(data) => Cow::Owned(series_getter(data).clone()),
+    }
+}
+
+fn to_hourly_custom_metrics_data<'a>(
+    data: &Cow<'a, DayData>,
+) -> Option<Vec<api_type::HourlyCustomMetricData<'a>>> {
+    let custom_metrics_data: Vec<api_type::HourlyCustomMetricData<'a>> = match data {
+        Cow::Borrowed(data) => data
+            .get_custom_metrics_data()
+            .iter()
+            .map(|metric| api_type::HourlyCustomMetricData {
+                name: Cow::Borrowed(&metric.name),
+                kind: metric.kind,
+                values: Cow::Borrowed(&metric.values),
+            })
+            .collect(),
+        Cow::Owned(data) => data
+            .get_custom_metrics_data()
+            .iter()
+            .map(|metric| api_type::HourlyCustomMetricData {
+                name: Cow::Owned(metric.name.clone()),
+                kind: metric.kind,
+                values: Cow::Owned(metric.values.clone()),
+            })
+            .collect(),
+    };
+    if custom_metrics_data.is_empty() {
+        None
+    } else {
+        Some(custom_metrics_data)
+    }
+}
+
+fn to_hourly_method_calls_data<'a>(
+    data: &Cow<'a, DayData>,
+) -> Option<Vec<api_type::HourlyMethodCallsData<'a>>> {
+    let method_calls_data: Vec<api_type::HourlyMethodCallsData<'a>> = match data {
+        Cow::Borrowed(data) => data
+            .get_method_calls_data()
+            .iter()
+            .map(|method| api_type::HourlyMethodCallsData {
+                name: Cow::Borrowed(&method.name),
+                updateCalls: Cow::Borrowed(&method.values),
+            })
+            .collect(),
+        Cow::Owned(data) => data
+            .get_method_calls_data()
+            .iter()
+            .map(|method| api_type::HourlyMethodCallsData {
+                name: Cow::Owned(method.name.clone()),
+                updateCalls: Cow::Owned(method.values.clone()),
+            })
+            .collect(),
+    };
+    if method_calls_data.is_empty() {
+        None
+    } else {
+        Some(method_calls_data)
+    }
+}
+
+fn to_daily_method_calls_data(data: &DayData) -> Option<Vec<api_type::DailyMethodCallsData>> {
+    let method_calls_data = data.get_method_calls_data();
+    if method_calls_data.is_empty() {
+        return None;
+    }
+    Some(
+        method_calls_data
+            .iter()
+            .map(|method| api_type::DailyMethodCallsData {
+                name: method.name.clone(),
+                updateCalls: method.values.iter().sum(),
+            })
+            .collect(),
+    )
+}
+
+fn to_hourly_call_instructions_data<'a>(
+    data: &Cow<'a, DayData>,
+) -> Option<Vec<api_type::HourlyCallInstructionsData<'a>>> {
+    let call_instructions_data: Vec<api_type::HourlyCallInstructionsData<'a>> = match data {
+        Cow::Borrowed(data) => data
+            .get_call_instructions_data()
+            .iter()
+            .map(|method| api_type::HourlyCallInstructionsData {
+                name: Cow::Borrowed(&method.name),
+                calls: Cow::Borrowed(&method.calls),
+                totalInstructions: Cow::Borrowed(&method.total),
+                maxInstructions: Cow::Borrowed(&method.max),
+                meanInstructions: calculate_mean_instructions(&method.total, &method.calls),
+            })
+            .collect(),
+        Cow::Owned(data) => data
+            .get_call_instructions_data()
+            .iter()
+            .map(|method| api_type::HourlyCallInstructionsData {
+                name: Cow::Owned(method.name.clone()),
+                calls: Cow::Owned(method.calls.clone()),
+                totalInstructions: Cow::Owned(method.total.clone()),
+                maxInstructions: Cow::Owned(method.max.clone()),
+                meanInstructions: calculate_mean_instructions(&method.total, &method.calls),
+            })
+            .collect(),
+    };
+    if call_instructions_data.is_empty() {
+        None
+    } else {
+        Some(call_instructions_data)
+    }
+}
+
+fn calculate_mean_instructions(total: &[u64], calls: &[u64]) -> Vec<u64> {
+    total
+        .iter()
+        .zip(calls)
+        .map(|(total, calls)| total.checked_div(*calls).unwrap_or(0))
+        .collect()
+}
+
+fn to_daily_call_instructions_data(
+    data: &DayData,
+) -> Option<Vec<api_type::DailyCallInstructionsData>> {
+    let call_instructions_data = data.get_call_instructions_data();
+    if call_instructions_data.is_empty() {
+        return None;
+    }
+    Some(
+        call_instructions_data
+            .iter()
+            .map(|method| {
+                let calls: u64 = method.calls.iter().sum();
+                let total_instructions = method
+                    .total
+                    .iter()
+                    .fold(0_u64, |sum, value| sum.saturating_add(*value));
+                api_type::DailyCallInstructionsData {
+                    name: method.name.clone(),
+                    calls,
+                    totalInstructions: total_instructions,
+                    maxInstructions: method.max.iter().copied().max().unwrap_or(0),
+                    meanInstructions: total_instructions.checked_div(calls).unwrap_or(0),
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Sums up counters and aggregates gauges like the canister info.
+fn to_daily_custom_metrics_data(data: &DayData) -> Option<Vec<api_type::DailyCustomMetricData>> {
+    let custom_metrics_data = data.get_custom_metrics_data();
+    if custom_metrics_data.is_empty() {
+        return None;
+    }
+    Some(
+        custom_metrics_data
+            .iter()
+            .map(|metric| api_type::DailyCustomMetricData {
+                name: metric.name.clone(),
+                value: match metric.kind {
+                    api_type::CustomMetricKind::counter => {
+                        api_type::DailyCustomMetricValue::counter(metric.values.iter().sum())
+                    }
+                    api_type::CustomMetricKind::gauge => api_type::DailyCustomMetricValue::gauge(
+                        calculate_numeric_metrics_entity(&metric.values),
+                    ),
+                },
+            })
+            .collect(),
+    )
+}
+
+fn calculate_numeric_metrics_entity(arr: &[u64]) -> api_type::NumericEntity {
     let array_size = arr.len();
 
     let mut sum_for_avg: u64 = 0;
@@ -68,7 +281,6 @@ fn calculate_numeric_metrics_entity(arr: &Vec<u64>) -> api_type::NumericEntity {
     let mut max: u64 = 0;
     let mut first: u64 = 0;
     let mut last: u64 = 0;
-    let mut avg: u64 = 0;
 
     if array_size > 0 {
         first = arr[0];
@@ -90,9 +302,7 @@ fn calculate_numeric_metrics_entity(arr: &Vec<u64>) -> api_type::NumericEntity {
         }
     }
 
-    if count_for_avg > 0 {
-        avg = sum_for_avg / count_for_avg;
-    }
+    let avg = sum_for_avg.checked_div(count_for_avg).unwrap_or(0);
 
     api_type::NumericEntity {
         avg,
@@ -1,10 +1,30 @@
 use chrono::prelude::*;
 
+use super::super::api_type::CustomMetricKind;
 use super::data_type::*;
 
-const INTERVAL_IN_SECONDS: usize = 5 * 60;
+pub const DEFAULT_INTERVAL_IN_SECONDS: u32 = 5 * 60;
+pub const MIN_INTERVAL_IN_SECONDS: u32 = 60;
 const DAY_SECONDS: usize = 24 * 60 * 60;
-const DAY_CELL_COUNT: usize = DAY_SECONDS / INTERVAL_IN_SECONDS;
+
+/// Checks that the day can be split into the intervals of the given length.
+pub fn validate_interval(interval_in_seconds: u32) -> Result<(), &'static str> {
+    if interval_in_seconds < MIN_INTERVAL_IN_SECONDS
+        || interval_in_seconds as usize > DAY_SECONDS
+        || DAY_SECONDS % interval_in_seconds as usize != 0
+    {
+        return Err("Interval should divide the day evenly and be at least one minute");
+    }
+    Ok(())
+}
+
+pub fn get_cell_count(interval_in_seconds: u32) -> usize {
+    DAY_SECONDS / interval_in_seconds as usize
+}
+
+pub fn get_interval_in_seconds(cell_count: usize) -> u32 {
+    (DAY_SECONDS / cell_count) as u32
+}
 
 pub struct CanisterInfo {
     pub heap_memory_size: u64,
@@ -12,19 +32,24 @@ pub struct CanisterInfo {
     pub cycles: u64,
 }
 
+/// Collects metrics and applies the retention policy, returns the number of removed days.
+/// New days are split into `cell_count` intervals, existing days keep their own interval.
 pub fn collect_canister_metrics<F>(
     storage: &mut dyn DayDataStorage,
     time_nanos: u64,
     force_set_info: bool,
+    cell_count: usize,
+    retention_policy: &RetentionPolicy,
     canister_info_supplier: F,
-) where
+) -> usize
+where
     F: Fn() -> CanisterInfo,
 {
     let data_time = Utc.timestamp_nanos(time_nanos as i64);
 
     match storage.get_day_data(&data_time.year(), &data_time.month(), &data_time.day()) {
         None => {
-            let mut day_data = DayData::new(&DAY_CELL_COUNT);
+            let mut day_data = DayData::new(&cell_count);
             let cell = get_cell(&day_data, data_time);
             init_cell(&mut day_data, &cell, canister_info_supplier);
             storage.store_day_data(
@@ -50,7 +75,149 @@ pub fn collect_canister_metrics<F>(
                     );
                 }
             }
+            storage.day_data_changed(&data_time.year(), &data_time.month(), &data_time.day());
+        }
+    }
+
+    apply_retention_policy(storage, retention_policy, time_nanos)
+}
+
+/// Stores the canister info in the cell of the time without counting an update call
+/// and applies the retention policy, returns the number of removed days.
+pub fn collect_canister_info<F>(
+    storage: &mut dyn DayDataStorage,
+    time_nanos: u64,
+    cell_count: usize,
+    retention_policy: &RetentionPolicy,
+    canister_info_supplier: F,
+) -> usize
+where
+    F: Fn() -> CanisterInfo,
+{
+    update_day_data(storage, time_nanos, cell_count, |day_data, cell| {
+        let canister_info = canister_info_supplier();
+        day_data.set_canister_info(
+            cell,
+            canister_info.heap_memory_size,
+            canister_info.memory_size,
+            canister_info.cycles,
+        );
+        true
+    });
+
+    apply_retention_policy(storage, retention_policy, time_nanos)
+}
+
+/// Removes the oldest days exceeding the policy, returns the number of removed days.
+pub fn apply_retention_policy(
+    storage: &mut dyn DayDataStorage,
+    retention_policy: &RetentionPolicy,
+    time_nanos: u64,
+) -> usize {
+    let mut removed = 0;
+    if let Some(max_days) = retention_policy.max_days {
+        let data_time = Utc.timestamp_nanos(time_nanos as i64);
+        let first_day = data_time.date_naive() - chrono::Duration::days(max_days.max(1) as i64 - 1);
+        removed +=
+            storage.remove_days_before(&first_day.year(), &first_day.month(), &first_day.day());
+    }
+    if let Some(max_bytes) = retention_policy.max_bytes {
+        while storage.get_size_in_bytes() > max_bytes && storage.remove_first_day() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Stores the value of the application metric in the cell of the time,
+/// new days are split into `cell_count` intervals.
+pub fn collect_custom_metric(
+    storage: &mut dyn DayDataStorage,
+    time_nanos: u64,
+    cell_count: usize,
+    name: &str,
+    kind: CustomMetricKind,
+    value: u64,
+) {
+    update_day_data(storage, time_nanos, cell_count, |day_data, cell| {
+        day_data.store_custom_metric(cell, name, kind, value);
+        true
+    });
+}
+
+/// Counts the update call of the method in the cell of the time, returns `false`
+/// if the method is not tracked because `max_method_count` other methods are already tracked for the day.
+pub fn collect_method_call(
+    storage: &mut dyn DayDataStorage,
+    time_nanos: u64,
+    cell_count: usize,
+    method_name: &str,
+    max_method_count: usize,
+) -> bool {
+    update_day_data(storage, time_nanos, cell_count, |day_data, cell| {
+        day_data.increment_method_calls(cell, method_name, max_method_count)
+    })
+}
+
+/// Stores instructions of the method call in the cell of the time, returns `false`
+/// if the method is not profiled because `max_method_count` other methods are already profiled for the day.
+pub fn collect_call_instructions(
+    storage: &mut dyn DayDataStorage,
+    time_nanos: u64,
+    cell_count: usize,
+    method_name: &str,
+    instructions: u64,
+    max_method_count: usize,
+) -> bool {
+    update_day_data(storage, time_nanos, cell_count, |day_data, cell| {
+        day_data.store_call_instructions(cell, method_name, instructions, max_method_count)
+    })
+}
+
+fn update_day_data<F>(
+    storage: &mut dyn DayDataStorage,
+    time_nanos: u64,
+    cell_count: usize,
+    update: F,
+) -> bool
+where
+    F: FnOnce(&mut DayData, &usize) -> bool,
+{
+    let data_time = Utc.timestamp_nanos(time_nanos as i64);
+
+    match storage.get_day_data(&data_time.year(), &data_time.month(), &data_time.day()) {
+        None => {
+            let mut day_data = DayData::new(&cell_count);
+            let cell = get_cell(&day_data, data_time);
+            let updated = update(&mut day_data, &cell);
+            if updated {
+                storage.store_day_data(
+                    &data_time.year(),
+                    &data_time.month(),
+                    &data_time.day(),
+                    day_data,
+                );
+            }
+            updated
         }
+        Some(day_data) => {
+            let cell = get_cell(day_data, data_time);
+            let updated = update(day_data, &cell);
+            if updated {
+                storage.day_data_changed(&data_time.year(), &data_time.month(), &data_time.day());
+            }
+            updated
+        }
+    }
+}
+
+/// Returns update calls counted in the interval of the time.
+pub fn get_update_calls_in_interval(supplier: &dyn DayDataInfoSupplier, time_nanos: u64) -> u64 {
+    let data_time = Utc.timestamp_nanos(time_nanos as i64);
+
+    match supplier.get_day_data_info(&data_time.year(), &data_time.month(), &data_time.day()) {
+        None => 0,
+        Some(day_data) => day_data.get_update_calls_data()[get_cell(&day_data, data_time)],
     }
 }
 
@@ -73,3 +240,141 @@ fn get_cell(day_data: &DayData, data_time: DateTime<Utc>) -> usize {
     let seconds = (data_time.hour() * 3600 + data_time.minute() * 60 + data_time.second()) as usize;
     seconds / (DAY_SECONDS / cell_count)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::data_type::{DayDataInfo, DayDataStorage};
+    use super::super::store::Storage;
+    use super::*;
+
+    #[test]
+    fn test_interval() {
+        assert!(validate_interval(60).is_ok());
+        assert!(validate_interval(15 * 60).is_ok());
+        assert!(validate_interval(24 * 60 * 60).is_ok());
+        assert!(validate_interval(59).is_err());
+        assert!(validate_interval(7 * 60).is_err());
+        assert!(validate_interval(2 * 24 * 60 * 60).is_err());
+        assert_eq!(get_cell_count(60), 1440);
+        assert_eq!(get_interval_in_seconds(96), 15 * 60);
+    }
+
+    #[test]
+    fn test_custom_metrics() {
+        let mut storage = Storage::default();
+        let collect = |storage: &mut Storage, minute: u32, name: &str, kind, value| {
+            let time_nanos = Utc
+                .with_ymd_and_hms(2022, 1, 28, 10, minute, 0)
+                .unwrap()
+                .timestamp_nanos_opt()
+                .unwrap() as u64;
+            collect_custom_metric(storage, time_nanos, 288, name, kind, value);
+        };
+
+        collect(&mut storage, 0, "orders", CustomMetricKind::counter, 2);
+        collect(&mut storage, 1, "orders", CustomMetricKind::counter, 3);
+        collect(&mut storage, 6, "orders", CustomMetricKind::counter, 1);
+        collect(&mut storage, 0, "queue", CustomMetricKind::gauge, 7);
+        collect(&mut storage, 1, "queue", CustomMetricKind::gauge, 5);
+
+        let day_data = storage.get_day_data(&2022, &1, &28).unwrap();
+        // update calls are not counted
+        assert_eq!(day_data.get_update_calls_data()[10 * 12], 0);
+
+        let custom_metrics_data = day_data.get_custom_metrics_data();
+        assert_eq!(custom_metrics_data.len(), 2);
+        assert_eq!(custom_metrics_data[0].name, "orders");
+        assert_eq!(custom_metrics_data[0].values.len(), 288);
+        assert_eq!(custom_metrics_data[0].values[10 * 12], 5);
+        assert_eq!(custom_metrics_data[0].values[10 * 12 + 1], 1);
+        assert_eq!(custom_metrics_data[1].name, "queue");
+        assert_eq!(custom_metrics_data[1].values[10 * 12], 5);
+    }
+
+    #[test]
+    fn test_method_calls() {
+        let mut storage = Storage::default();
+        let collect = |storage: &mut Storage, minute: u32, method_name: &str| {
+            let time_nanos = Utc
+                .with_ymd_and_hms(2022, 1, 28, 10, minute, 0)
+                .unwrap()
+                .timestamp_nanos_opt()
+                .unwrap() as u64;
+            collect_method_call(storage, time_nanos, 288, method_name, 2)
+        };
+
+        assert!(collect(&mut storage, 0, "doThis"));
+        assert!(collect(&mut storage, 1, "doThis"));
+        assert!(collect(&mut storage, 5, "doThat"));
+        // the cap is reached
+        assert!(!collect(&mut storage, 5, "doSomethingElse"));
+        assert!(collect(&mut storage, 6, "doThat"));
+
+        let day_data = storage.get_day_data(&2022, &1, &28).unwrap();
+        let method_calls_data = day_data.get_method_calls_data();
+        assert_eq!(method_calls_data.len(), 2);
+        assert_eq!(method_calls_data[0].name, "doThis");
+        assert_eq!(method_calls_data[0].values[10 * 12], 2);
+        assert_eq!(method_calls_data[1].name, "doThat");
+        assert_eq!(method_calls_data[1].values[10 * 12 + 1], 2);
+    }
+
+    #[test]
+    fn test_canister_info() {
+        let mut storage = Storage::default();
+        let time_nanos = Utc
+            .with_ymd_and_hms(2022, 1, 28, 10, 0, 0)
+            .unwrap()
+            .timestamp_nanos_opt()
+            .unwrap() as u64;
+        collect_canister_info(&mut storage, time_nanos, 288, &Default::default(), || {
+            CanisterInfo {
+                heap_memory_size: 1,
+                memory_size: 2,
+                cycles: 3,
+            }
+        });
+
+        let day_data = storage.get_day_data(&2022, &1, &28).unwrap();
+        assert_eq!(day_data.get_update_calls_data()[10 * 12], 0);
+        assert_eq!(day_data.get_canister_heap_memory_size_data()[10 * 12], 1);
+        assert_eq!(day_data.get_canister_memory_size_data()[10 * 12], 2);
+        assert_eq!(day_data.get_canister_cycles_data()[10 * 12], 3);
+    }
+
+    #[test]
+    fn test_day_keeps_interval() {
+        let mut storage = Storage::default();
+        let collect = |storage: &mut Storage, hour: u32, minute: u32, cell_count: usize| {
+            let time_nanos = Utc
+                .with_ymd_and_hms(2022, 1, 28, hour, minute, 0)
+                .unwrap()
+                .timestamp_nanos_opt()
+                .unwrap() as u64;
+            collect_canister_metrics(
+                storage,
+                time_nanos,
+                false,
+                cell_count,
+                &Default::default(),
+                || CanisterInfo {
+                    heap_memory_size: 1,
+                    memory_size: 2,
+                    cycles: 3,
+                },
+            );
+        };
+
+        collect(&mut storage, 10, 0, 288);
+        // the day has been created with 5 minute intervals
+        collect(&mut storage, 10, 14, 1440);
+        collect(&mut storage, 10, 16, 1440);
+
+        let day_data = storage.get_day_data(&2022, &1, &28).unwrap();
+        let update_calls = day_data.get_update_calls_data();
+        assert_eq!(update_calls.len(), 288);
+        assert_eq!(update_calls[10 * 12], 1);
+        assert_eq!(update_calls[10 * 12 + 2], 1);
+        assert_eq!(update_calls[10 * 12 + 3], 1);
+    }
+}
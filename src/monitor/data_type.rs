@@ -1,5 +1,7 @@
+use super::super::api_type::CustomMetricKind;
 use candid::{CandidType, Deserialize};
 use serde::Serialize;
+use std::borrow::Cow;
 
 // number of update calls in each time interval for a specific day.
 pub type DayUpdateCallsCountData = Vec<u64>;
@@ -13,13 +15,40 @@ pub type DayCanisterMemorySizeData = Vec<u64>;
 // canister available cycles in each time interval for a specific day.
 pub type DayCanisterCyclesData = Vec<u64>;
 
+// values of an application metric in each time interval for a specific day.
+#[derive(Debug, CandidType, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CustomMetricDayData {
+    pub name: String,
+    pub kind: CustomMetricKind,
+    pub values: Vec<u64>,
+}
+
+// number of update calls of a method in each time interval for a specific day.
+#[derive(Debug, CandidType, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MethodCallsDayData {
+    pub name: String,
+    pub values: Vec<u64>,
+}
+
+// instructions executed by profiled calls of a method in each time interval for a specific day.
+#[derive(Debug, CandidType, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CallInstructionsDayData {
+    pub name: String,
+    pub calls: Vec<u64>,
+    pub total: Vec<u64>,
+    pub max: Vec<u64>,
+}
+
 // specific day data with all necessary metrics
-#[derive(Debug, CandidType, Deserialize, Serialize)]
+#[derive(Debug, CandidType, Deserialize, Serialize, Clone)]
 pub struct DayData {
     update_calls_data: DayUpdateCallsCountData,
     canister_heap_memory_size_data: DayCanisterHeapMemorySizeData,
     canister_memory_size_data: DayCanisterMemorySizeData,
     canister_cycles_data: DayCanisterCyclesData,
+    custom_metrics_data: Option<Vec<CustomMetricDayData>>,
+    method_calls_data: Option<Vec<MethodCallsDayData>>,
+    call_instructions_data: Option<Vec<CallInstructionsDayData>>,
 }
 
 impl DayData {
@@ -29,6 +58,150 @@ impl DayData {
             canister_heap_memory_size_data: create_empty_vector(cell_count),
             canister_memory_size_data: create_empty_vector(cell_count),
             canister_cycles_data: create_empty_vector(cell_count),
+            custom_metrics_data: None,
+            method_calls_data: None,
+            call_instructions_data: None,
+        }
+    }
+
+    pub fn init(
+        update_calls_data: DayUpdateCallsCountData,
+        canister_heap_memory_size_data: DayCanisterHeapMemorySizeData,
+        canister_memory_size_data: DayCanisterMemorySizeData,
+        canister_cycles_data: DayCanisterCyclesData,
+    ) -> Self {
+        Self {
+            update_calls_data,
+            canister_heap_memory_size_data,
+            canister_memory_size_data,
+            canister_cycles_data,
+            custom_metrics_data: None,
+            method_calls_data: None,
+            call_instructions_data: None,
+        }
+    }
+
+    pub fn set_custom_metrics_data(&mut self, custom_metrics_data: Vec<CustomMetricDayData>) {
+        self.custom_metrics_data = if custom_metrics_data.is_empty() {
+            None
+        } else {
+            Some(custom_metrics_data)
+        };
+    }
+
+    pub fn set_method_calls_data(&mut self, method_calls_data: Vec<MethodCallsDayData>) {
+        self.method_calls_data = if method_calls_data.is_empty() {
+            None
+        } else {
+            Some(method_calls_data)
+        };
+    }
+
+    /// Increments update calls of the method in the cell,
+    /// returns `false` if `max_method_count` other methods are already tracked.
+    pub fn increment_method_calls(
+        &mut self,
+        cell: &usize,
+        name: &str,
+        max_method_count: usize,
+    ) -> bool {
+        let cell_count = self.update_calls_data.len();
+        let method_calls_data = self.method_calls_data.get_or_insert_with(Vec::new);
+        let index = match method_calls_data
+            .iter()
+            .position(|method| method.name == name)
+        {
+            Some(index) => index,
+            None if method_calls_data.len() < max_method_count => {
+                method_calls_data.push(MethodCallsDayData {
+                    name: name.to_string(),
+                    values: create_empty_vector(&cell_count),
+                });
+                method_calls_data.len() - 1
+            }
+            None => return false,
+        };
+        method_calls_data[index].values[*cell] += 1;
+        true
+    }
+
+    pub fn set_call_instructions_data(
+        &mut self,
+        call_instructions_data: Vec<CallInstructionsDayData>,
+    ) {
+        self.call_instructions_data = if call_instructions_data.is_empty() {
+            None
+        } else {
+            Some(call_instructions_data)
+        };
+    }
+
+    /// Adds instructions of the method call to the cell,
+    /// returns `false` if `max_method_count` other methods are already profiled.
+    pub fn store_call_instructions(
+        &mut self,
+        cell: &usize,
+        name: &str,
+        instructions: u64,
+        max_method_count: usize,
+    ) -> bool {
+        let cell_count = self.update_calls_data.len();
+        let call_instructions_data = self.call_instructions_data.get_or_insert_with(Vec::new);
+        let index = match call_instructions_data
+            .iter()
+            .position(|method| method.name == name)
+        {
+            Some(index) => index,
+            None if call_instructions_data.len() < max_method_count => {
+                call_instructions_data.push(CallInstructionsDayData {
+                    name: name.to_string(),
+                    calls: create_empty_vector(&cell_count),
+                    total: create_empty_vector(&cell_count),
+                    max: create_empty_vector(&cell_count),
+                });
+                call_instructions_data.len() - 1
+            }
+            None => return false,
+        };
+
+        let method = &mut call_instructions_data[index];
+        method.calls[*cell] += 1;
+        method.total[*cell] = method.total[*cell].saturating_add(instructions);
+        method.max[*cell] = method.max[*cell].max(instructions);
+        true
+    }
+
+    /// Adds the value to the counter or sets the value of the gauge in the cell.
+    pub fn store_custom_metric(
+        &mut self,
+        cell: &usize,
+        name: &str,
+        kind: CustomMetricKind,
+        value: u64,
+    ) {
+        let cell_count = self.update_calls_data.len();
+        let custom_metrics_data = self.custom_metrics_data.get_or_insert_with(Vec::new);
+        let index = match custom_metrics_data
+            .iter()
+            .position(|metric| metric.name == name)
+        {
+            Some(index) => index,
+            None => {
+                custom_metrics_data.push(CustomMetricDayData {
+                    name: name.to_string(),
+                    kind,
+                    values: create_empty_vector(&cell_count),
+                });
+                custom_metrics_data.len() - 1
+            }
+        };
+
+        let metric = &mut custom_metrics_data[index];
+        match metric.kind {
+            CustomMetricKind::counter => {
+                metric.values[*cell] = metric.values[*cell].saturating_add(value)
+            }
+            CustomMetricKind::gauge => metric.values[*cell] = value,
         }
     }
 
@@ -75,6 +248,9 @@ pub trait DayDataInfo {
     fn get_canister_heap_memory_size_data(&self) -> &DayCanisterHeapMemorySizeData;
     fn get_canister_memory_size_data(&self) -> &DayCanisterMemorySizeData;
     fn get_canister_cycles_data(&self) -> &DayCanisterCyclesData;
+    fn get_custom_metrics_data(&self) -> &[CustomMetricDayData];
+    fn get_method_calls_data(&self) -> &[MethodCallsDayData];
+    fn get_call_instructions_data(&self) -> &[CallInstructionsDayData];
 }
 
 impl DayDataInfo for DayData {
@@ -93,13 +269,51 @@ impl DayDataInfo for DayData {
     fn get_canister_cycles_data(&self) -> &DayCanisterCyclesData {
         &self.canister_cycles_data
     }
+
+    fn get_custom_metrics_data(&self) -> &[CustomMetricDayData] {
+        self.custom_metrics_data.as_deref().unwrap_or_default()
+    }
+
+    fn get_method_calls_data(&self) -> &[MethodCallsDayData] {
+        self.method_calls_data.as_deref().unwrap_or_default()
+    }
+
+    fn get_call_instructions_data(&self) -> &[CallInstructionsDayData] {
+        self.call_instructions_data.as_deref().unwrap_or_default()
+    }
+}
+
+/// Limits how much history the monitor keeps, the oldest days are removed first.
+/// The current day is never removed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Keep days not older than `max_days` days including the current one.
+    pub max_days: Option<u32>,
+    /// Keep no more than `max_bytes` bytes of day data.
+    pub max_bytes: Option<u64>,
 }
 
 pub trait DayDataInfoSupplier {
-    fn get_day_data_info(&self, year: &i32, month: &u32, day: &u32) -> Option<&dyn DayDataInfo>;
+    fn get_day_data_info(&self, year: &i32, month: &u32, day: &u32) -> Option<Cow<'_, DayData>>;
 }
 
 pub trait DayDataStorage {
     fn get_day_data(&mut self, year: &i32, month: &u32, day: &u32) -> Option<&mut DayData>;
     fn store_day_data(&mut self, year: &i32, month: &u32, day: &u32, day_data: DayData);
+
+    /// Removes all days before the given one and returns the number of removed days.
+    fn remove_days_before(&mut self, year: &i32, month: &u32, day: &u32) -> usize;
+
+    /// Removes the oldest day unless it is the only one left.
+    fn remove_first_day(&mut self) -> bool;
+
+    /// Returns the number of bytes occupied by the stored days.
+    fn get_size_in_bytes(&self) -> u64;
+
+    /// Returns the number of cells of the latest stored day.
+    fn get_last_day_cell_count(&self) -> Option<usize>;
+
+    /// Called after the day data returned by `get_day_data` has been changed,
+    /// so storages keeping a copy of the data can write it back.
+    fn day_data_changed(&mut self, _year: &i32, _month: &u32, _day: &u32) {}
 }
@@ -1,70 +1,613 @@
 pub mod calculator;
 pub mod collector;
 pub mod data_type;
+#[cfg(feature = "timers")]
+mod scheduler;
 pub mod store;
 
-use super::api_type::{CanisterMetrics, GetMetricsParameters};
+use super::alert;
+use super::api_type::{
+    CanisterMetrics, CustomMetricKind, CyclesForecastRequest, CyclesForecastResponse,
+    GetMetricsParameters, InformationError,
+};
 use super::ic_util;
+use super::ic_util::memory::Memory;
+use super::instance;
+use super::migration::{self, MigrationError, MigrationReport, MigrationStep};
 use crate::api_type::{StatusRequest, StatusResponse};
+use chrono::{Datelike, TimeZone, Utc};
 use collector::CanisterInfo;
+use data_type::{DayDataInfoSupplier, DayDataStorage, RetentionPolicy};
+use std::collections::BTreeMap;
+use store::stable::StableStorage;
 use store::Storage;
 
-pub type PreUpgradeStableData<'a> = (&'a u8, &'a store::DayDataTable);
-pub type PostUpgradeStableData = (u8, store::DayDataTable);
+/// Day data with the interval of the started collecting timer, which is absent in the data saved by version 1.
+pub type PreUpgradeStableData<'a> = (&'a u8, &'a store::DayDataTable, Option<&'a u32>);
+pub type PostUpgradeStableData = (u8, store::DayDataTable, Option<u32>);
 
-const VERSION: u8 = 1;
-static mut STORAGE: Option<Storage> = None;
+// `MIGRATIONS[i]` upgrades the day data of version `i + 1` to `i + 2`.
+const MIGRATIONS: [MigrationStep<store::DayDataTable>; 0] = [];
+const VERSION: u8 = MIGRATIONS.len() as u8 + 1;
 
-fn storage<'a>() -> &'a mut Storage {
-    unsafe {
-        if let Some(s) = &mut *std::ptr::addr_of_mut!(STORAGE) {
-            s
-        } else {
-            STORAGE = Some(Storage::default());
-            storage()
+/// Monitor state of a `Canistergeek` instance, the free functions of this module
+/// work with the instance of the current thread.
+#[derive(Default)]
+pub struct Monitor {
+    storage: Storage,
+    stable_storage: Option<StableStorage>,
+    interval_in_seconds: Option<u32>,
+    custom_metrics: BTreeMap<String, CustomMetricKind>,
+    max_method_count: usize,
+    retention_policy: RetentionPolicy,
+    // interval of the started collecting timer, persisted to start it again after upgrade
+    timer_interval_in_seconds: Option<u32>,
+}
+
+impl Monitor {
+    fn day_data_storage(&mut self) -> &mut dyn DayDataStorage {
+        match &mut self.stable_storage {
+            Some(stable_storage) => stable_storage,
+            None => &mut self.storage,
+        }
+    }
+
+    fn day_data_supplier(&self) -> &dyn DayDataInfoSupplier {
+        match &self.stable_storage {
+            Some(stable_storage) => stable_storage,
+            None => &self.storage,
+        }
+    }
+
+    /// Moves days restored into the heap storage to the stable one.
+    fn migrate_to_stable_storage(&mut self) {
+        if let Some(stable_storage) = &mut self.stable_storage {
+            self.storage.move_to(stable_storage);
+        }
+    }
+
+    fn get_cell_count(&self) -> usize {
+        collector::get_cell_count(self.get_sampling_interval())
+    }
+
+    /// See `monitor::init_stable_storage`.
+    pub fn init_stable_storage(&mut self, memory: Box<dyn Memory>) -> Result<(), &'static str> {
+        let cell_count = self.get_cell_count();
+        let custom_metric_count = self.custom_metrics.len();
+        let stable_storage = StableStorage::init(
+            memory,
+            cell_count,
+            custom_metric_count,
+            self.max_method_count,
+        )?;
+        if cell_count > stable_storage.get_max_cell_count() {
+            return Err("Sampling interval is too short for the stable storage");
+        }
+        if custom_metric_count > stable_storage.get_max_custom_metric_count() {
+            return Err("Too many custom metrics for the stable storage");
+        }
+        if self.max_method_count > stable_storage.get_max_method_count() {
+            return Err("Too many tracked methods for the stable storage");
+        }
+        self.stable_storage = Some(stable_storage);
+        self.migrate_to_stable_storage();
+        Ok(())
+    }
+
+    /// See `monitor::set_sampling_interval`.
+    pub fn set_sampling_interval(&mut self, interval_in_seconds: u32) -> Result<(), &'static str> {
+        collector::validate_interval(interval_in_seconds)?;
+        if let Some(stable_storage) = &self.stable_storage {
+            if collector::get_cell_count(interval_in_seconds) > stable_storage.get_max_cell_count()
+            {
+                return Err("Sampling interval is too short for the stable storage");
+            }
+        }
+        self.interval_in_seconds = Some(interval_in_seconds);
+        Ok(())
+    }
+
+    /// See `monitor::get_dropped_series_count`.
+    pub fn get_dropped_series_count(&self) -> usize {
+        self.stable_storage
+            .as_ref()
+            .map_or(0, StableStorage::get_dropped_series_count)
+    }
+
+    /// Interval of the started collecting timer, see `monitor::start_collecting_timer`.
+    pub fn get_timer_interval(&self) -> Option<u32> {
+        self.timer_interval_in_seconds
+    }
+
+    #[cfg(feature = "timers")]
+    pub(crate) fn set_timer_interval(&mut self, interval_in_seconds: Option<u32>) {
+        self.timer_interval_in_seconds = interval_in_seconds;
+    }
+
+    pub fn get_sampling_interval(&self) -> u32 {
+        match self.interval_in_seconds {
+            Some(interval_in_seconds) => interval_in_seconds,
+            None => match &self.stable_storage {
+                Some(stable_storage) => stable_storage.get_last_day_cell_count(),
+                None => self.storage.get_last_day_cell_count(),
+            }
+            .map(collector::get_interval_in_seconds)
+            .unwrap_or(collector::DEFAULT_INTERVAL_IN_SECONDS),
+        }
+    }
+
+    /// See `monitor::set_max_tracked_method_count`.
+    pub fn set_max_tracked_method_count(
+        &mut self,
+        max_method_count: usize,
+    ) -> Result<(), &'static str> {
+        if let Some(stable_storage) = &self.stable_storage {
+            if max_method_count > stable_storage.get_max_method_count() {
+                return Err("Too many tracked methods for the stable storage");
+            }
+        }
+        self.max_method_count = max_method_count;
+        Ok(())
+    }
+
+    pub fn get_max_tracked_method_count(&self) -> usize {
+        self.max_method_count
+    }
+
+    /// See `monitor::register_counter`.
+    pub fn register_counter(&mut self, name: &str) -> Result<(), &'static str> {
+        self.register_custom_metric(name, CustomMetricKind::counter)
+    }
+
+    /// See `monitor::register_gauge`.
+    pub fn register_gauge(&mut self, name: &str) -> Result<(), &'static str> {
+        self.register_custom_metric(name, CustomMetricKind::gauge)
+    }
+
+    fn register_custom_metric(
+        &mut self,
+        name: &str,
+        kind: CustomMetricKind,
+    ) -> Result<(), &'static str> {
+        if name.is_empty() || name.len() as u64 > store::stable::MAX_SERIES_NAME_LENGTH {
+            return Err("Wrong metric name length");
+        }
+        match self.custom_metrics.get(name) {
+            Some(registered_kind) if *registered_kind == kind => Ok(()),
+            Some(_) => Err("Metric is registered with another kind"),
+            None => {
+                if let Some(stable_storage) = &self.stable_storage {
+                    if self.custom_metrics.len() >= stable_storage.get_max_custom_metric_count() {
+                        return Err("Too many custom metrics for the stable storage");
+                    }
+                }
+                self.custom_metrics.insert(name.to_string(), kind);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn increment_counter(&mut self, name: &str, delta: u64) -> Result<(), &'static str> {
+        self.collect_custom_metric(name, CustomMetricKind::counter, delta)
+    }
+
+    pub fn set_gauge(&mut self, name: &str, value: u64) -> Result<(), &'static str> {
+        self.collect_custom_metric(name, CustomMetricKind::gauge, value)
+    }
+
+    fn collect_custom_metric(
+        &mut self,
+        name: &str,
+        kind: CustomMetricKind,
+        value: u64,
+    ) -> Result<(), &'static str> {
+        match self.custom_metrics.get(name) {
+            None => Err("Metric is not registered"),
+            Some(registered_kind) if *registered_kind != kind => {
+                Err("Metric is registered with another kind")
+            }
+            Some(_) => {
+                let cell_count = self.get_cell_count();
+                collector::collect_custom_metric(
+                    self.day_data_storage(),
+                    ic_util::get_ic_time_nanos(),
+                    cell_count,
+                    name,
+                    kind,
+                    value,
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// See `monitor::set_retention_policy`.
+    pub fn set_retention_policy(&mut self, retention_policy: RetentionPolicy) {
+        self.retention_policy = retention_policy;
+    }
+
+    pub fn get_retention_policy(&self) -> RetentionPolicy {
+        self.retention_policy.clone()
+    }
+
+    /// See `monitor::prune_before`.
+    pub fn prune_before(&mut self, time_millis: u64) -> usize {
+        let time = match Utc.timestamp_millis_opt(time_millis as i64).single() {
+            Some(time) => time,
+            None => return 0,
+        };
+        self.day_data_storage()
+            .remove_days_before(&time.year(), &time.month(), &time.day())
+    }
+
+    pub fn pre_upgrade_stable_data(&self) -> PreUpgradeStableData<'_> {
+        (
+            &VERSION,
+            self.storage.get_day_data_table(),
+            self.timer_interval_in_seconds.as_ref(),
+        )
+    }
+
+    /// See `monitor::post_upgrade_stable_data`.
+    pub fn post_upgrade_stable_data(
+        &mut self,
+        (version, upgrade_data, timer_interval_in_seconds): PostUpgradeStableData,
+    ) -> Result<MigrationReport, MigrationError> {
+        let (upgrade_data, report) =
+            migration::migrate("monitor", version, upgrade_data, &MIGRATIONS)?;
+        self.storage = Storage::init(upgrade_data);
+        self.timer_interval_in_seconds = timer_interval_in_seconds;
+        self.migrate_to_stable_storage();
+        Ok(report)
+    }
+
+    /// Stores instructions executed by the update call of the method, see `monitor::begin_call`.
+    pub fn collect_call_instructions(&mut self, method_name: &str, instructions: u64) {
+        let mut max_method_count = self.max_method_count;
+        if let Some(stable_storage) = &self.stable_storage {
+            max_method_count = max_method_count.min(stable_storage.get_max_profiled_method_count());
+        }
+        if max_method_count > 0 && method_name.len() as u64 <= store::stable::MAX_SERIES_NAME_LENGTH
+        {
+            let cell_count = self.get_cell_count();
+            collector::collect_call_instructions(
+                self.day_data_storage(),
+                ic_util::get_ic_time_nanos(),
+                cell_count,
+                method_name,
+                instructions,
+                max_method_count,
+            );
+        }
+    }
+
+    pub(crate) fn collect_method_call(&mut self, method_name: &str) {
+        let max_method_count = self.max_method_count;
+        if max_method_count > 0 && method_name.len() as u64 <= store::stable::MAX_SERIES_NAME_LENGTH
+        {
+            let cell_count = self.get_cell_count();
+            collector::collect_method_call(
+                self.day_data_storage(),
+                ic_util::get_ic_time_nanos(),
+                cell_count,
+                method_name,
+                max_method_count,
+            );
         }
     }
+
+    /// Returns the number of days removed by the retention policy.
+    pub(crate) fn collect_metrics(&mut self, force_set_info: bool) -> usize {
+        let cell_count = self.get_cell_count();
+        let retention_policy = self.get_retention_policy();
+        collector::collect_canister_metrics(
+            self.day_data_storage(),
+            ic_util::get_ic_time_nanos(),
+            force_set_info,
+            cell_count,
+            &retention_policy,
+            get_canister_info,
+        )
+    }
+
+    #[cfg(feature = "timers")]
+    pub(crate) fn collect_canister_info(&mut self) -> usize {
+        let cell_count = self.get_cell_count();
+        let retention_policy = self.get_retention_policy();
+        collector::collect_canister_info(
+            self.day_data_storage(),
+            ic_util::get_ic_time_nanos(),
+            cell_count,
+            &retention_policy,
+            get_canister_info,
+        )
+    }
+
+    pub fn get_metrics(&self, parameters: &GetMetricsParameters) -> Option<CanisterMetrics<'_>> {
+        self.try_get_metrics(parameters).ok()
+    }
+
+    /// Like `get_metrics`, but tells why the request is rejected.
+    pub fn try_get_metrics(
+        &self,
+        parameters: &GetMetricsParameters,
+    ) -> Result<CanisterMetrics<'_>, InformationError> {
+        calculator::get_canister_metrics(parameters, self.day_data_supplier())
+            .map(|data| CanisterMetrics { data })
+    }
+
+    pub(crate) fn get_metrics_sample(&self, time_nanos: u64) -> alert::MetricsSample {
+        alert::MetricsSample {
+            cycles: get_current_cycles(),
+            heap_memory_size: get_current_heap_memory_size(),
+            memory_size: get_current_memory_size(),
+            update_calls_in_interval: collector::get_update_calls_in_interval(
+                self.day_data_supplier(),
+                time_nanos,
+            ),
+        }
+    }
+
+    pub(crate) fn get_cycles_forecast(
+        &self,
+        request: CyclesForecastRequest,
+    ) -> CyclesForecastResponse {
+        CyclesForecastResponse {
+            forecast: calculator::cycles_forecast::get_cycles_forecast(
+                &request,
+                self.day_data_supplier(),
+                ic_util::get_ic_time_nanos() / 1_000_000,
+            ),
+        }
+    }
+
+    pub(crate) fn get_latest_interval(
+        &self,
+    ) -> Option<calculator::latest_interval::LatestInterval> {
+        calculator::latest_interval::get_latest_interval(
+            self.day_data_supplier(),
+            ic_util::get_ic_time_nanos(),
+        )
+    }
+}
+
+fn get_canister_info() -> CanisterInfo {
+    CanisterInfo {
+        heap_memory_size: get_current_heap_memory_size(),
+        memory_size: get_current_memory_size(),
+        cycles: get_current_cycles(),
+    }
 }
 
-pub fn pre_upgrade_stable_data<'a>() -> PreUpgradeStableData<'a> {
-    (&VERSION, storage().get_day_data_table())
+/// Switches monitor to the storage which keeps day data directly in the passed memory
+/// (a region of the stable memory), so the history does not inflate the heap and upgrades.
+///
+/// Has to be called in `init` and `post_upgrade` hooks: the days stored in the memory before are reopened.
+/// Days collected with the heap storage are moved to the stable one.
+///
+/// A new storage reserves space for days with the current sampling interval, registered custom metrics
+/// and tracked methods, so set them before.
+pub fn init_stable_storage(memory: Box<dyn Memory>) -> Result<(), &'static str> {
+    instance::with_mut(|canistergeek| canistergeek.monitor_mut().init_stable_storage(memory))
 }
 
-pub fn post_upgrade_stable_data((version, upgrade_data): PostUpgradeStableData) {
-    if version != VERSION {
-        ic_cdk::api::debug_print(std::format!(
-            "Can not upgrade stable data. Unsupported version {}",
-            version
-        ));
-    } else {
-        unsafe {
-            STORAGE = Some(Storage::init(upgrade_data));
-        }
+/// Sets the length (in seconds) of the intervals the days collected from now on are split into.
+/// The interval should divide the day evenly and be at least one minute, the default is 5 minutes.
+///
+/// Already stored days keep their interval. After upgrade the interval of the latest stored day is used
+/// unless it is set again.
+/// The started collecting timer is restarted for the new interval.
+pub fn set_sampling_interval(interval_in_seconds: u32) -> Result<(), &'static str> {
+    instance::with_mut(|canistergeek| {
+        canistergeek
+            .monitor_mut()
+            .set_sampling_interval(interval_in_seconds)
+    })?;
+    #[cfg(feature = "timers")]
+    if is_collecting_timer_started() {
+        start_collecting_timer();
     }
+    Ok(())
+}
+
+pub fn get_sampling_interval() -> u32 {
+    instance::with(|canistergeek| canistergeek.monitor().get_sampling_interval())
+}
+
+/// Returns the number of custom metrics, method calls and profiled methods series of the current day,
+/// which have not fit the stable storage and have been dropped (the heap storage keeps all of them).
+pub fn get_dropped_series_count() -> usize {
+    instance::with(|canistergeek| canistergeek.monitor().get_dropped_series_count())
+}
+
+/// Sets how many distinct methods a day tracks update calls of (see `collect_metrics_for_method`),
+/// calls of other methods are counted in the total update calls only. Tracking is disabled by default.
+/// The value is not persisted, so set it in `init` and `post_upgrade` hooks.
+pub fn set_max_tracked_method_count(max_method_count: usize) -> Result<(), &'static str> {
+    instance::with_mut(|canistergeek| {
+        canistergeek
+            .monitor_mut()
+            .set_max_tracked_method_count(max_method_count)
+    })
+}
+
+pub fn get_max_tracked_method_count() -> usize {
+    instance::with(|canistergeek| canistergeek.monitor().get_max_tracked_method_count())
+}
+
+/// Registers the application counter, values passed to `increment_counter` are summed up in each interval.
+/// Registered metrics are not persisted, so register them in `init` and `post_upgrade` hooks.
+pub fn register_counter(name: &str) -> Result<(), &'static str> {
+    instance::with_mut(|canistergeek| canistergeek.monitor_mut().register_counter(name))
+}
+
+/// Registers the application gauge, the last value passed to `set_gauge` is kept in each interval.
+/// Registered metrics are not persisted, so register them in `init` and `post_upgrade` hooks.
+pub fn register_gauge(name: &str) -> Result<(), &'static str> {
+    instance::with_mut(|canistergeek| canistergeek.monitor_mut().register_gauge(name))
+}
+
+pub fn increment_counter(name: &str, delta: u64) -> Result<(), &'static str> {
+    instance::with_mut(|canistergeek| canistergeek.monitor_mut().increment_counter(name, delta))
+}
+
+pub fn set_gauge(name: &str, value: u64) -> Result<(), &'static str> {
+    instance::with_mut(|canistergeek| canistergeek.monitor_mut().set_gauge(name, value))
+}
+
+/// Sets how much history is kept, the oldest days exceeding the policy are removed while collecting metrics.
+/// The policy is not persisted, so set it in `init` and `post_upgrade` hooks.
+pub fn set_retention_policy(retention_policy: RetentionPolicy) {
+    instance::with_mut(|canistergeek| {
+        canistergeek
+            .monitor_mut()
+            .set_retention_policy(retention_policy)
+    });
+}
+
+pub fn get_retention_policy() -> RetentionPolicy {
+    instance::with(|canistergeek| canistergeek.monitor().get_retention_policy())
+}
+
+/// Removes data of all days before the day of the passed time, returns the number of removed days.
+pub fn prune_before(time_millis: u64) -> usize {
+    instance::with_mut(|canistergeek| canistergeek.monitor_mut().prune_before(time_millis))
+}
+
+/// Returns a copy of the day data kept in the heap storage.
+pub fn pre_upgrade_stable_data() -> PostUpgradeStableData {
+    with_pre_upgrade_stable_data(|(version, day_data_table, timer_interval)| {
+        (*version, day_data_table.clone(), timer_interval.copied())
+    })
+}
+
+/// Like `pre_upgrade_stable_data`, but runs the function with the day data borrowed, so it is saved
+/// in `pre_upgrade` without copying: `with_pre_upgrade_stable_data(|data| ic_cdk::storage::stable_save((data,)))`.
+pub fn with_pre_upgrade_stable_data<R>(f: impl FnOnce(PreUpgradeStableData<'_>) -> R) -> R {
+    instance::with(|canistergeek| f(canistergeek.monitor().pre_upgrade_stable_data()))
+}
+
+/// Restores the day data saved by `pre_upgrade_stable_data` of this or previous versions.
+/// Nothing is restored on error, trap to roll the upgrade back and keep the history.
+pub fn post_upgrade_stable_data(
+    upgrade_data: PostUpgradeStableData,
+) -> Result<MigrationReport, MigrationError> {
+    let report = instance::with_mut(|canistergeek| {
+        canistergeek
+            .monitor_mut()
+            .post_upgrade_stable_data(upgrade_data)
+    })?;
+    #[cfg(feature = "timers")]
+    if let Some(interval_in_seconds) =
+        instance::with(|canistergeek| canistergeek.monitor().get_timer_interval())
+    {
+        scheduler::start(interval_in_seconds);
+    }
+    Ok(report)
+}
+
+/// Starts collecting cycles and memory once per sampling interval by an `ic-cdk-timers` interval timer,
+/// so idle canisters get samples too. Timer collections are not counted as update calls.
+///
+/// The timer interval is persisted with the day data, `post_upgrade_stable_data` starts the timer again.
+#[cfg(feature = "timers")]
+pub fn start_collecting_timer() {
+    let interval_in_seconds = get_sampling_interval();
+    instance::with_mut(|canistergeek| {
+        canistergeek
+            .monitor_mut()
+            .set_timer_interval(Some(interval_in_seconds))
+    });
+    scheduler::start(interval_in_seconds);
+}
+
+#[cfg(feature = "timers")]
+pub fn stop_collecting_timer() {
+    instance::with_mut(|canistergeek| canistergeek.monitor_mut().set_timer_interval(None));
+    scheduler::stop();
+}
+
+#[cfg(feature = "timers")]
+pub fn is_collecting_timer_started() -> bool {
+    instance::with(|canistergeek| canistergeek.monitor().get_timer_interval().is_some())
+}
+
+#[cfg(feature = "timers")]
+pub(crate) fn collect_canister_info_int() {
+    let events = instance::with_mut(|canistergeek| canistergeek.collect_canister_info());
+    alert::notify(&events);
 }
 
 pub fn collect_metrics() {
     collect_metrics_int(false);
 }
 
-pub fn get_metrics<'a>(parameters: &GetMetricsParameters) -> Option<CanisterMetrics<'a>> {
-    match calculator::get_canister_metrics(parameters, storage()) {
-        Ok(data) => Some(CanisterMetrics { data }),
-        Err(_) => None,
+/// Measures instructions executed by the call till it is dropped, see `begin_call`.
+pub struct CallGuard {
+    method_name: String,
+    start_instructions: u64,
+}
+
+/// Starts profiling of the update call, instructions executed by the call (across awaits)
+/// till the returned guard is dropped are stored for the method.
+///
+/// Total, max and mean instructions are collected for up to `max_tracked_method_count` methods a day.
+///
+/// ```ignore
+/// #[ic_cdk_macros::update]
+/// async fn transfer() {
+///     let _guard = canistergeek_ic_rust::monitor::begin_call("transfer");
+///     ...
+/// }
+/// ```
+pub fn begin_call(method_name: &str) -> CallGuard {
+    CallGuard {
+        method_name: method_name.to_string(),
+        start_instructions: ic_util::get_instruction_counter(),
     }
 }
 
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        let instructions =
+            ic_util::get_instruction_counter().saturating_sub(self.start_instructions);
+        instance::with_mut(|canistergeek| {
+            canistergeek
+                .monitor_mut()
+                .collect_call_instructions(&self.method_name, instructions)
+        });
+    }
+}
+
+/// Collects metrics like `collect_metrics` and counts the update call of the method.
+///
+/// The method name has to be passed explicitly: the IC exposes it only in `canister_inspect_message`.
+pub fn collect_metrics_for_method(method_name: &str) {
+    let events =
+        instance::with_mut(|canistergeek| canistergeek.collect_metrics_for_method(method_name));
+    alert::notify(&events);
+}
+
+pub fn get_metrics<'a>(parameters: &GetMetricsParameters) -> Option<CanisterMetrics<'a>> {
+    try_get_metrics(parameters).ok()
+}
+
+/// Like `get_metrics`, but tells why the request is rejected.
+pub fn try_get_metrics<'a>(
+    parameters: &GetMetricsParameters,
+) -> Result<CanisterMetrics<'a>, InformationError> {
+    instance::with(|canistergeek| {
+        canistergeek
+            .monitor()
+            .try_get_metrics(parameters)
+            .map(CanisterMetrics::into_owned)
+    })
+}
+
 pub(crate) fn collect_metrics_int(force_set_info: bool) {
-    collector::collect_canister_metrics(
-        storage(),
-        ic_util::get_ic_time_nanos(),
-        force_set_info,
-        || CanisterInfo {
-            heap_memory_size: get_current_heap_memory_size(),
-            memory_size: get_current_memory_size(),
-            cycles: get_current_cycles(),
-        },
-    );
+    let events =
+        instance::with_mut(|canistergeek| canistergeek.collect_metrics_int(force_set_info));
+    alert::notify(&events);
 }
 
 pub(crate) fn get_status(request: StatusRequest) -> StatusResponse {
@@ -104,53 +647,355 @@ fn get_current_heap_memory_size() -> u64 {
 
 #[cfg(test)]
 mod tests {
+    use super::super::ic_util::memory::VecMemory;
     use super::calculator;
     use super::collector;
-    use crate::api_type::CanisterMetricsData;
+    use super::data_type::{
+        DayData, DayDataInfo, DayDataInfoSupplier, DayDataStorage, RetentionPolicy,
+    };
+    use super::store::stable::StableStorage;
+    use super::store::Storage;
+    use crate::api_type::{
+        CanisterMetricsData, CustomMetricKind, DailyCustomMetricValue, InformationError,
+    };
     use candid::Nat;
     use chrono::prelude::*;
 
     #[test]
     fn test_metrics() {
-        let mut storage = super::store::Storage::default();
-
-        let time_nanos = Utc.with_ymd_and_hms(2022, 01, 28, 13, 0, 0).unwrap().timestamp_nanos_opt().unwrap() as u64;
-
-        collector::collect_canister_metrics(&mut storage, time_nanos, false, || {
-            let heap_memory_size = 234000;
-            let memory_size = 345000;
-            let cycles = 8787;
-            collector::CanisterInfo {
-                heap_memory_size,
-                memory_size,
-                cycles,
-            }
-        });
+        check_metrics(&mut Storage::default());
+        check_metrics(&mut StableStorage::init(Box::new(VecMemory::default()), 288, 0, 0).unwrap());
+    }
 
-        let time_nanos = Utc.with_ymd_and_hms(2022, 01, 28, 9, 0, 0).unwrap().timestamp_nanos_opt().unwrap() as u64;
+    #[test]
+    fn test_custom_metrics_and_method_calls() {
+        check_custom_metrics_and_method_calls(&mut Storage::default());
+        check_custom_metrics_and_method_calls(
+            &mut StableStorage::init(Box::new(VecMemory::default()), 288, 2, 1).unwrap(),
+        );
+    }
+
+    fn check_custom_metrics_and_method_calls<S: DayDataStorage + DayDataInfoSupplier>(
+        storage: &mut S,
+    ) {
+        for (hour, orders, queue) in [(9, 2, 10), (9, 3, 30), (13, 1, 20)] {
+            let time_nanos = Utc
+                .with_ymd_and_hms(2022, 1, 28, hour, 0, 0)
+                .unwrap()
+                .timestamp_nanos_opt()
+                .unwrap() as u64;
+            collector::collect_custom_metric(
+                storage,
+                time_nanos,
+                288,
+                "orders",
+                CustomMetricKind::counter,
+                orders,
+            );
+            collector::collect_custom_metric(
+                storage,
+                time_nanos,
+                288,
+                "queue",
+                CustomMetricKind::gauge,
+                queue,
+            );
+            collector::collect_method_call(storage, time_nanos, 288, "doThis", 1);
+        }
+
+        let date_millis = Nat::from(
+            Utc.with_ymd_and_hms(2022, 1, 28, 11, 11, 11)
+                .unwrap()
+                .timestamp_millis() as u64,
+        );
+        let mut params = crate::api_type::GetMetricsParameters {
+            granularity: crate::api_type::MetricsGranularity::hourly,
+            dateFromMillis: date_millis.clone(),
+            dateToMillis: date_millis,
+        };
+
+        let hourly_data = match calculator::get_canister_metrics(&params, storage).unwrap() {
+            CanisterMetricsData::hourly(vector) => vector,
+            _ => panic!(),
+        };
+        let custom_metrics = hourly_data[0].customMetrics.as_ref().unwrap();
+        assert_eq!(custom_metrics.len(), 2);
+        assert_eq!(custom_metrics[0].name, "orders");
+        assert_eq!(custom_metrics[0].kind, CustomMetricKind::counter);
+        assert_eq!(custom_metrics[0].values[9 * 12], 5);
+        assert_eq!(custom_metrics[0].values[13 * 12], 1);
+        assert_eq!(custom_metrics[1].values[9 * 12], 30);
+        let method_calls = hourly_data[0].methodCalls.as_ref().unwrap();
+        assert_eq!(method_calls[0].name, "doThis");
+        assert_eq!(method_calls[0].updateCalls[9 * 12], 2);
+        assert_eq!(method_calls[0].updateCalls[13 * 12], 1);
 
-        collector::collect_canister_metrics(&mut storage, time_nanos, false, || {
-            let heap_memory_size = 1234000;
-            let memory_size = 1345000;
-            let cycles = 18787;
-            collector::CanisterInfo {
-                heap_memory_size,
-                memory_size,
-                cycles,
+        params.granularity = crate::api_type::MetricsGranularity::daily;
+        let daily_data = match calculator::get_canister_metrics(&params, storage).unwrap() {
+            CanisterMetricsData::daily(vector) => vector,
+            _ => panic!(),
+        };
+        let method_calls = daily_data[0].methodCalls.as_ref().unwrap();
+        assert_eq!(method_calls[0].updateCalls, 3);
+        let custom_metrics = daily_data[0].customMetrics.as_ref().unwrap();
+        assert_eq!(custom_metrics[0].name, "orders");
+        assert!(matches!(
+            custom_metrics[0].value,
+            DailyCustomMetricValue::counter(6)
+        ));
+        match &custom_metrics[1].value {
+            DailyCustomMetricValue::gauge(entity) => {
+                assert_eq!(entity.avg, 25);
+                assert_eq!(entity.max, 30);
+                assert_eq!(entity.min, 20);
             }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_call_instructions() {
+        super::set_max_tracked_method_count(2).unwrap();
+        for instructions in [1000, 3000] {
+            let _guard = super::begin_call("transfer");
+            super::ic_util::advance_fake_instruction_counter(instructions);
+        }
+
+        let now_millis = Nat::from(Utc::now().timestamp_millis() as u64);
+        let params = crate::api_type::GetMetricsParameters {
+            granularity: crate::api_type::MetricsGranularity::daily,
+            dateFromMillis: now_millis.clone(),
+            dateToMillis: now_millis,
+        };
+        let metrics = super::get_metrics(&params).unwrap();
+        let daily_data = match metrics.data {
+            CanisterMetricsData::daily(vector) => vector,
+            _ => panic!(),
+        };
+        let call_instructions = daily_data[0].callInstructions.as_ref().unwrap();
+        assert_eq!(call_instructions[0].name, "transfer");
+        assert_eq!(call_instructions[0].calls, 2);
+        assert_eq!(call_instructions[0].totalInstructions, 4000);
+        assert_eq!(call_instructions[0].maxInstructions, 3000);
+        assert_eq!(call_instructions[0].meanInstructions, 2000);
+    }
+
+    #[cfg(feature = "timers")]
+    #[test]
+    fn test_collecting_timer() {
+        use std::time::Duration;
+        super::set_sampling_interval(60).unwrap();
+
+        super::start_collecting_timer();
+        assert!(super::is_collecting_timer_started());
+        assert_eq!(
+            super::ic_util::get_fake_timer_interval(),
+            Some(Duration::from_secs(60))
+        );
+
+        super::ic_util::run_fake_timer();
+        let now = Utc::now();
+        super::instance::with(|canistergeek| {
+            let day_data = canistergeek
+                .monitor()
+                .day_data_supplier()
+                .get_day_data_info(&now.year(), &now.month(), &now.day())
+                .unwrap();
+            let cell = (now.num_seconds_from_midnight() as usize) / 60;
+            // timer collections are not update calls
+            assert_eq!(day_data.get_update_calls_data().len(), 24 * 60);
+            assert_eq!(day_data.get_update_calls_data()[cell], 0);
         });
 
+        // the started timer follows the sampling interval
+        super::set_sampling_interval(5 * 60).unwrap();
+        assert_eq!(
+            super::ic_util::get_fake_timer_interval(),
+            Some(Duration::from_secs(5 * 60))
+        );
+
+        // upgrade: timers are cleared, the interval is restored with the day data
+        let bytes = super::with_pre_upgrade_stable_data(|data| candid::encode_one(data)).unwrap();
+        super::scheduler::stop();
+        assert_eq!(super::ic_util::get_fake_timer_interval(), None);
+        super::post_upgrade_stable_data(candid::decode_one(&bytes).unwrap()).unwrap();
+        assert!(super::is_collecting_timer_started());
+        assert_eq!(
+            super::ic_util::get_fake_timer_interval(),
+            Some(Duration::from_secs(5 * 60))
+        );
+
+        super::stop_collecting_timer();
+        assert!(!super::is_collecting_timer_started());
+        assert_eq!(super::ic_util::get_fake_timer_interval(), None);
+    }
+
+    #[test]
+    fn test_move_to_stable_storage() {
+        let mut storage = Storage::default();
+        let time_nanos = Utc
+            .with_ymd_and_hms(2022, 1, 28, 9, 0, 0)
+            .unwrap()
+            .timestamp_nanos_opt()
+            .unwrap() as u64;
+        collector::collect_canister_metrics(
+            &mut storage,
+            time_nanos,
+            true,
+            288,
+            &Default::default(),
+            || collector::CanisterInfo {
+                heap_memory_size: 1,
+                memory_size: 2,
+                cycles: 3,
+            },
+        );
+
+        let mut stable_storage =
+            StableStorage::init(Box::new(VecMemory::default()), 288, 0, 0).unwrap();
+        storage.move_to(&mut stable_storage);
+        assert!(storage.get_day_data_table().is_empty());
+
+        let day_data = stable_storage.get_day_data_info(&2022, &1, &28).unwrap();
+        assert_eq!(day_data.get_canister_cycles_data()[9 * 3600 / 300], 3);
+    }
+
+    #[test]
+    fn test_sampling_interval() {
+        let mut storage = Storage::default();
+        storage.store_day_data(&2022, &1, &27, DayData::new(&288));
+        storage.store_day_data(&2022, &1, &28, DayData::new(&96));
+        // settings are not persisted over upgrades
+        let mut monitor = super::Monitor::default();
+        monitor
+            .post_upgrade_stable_data((1, storage.get_day_data_table().clone(), None))
+            .unwrap();
+        assert_eq!(monitor.get_sampling_interval(), 15 * 60);
+
+        assert!(monitor.set_sampling_interval(7 * 60).is_err());
+        assert!(monitor.set_sampling_interval(60).is_ok());
+        assert_eq!(monitor.get_sampling_interval(), 60);
+
+        super::post_upgrade_stable_data((1, storage.get_day_data_table().clone(), None)).unwrap();
+        assert_eq!(super::get_sampling_interval(), 15 * 60);
+    }
+
+    #[test]
+    fn test_retention_policy() {
+        let mut storage = Storage::default();
+        let collect = |storage: &mut Storage, day: u32, retention_policy: &RetentionPolicy| {
+            let time_nanos = Utc
+                .with_ymd_and_hms(2022, 1, day, 12, 0, 0)
+                .unwrap()
+                .timestamp_nanos_opt()
+                .unwrap() as u64;
+            collector::collect_canister_metrics(
+                storage,
+                time_nanos,
+                false,
+                288,
+                retention_policy,
+                || collector::CanisterInfo {
+                    heap_memory_size: 1,
+                    memory_size: 2,
+                    cycles: 3,
+                },
+            )
+        };
+
+        let unlimited = RetentionPolicy::default();
+        for day in 1..=5 {
+            assert_eq!(collect(&mut storage, day, &unlimited), 0);
+        }
+        let retention_policy = RetentionPolicy {
+            max_days: Some(3),
+            max_bytes: None,
+        };
+        assert_eq!(collect(&mut storage, 6, &retention_policy), 3);
+        assert!(storage.get_day_data_info(&2022, &1, &3).is_none());
+        assert!(storage.get_day_data_info(&2022, &1, &4).is_some());
+
+        let retention_policy = RetentionPolicy {
+            max_days: None,
+            max_bytes: Some(2 * 4 * 288 * 8),
+        };
+        assert_eq!(collect(&mut storage, 6, &retention_policy), 1);
+        assert!(storage.get_day_data_info(&2022, &1, &4).is_none());
+        assert!(storage.get_day_data_info(&2022, &1, &5).is_some());
+
+        // the current day is kept
+        let retention_policy = RetentionPolicy {
+            max_days: Some(0),
+            max_bytes: Some(0),
+        };
+        assert_eq!(collect(&mut storage, 6, &retention_policy), 1);
+        assert!(storage.get_day_data_info(&2022, &1, &6).is_some());
+    }
+
+    fn check_metrics<S: DayDataStorage + DayDataInfoSupplier>(storage: &mut S) {
+        let time_nanos = Utc
+            .with_ymd_and_hms(2022, 1, 28, 13, 0, 0)
+            .unwrap()
+            .timestamp_nanos_opt()
+            .unwrap() as u64;
+
+        collector::collect_canister_metrics(
+            storage,
+            time_nanos,
+            false,
+            288,
+            &Default::default(),
+            || {
+                let heap_memory_size = 234000;
+                let memory_size = 345000;
+                let cycles = 8787;
+                collector::CanisterInfo {
+                    heap_memory_size,
+                    memory_size,
+                    cycles,
+                }
+            },
+        );
+
+        let time_nanos = Utc
+            .with_ymd_and_hms(2022, 1, 28, 9, 0, 0)
+            .unwrap()
+            .timestamp_nanos_opt()
+            .unwrap() as u64;
+
+        collector::collect_canister_metrics(
+            storage,
+            time_nanos,
+            false,
+            288,
+            &Default::default(),
+            || {
+                let heap_memory_size = 1234000;
+                let memory_size = 1345000;
+                let cycles = 18787;
+                collector::CanisterInfo {
+                    heap_memory_size,
+                    memory_size,
+                    cycles,
+                }
+            },
+        );
+
         let params = crate::api_type::GetMetricsParameters {
             granularity: crate::api_type::MetricsGranularity::hourly,
             dateFromMillis: Nat::from(
-                Utc.with_ymd_and_hms(2022, 01, 28, 11, 11, 11).unwrap().timestamp_millis() as u64
+                Utc.with_ymd_and_hms(2022, 1, 28, 11, 11, 11)
+                    .unwrap()
+                    .timestamp_millis() as u64,
             ),
             dateToMillis: Nat::from(
-                Utc.with_ymd_and_hms(2022, 01, 28, 11, 11, 11).unwrap().timestamp_millis() as u64
+                Utc.with_ymd_and_hms(2022, 1, 28, 11, 11, 11)
+                    .unwrap()
+                    .timestamp_millis() as u64,
             ),
         };
 
-        let result = calculator::get_canister_metrics(&params, &storage);
+        let result = calculator::get_canister_metrics(&params, storage);
         dbg!(&result);
 
         let vector = match result.unwrap() {
@@ -159,7 +1004,7 @@ mod tests {
         };
 
         assert_eq!(vector.len(), 1);
-        let hourly_data = vector.get(0).unwrap();
+        let hourly_data = vector.first().unwrap();
         assert_eq!(hourly_data.timeMillis, candid::Int::from(1643328000000_i64));
 
         let cell_count = 288;
@@ -212,4 +1057,97 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_metrics_errors() {
+        let storage = Storage::default();
+        let millis = |year: i32, month: u32, day: u32| {
+            Nat::from(
+                Utc.with_ymd_and_hms(year, month, day, 12, 0, 0)
+                    .unwrap()
+                    .timestamp_millis() as u64,
+            )
+        };
+        let params = |granularity, from, to| crate::api_type::GetMetricsParameters {
+            granularity,
+            dateFromMillis: from,
+            dateToMillis: to,
+        };
+
+        let result = calculator::get_canister_metrics(
+            &params(
+                crate::api_type::MetricsGranularity::hourly,
+                millis(2022, 1, 28),
+                millis(2022, 1, 27),
+            ),
+            &storage,
+        );
+        assert_eq!(result.unwrap_err(), InformationError::invalidDateRange);
+
+        let result = calculator::get_canister_metrics(
+            &params(
+                crate::api_type::MetricsGranularity::hourly,
+                millis(2022, 1, 1),
+                Nat::from(u128::MAX),
+            ),
+            &storage,
+        );
+        assert_eq!(result.unwrap_err(), InformationError::invalidDateRange);
+
+        let result = calculator::get_canister_metrics(
+            &params(
+                crate::api_type::MetricsGranularity::hourly,
+                millis(2022, 1, 20),
+                millis(2022, 1, 28),
+            ),
+            &storage,
+        );
+        assert!(result.is_ok());
+        let result = calculator::get_canister_metrics(
+            &params(
+                crate::api_type::MetricsGranularity::hourly,
+                millis(2022, 1, 19),
+                millis(2022, 1, 28),
+            ),
+            &storage,
+        );
+        assert_eq!(
+            result.unwrap_err(),
+            InformationError::tooManyDays { maxDays: 9 }
+        );
+    }
+
+    #[test]
+    fn test_upgrade_data_v1() {
+        use candid::Decode;
+        let bytes = include_bytes!("../migration/fixtures/upgrade_data_v1.bin");
+        let (monitor_data, _) = Decode!(
+            bytes,
+            super::PostUpgradeStableData,
+            crate::logger::PostUpgradeStableData
+        )
+        .unwrap();
+        let (version, day_data_table, timer_interval) = monitor_data;
+        // the timer interval is absent in the data saved by version 1
+        assert!(timer_interval.is_none());
+        let (day_data_table, report) =
+            crate::migration::migrate("monitor", version, day_data_table, &super::MIGRATIONS)
+                .unwrap();
+        assert_eq!(report.from_version, 1);
+        assert_eq!(report.to_version, super::VERSION);
+
+        let storage = Storage::init(day_data_table);
+        let day = storage.get_day_data_info(&2022, &1, &27).unwrap();
+        assert_eq!(day.get_update_calls_data().len(), 288);
+        assert_eq!(day.get_update_calls_data()[108], 2);
+        assert_eq!(day.get_canister_cycles_data()[108], 1000);
+        assert_eq!(day.get_canister_heap_memory_size_data()[108], 65536 * 541);
+        assert_eq!(day.get_canister_memory_size_data()[108], 2 * 65536 * 541);
+        assert_eq!(day.get_canister_cycles_data()[287], 900);
+        assert!(day.get_custom_metrics_data().is_empty());
+        let day = storage.get_day_data_info(&2022, &1, &28).unwrap();
+        assert_eq!(day.get_canister_cycles_data()[0], 800);
+        assert_eq!(day.get_canister_cycles_data()[156], 5000);
+        assert_eq!(day.get_update_calls_data().iter().sum::<u64>(), 2);
+    }
 }
@@ -0,0 +1,23 @@
+use super::super::ic_util;
+use std::cell::Cell;
+use std::time::Duration;
+
+thread_local! {
+    static TIMER: Cell<Option<ic_cdk_timers::TimerId>> = const { Cell::new(None) };
+}
+
+/// Replaces the interval timer, timers are not persisted, so it is started again after upgrade.
+pub(crate) fn start(interval_in_seconds: u32) {
+    stop();
+    let timer_id = ic_util::set_timer_interval(
+        Duration::from_secs(interval_in_seconds as u64),
+        super::collect_canister_info_int,
+    );
+    TIMER.with(|timer| timer.set(Some(timer_id)));
+}
+
+pub(crate) fn stop() {
+    if let Some(timer_id) = TIMER.with(|timer| timer.take()) {
+        ic_util::clear_timer(timer_id);
+    }
+}
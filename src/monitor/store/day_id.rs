@@ -16,6 +16,14 @@ pub fn to_day_id(year: &i32, month: &u32, day: &u32) -> Result<DayId, &'static s
     Ok(day_id)
 }
 
+pub fn from_day_id(day_id: DayId) -> (i32, u32, u32) {
+    (
+        MINIMAL_VALID_YEAR + (((day_id >> 12) & 0x000000FF) as i32),
+        (day_id >> 8) & 0x0000000F,
+        day_id & 0x000000FF,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -46,5 +54,6 @@ mod tests {
         assert_eq!(from_day_id_to_year(day_id), 2021);
         assert_eq!(from_day_id_to_month(day_id), 12);
         assert_eq!(from_day_id_to_day(day_id), 31);
+        assert_eq!(from_day_id(day_id), (2021, 12, 31));
     }
 }
@@ -1,7 +1,9 @@
 mod day_id;
+pub mod stable;
 
 use super::data_type::{DayData, DayDataInfo, DayDataInfoSupplier, DayDataStorage};
 use day_id::DayId;
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 
 pub type DayDataTable = BTreeMap<DayId, DayData>;
@@ -19,15 +21,23 @@ impl Storage {
     pub fn get_day_data_table(&self) -> &DayDataTable {
         &self.day_data_table
     }
+
+    /// Moves all days to another storage, days already present there are kept.
+    pub fn move_to(&mut self, storage: &mut dyn DayDataStorage) {
+        let day_data_table = std::mem::take(&mut self.day_data_table);
+        for (day_id, day_data) in day_data_table {
+            let (year, month, day) = day_id::from_day_id(day_id);
+            if storage.get_day_data(&year, &month, &day).is_none() {
+                storage.store_day_data(&year, &month, &day, day_data);
+            }
+        }
+    }
 }
 
 impl DayDataInfoSupplier for Storage {
-    fn get_day_data_info(&self, year: &i32, month: &u32, day: &u32) -> Option<&dyn DayDataInfo> {
+    fn get_day_data_info(&self, year: &i32, month: &u32, day: &u32) -> Option<Cow<'_, DayData>> {
         match day_id::to_day_id(year, month, day) {
-            Ok(day_id) => match self.day_data_table.get(&day_id) {
-                None => None,
-                Some(day_data) => Some(day_data),
-            },
+            Ok(day_id) => self.day_data_table.get(&day_id).map(Cow::Borrowed),
             _ => None,
         }
     }
@@ -43,4 +53,78 @@ impl DayDataStorage for Storage {
         let day_id = day_id::to_day_id(year, month, day).unwrap();
         self.day_data_table.insert(day_id, day_data);
     }
+
+    fn remove_days_before(&mut self, year: &i32, month: &u32, day: &u32) -> usize {
+        match day_id::to_day_id(year, month, day) {
+            Ok(day_id) => {
+                let kept = self.day_data_table.split_off(&day_id);
+                let removed = std::mem::replace(&mut self.day_data_table, kept);
+                removed.len()
+            }
+            Err(_) => 0,
+        }
+    }
+
+    fn remove_first_day(&mut self) -> bool {
+        self.day_data_table.len() > 1 && self.day_data_table.pop_first().is_some()
+    }
+
+    fn get_size_in_bytes(&self) -> u64 {
+        self.day_data_table
+            .values()
+            .map(get_day_data_size_in_bytes)
+            .sum()
+    }
+
+    fn get_last_day_cell_count(&self) -> Option<usize> {
+        self.day_data_table
+            .last_key_value()
+            .map(|(_, day_data)| day_data.get_update_calls_data().len())
+    }
+}
+
+fn get_day_data_size_in_bytes(day_data: &DayData) -> u64 {
+    let values_count = day_data.get_update_calls_data().len()
+        + day_data.get_canister_heap_memory_size_data().len()
+        + day_data.get_canister_memory_size_data().len()
+        + day_data.get_canister_cycles_data().len()
+        + day_data
+            .get_custom_metrics_data()
+            .iter()
+            .map(|metric| metric.values.len())
+            .sum::<usize>()
+        + day_data
+            .get_method_calls_data()
+            .iter()
+            .map(|method| method.values.len())
+            .sum::<usize>()
+        + day_data
+            .get_call_instructions_data()
+            .iter()
+            .map(|method| method.calls.len() + method.total.len() + method.max.len())
+            .sum::<usize>();
+    (values_count * std::mem::size_of::<u64>()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::data_type::{DayData, DayDataInfoSupplier, DayDataStorage};
+    use super::Storage;
+
+    #[test]
+    fn test_remove_days() {
+        let mut storage = Storage::default();
+        storage.store_day_data(&2021, &12, &30, DayData::new(&4));
+        storage.store_day_data(&2021, &12, &31, DayData::new(&4));
+        storage.store_day_data(&2022, &1, &2, DayData::new(&4));
+        assert_eq!(storage.get_size_in_bytes(), 3 * 4 * 4 * 8);
+
+        assert_eq!(storage.remove_days_before(&2021, &12, &30), 0);
+        assert_eq!(storage.remove_days_before(&2022, &1, &1), 2);
+        assert!(storage.get_day_data_info(&2021, &12, &31).is_none());
+        assert!(storage.get_day_data_info(&2022, &1, &2).is_some());
+
+        assert!(!storage.remove_first_day());
+        assert!(storage.get_day_data_info(&2022, &1, &2).is_some());
+    }
 }
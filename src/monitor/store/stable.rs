@@ -0,0 +1,715 @@
+use super::super::super::ic_util;
+use super::super::super::ic_util::memory::{ensure_memory_size, Memory};
+use super::super::data_type::{
+    CallInstructionsDayData, CustomMetricDayData, DayData, DayDataInfo, DayDataInfoSupplier,
+    DayDataStorage, MethodCallsDayData,
+};
+use candid::{Decode, Encode};
+use chrono::{Datelike, NaiveDate};
+use std::borrow::Cow;
+
+/// Day data storage which keeps fixed-size day records directly in the (stable) memory,
+/// so neither the heap nor the upgrade cost grows with the history length.
+///
+/// Memory layout: header followed by the day slots.
+/// The slot of a day is addressed by the number of days passed since the first stored day,
+/// slots of the removed days are reused after compaction.
+/// Each slot is `[cell count: u32][named series length: u32]` followed by update calls, heap memory size,
+/// memory size and cycles series of `max_cell_count` values each and the region of candid encoded
+/// custom metrics, method calls and call instructions sized for `max_custom_metric_count` metrics
+/// and `max_method_count` methods; zero cell count marks an empty slot.
+///
+/// Days before the first stored one (e.g. after older days have been removed) can not be addressed,
+/// such days are not stored.
+pub struct StableStorage {
+    memory: Box<dyn Memory>,
+    header: Header,
+    // data of the last accessed day, written back in `day_data_changed`
+    current: Option<(i64, DayData)>,
+    // named series of the last written day which have not fit its slot
+    dropped_series_count: usize,
+}
+
+const MAGIC: &[u8; 4] = b"CGMN";
+const LAYOUT_VERSION: u8 = 1;
+const HEADER_SIZE: u64 = 64;
+const SLOT_HEADER_SIZE: u64 = 8;
+const SERIES_COUNT: u64 = 4;
+const NO_BASE_DAY: i64 = i64::MIN;
+pub const MAX_SERIES_NAME_LENGTH: u64 = 64;
+// candid header and type table of the custom metrics and method calls
+const NAMED_SERIES_OVERHEAD: u64 = 256;
+// name length, kind and values length of a custom metric or method calls
+const NAMED_SERIES_ITEM_OVERHEAD: u64 = 16;
+
+#[derive(Clone, Copy)]
+struct Header {
+    max_cell_count: u64,
+    // number of the first stored day since the common era
+    base_day: i64,
+    // number of initialized slots
+    slot_count: u64,
+    // physical slot of the first stored day
+    first_slot: u64,
+    max_custom_metric_count: u64,
+    max_method_count: u64,
+    // storages created before instructions profiling have no space for it
+    max_profiled_method_count: u64,
+}
+
+impl Header {
+    fn to_bytes(self) -> [u8; HEADER_SIZE as usize] {
+        let mut bytes = [0_u8; HEADER_SIZE as usize];
+        bytes[0..4].copy_from_slice(MAGIC);
+        bytes[4] = LAYOUT_VERSION;
+        bytes[8..16].copy_from_slice(&self.max_cell_count.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.base_day.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.slot_count.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.first_slot.to_le_bytes());
+        bytes[40..48].copy_from_slice(&self.max_custom_metric_count.to_le_bytes());
+        bytes[48..56].copy_from_slice(&self.max_method_count.to_le_bytes());
+        bytes[56..64].copy_from_slice(&self.max_profiled_method_count.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; HEADER_SIZE as usize]) -> Result<Header, &'static str> {
+        if bytes[4] != LAYOUT_VERSION {
+            return Err("Unsupported metrics storage layout version");
+        }
+        Ok(Header {
+            max_cell_count: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            base_day: i64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+            slot_count: u64::from_le_bytes(bytes[24..32].try_into().unwrap()),
+            first_slot: u64::from_le_bytes(bytes[32..40].try_into().unwrap()),
+            max_custom_metric_count: u64::from_le_bytes(bytes[40..48].try_into().unwrap()),
+            max_method_count: u64::from_le_bytes(bytes[48..56].try_into().unwrap()),
+            max_profiled_method_count: u64::from_le_bytes(bytes[56..64].try_into().unwrap()),
+        })
+    }
+}
+
+impl StableStorage {
+    /// Opens the storage previously created in the memory, or creates a new one
+    /// with slots for `max_cell_count` intervals a day, `max_custom_metric_count` application metrics
+    /// and update calls and instructions of `max_method_count` methods.
+    pub fn init(
+        memory: Box<dyn Memory>,
+        max_cell_count: usize,
+        max_custom_metric_count: usize,
+        max_method_count: usize,
+    ) -> Result<Self, &'static str> {
+        let mut header = None;
+        if memory.size() > 0 {
+            let mut bytes = [0_u8; HEADER_SIZE as usize];
+            memory.read(0, &mut bytes);
+            if &bytes[0..4] == MAGIC {
+                header = Some(Header::from_bytes(&bytes)?);
+            }
+        }
+
+        let storage = match header {
+            Some(header) => Self {
+                memory,
+                header,
+                current: None,
+                dropped_series_count: 0,
+            },
+            None => {
+                if max_cell_count == 0 {
+                    return Err("Wrong cell count");
+                }
+                ensure_memory_size(memory.as_ref(), HEADER_SIZE)?;
+                let storage = Self {
+                    memory,
+                    header: Header {
+                        max_cell_count: max_cell_count as u64,
+                        base_day: NO_BASE_DAY,
+                        slot_count: 0,
+                        first_slot: 0,
+                        max_custom_metric_count: max_custom_metric_count as u64,
+                        max_method_count: max_method_count as u64,
+                        max_profiled_method_count: max_method_count as u64,
+                    },
+                    current: None,
+                    dropped_series_count: 0,
+                };
+                storage.save_header();
+                storage
+            }
+        };
+        Ok(storage)
+    }
+
+    /// Returns `true` if no day has been stored yet.
+    pub fn is_empty(&self) -> bool {
+        self.header.base_day == NO_BASE_DAY
+    }
+
+    /// Returns the maximal number of cells a day can have.
+    pub fn get_max_cell_count(&self) -> usize {
+        self.header.max_cell_count as usize
+    }
+
+    /// Returns the maximal number of application metrics a day can have.
+    pub fn get_max_custom_metric_count(&self) -> usize {
+        self.header.max_custom_metric_count as usize
+    }
+
+    /// Returns the maximal number of methods with tracked update calls a day can have.
+    pub fn get_max_method_count(&self) -> usize {
+        self.header.max_method_count as usize
+    }
+
+    /// Returns the maximal number of methods with profiled instructions a day can have.
+    pub fn get_max_profiled_method_count(&self) -> usize {
+        self.header.max_profiled_method_count as usize
+    }
+
+    /// Returns the number of custom metrics, method calls and profiled methods series
+    /// of the last written day, which have not fit the storage.
+    pub fn get_dropped_series_count(&self) -> usize {
+        self.dropped_series_count
+    }
+
+    fn save_header(&self) {
+        self.memory.write(0, &self.header.to_bytes());
+    }
+
+    fn series_size(&self) -> u64 {
+        SERIES_COUNT * self.header.max_cell_count * 8
+    }
+
+    fn named_series_capacity(&self) -> u64 {
+        // call instructions have calls, total and max series
+        let series_count = self.header.max_custom_metric_count
+            + self.header.max_method_count
+            + 3 * self.header.max_profiled_method_count;
+        let item_count = self.header.max_custom_metric_count
+            + self.header.max_method_count
+            + self.header.max_profiled_method_count;
+        if item_count == 0 {
+            return 0;
+        }
+        NAMED_SERIES_OVERHEAD
+            + item_count * (MAX_SERIES_NAME_LENGTH + NAMED_SERIES_ITEM_OVERHEAD)
+            + series_count * self.header.max_cell_count * 8
+    }
+
+    fn slot_size(&self) -> u64 {
+        SLOT_HEADER_SIZE + self.series_size() + self.named_series_capacity()
+    }
+
+    fn slot_offset(&self, slot: u64) -> u64 {
+        HEADER_SIZE + (self.header.first_slot + slot) * self.slot_size()
+    }
+
+    fn is_slot_empty(&self, slot: u64) -> bool {
+        let mut bytes = [0_u8; 4];
+        self.memory.read(self.slot_offset(slot), &mut bytes);
+        u32::from_le_bytes(bytes) == 0
+    }
+
+    /// Removes `count` first slots, moves the remaining ones to the memory start
+    /// once they can be copied without overlapping.
+    fn remove_first_slots(&mut self, count: u64) {
+        self.current = None;
+        if count >= self.header.slot_count {
+            self.header.base_day = NO_BASE_DAY;
+            self.header.slot_count = 0;
+            self.header.first_slot = 0;
+        } else {
+            self.header.base_day += count as i64;
+            self.header.slot_count -= count;
+            self.header.first_slot += count;
+            if self.header.first_slot >= self.header.slot_count {
+                let mut bytes = vec![0_u8; self.slot_size() as usize];
+                for slot in 0..self.header.slot_count {
+                    self.memory.read(self.slot_offset(slot), &mut bytes);
+                    self.memory
+                        .write(HEADER_SIZE + slot * self.slot_size(), &bytes);
+                }
+                self.header.first_slot = 0;
+            }
+        }
+        self.save_header();
+    }
+
+    fn to_day_number(year: &i32, month: &u32, day: &u32) -> Option<i64> {
+        NaiveDate::from_ymd_opt(*year, *month, *day).map(|date| date.num_days_from_ce() as i64)
+    }
+
+    fn get_slot(&self, day_number: i64) -> Option<u64> {
+        if self.is_empty() || day_number < self.header.base_day {
+            return None;
+        }
+        let slot = (day_number - self.header.base_day) as u64;
+        if slot < self.header.slot_count {
+            Some(slot)
+        } else {
+            None
+        }
+    }
+
+    fn read_day_data(&self, day_number: i64) -> Option<DayData> {
+        let slot = self.get_slot(day_number)?;
+        let mut bytes = vec![0_u8; self.slot_size() as usize];
+        self.memory.read(self.slot_offset(slot), &mut bytes);
+
+        let cell_count = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        if cell_count == 0 {
+            return None;
+        }
+
+        let max_cell_count = self.header.max_cell_count as usize;
+        let series = |index: usize| -> Vec<u64> {
+            let start = SLOT_HEADER_SIZE as usize + index * max_cell_count * 8;
+            bytes[start..start + cell_count * 8]
+                .chunks_exact(8)
+                .map(|value| u64::from_le_bytes(value.try_into().unwrap()))
+                .collect()
+        };
+        let mut day_data = DayData::init(series(0), series(1), series(2), series(3));
+
+        let named_series_length = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        if named_series_length > 0 {
+            let start = (SLOT_HEADER_SIZE + self.series_size()) as usize;
+            match Decode!(
+                &bytes[start..start + named_series_length],
+                Vec<CustomMetricDayData>,
+                Option<Vec<MethodCallsDayData>>,
+                Option<Vec<CallInstructionsDayData>>
+            ) {
+                Ok((custom_metrics_data, method_calls_data, call_instructions_data)) => {
+                    day_data.set_custom_metrics_data(custom_metrics_data);
+                    day_data.set_method_calls_data(method_calls_data.unwrap_or_default());
+                    day_data.set_call_instructions_data(call_instructions_data.unwrap_or_default());
+                }
+                Err(_) => ic_util::debug_print(
+                    "Can not decode named series from the stable metrics storage",
+                ),
+            }
+        }
+        Some(day_data)
+    }
+
+    /// Returns false if the day data has not been stored.
+    fn write_day_data(&mut self, day_number: i64, day_data: &DayData) -> bool {
+        let cell_count = day_data.get_update_calls_data().len() as u64;
+        if cell_count == 0 || cell_count > self.header.max_cell_count {
+            ic_util::debug_print(std::format!(
+                "Can not store day data with {} cells in the stable metrics storage",
+                cell_count
+            ));
+            return false;
+        }
+
+        if self.is_empty() {
+            self.header.base_day = day_number;
+        } else if day_number < self.header.base_day {
+            ic_util::debug_print(
+                "Can not store day data before the first day of the stable metrics storage",
+            );
+            return false;
+        }
+
+        let slot = (day_number - self.header.base_day) as u64;
+        if slot >= self.header.slot_count {
+            if ensure_memory_size(self.memory.as_ref(), self.slot_offset(slot + 1)).is_err() {
+                ic_util::debug_print("Can not grow the stable metrics storage memory");
+                return false;
+            }
+            // mark skipped days as empty
+            for empty_slot in self.header.slot_count..slot {
+                self.memory.write(
+                    self.slot_offset(empty_slot),
+                    &[0_u8; SLOT_HEADER_SIZE as usize],
+                );
+            }
+            self.header.slot_count = slot + 1;
+            self.save_header();
+        }
+
+        let max_cell_count = self.header.max_cell_count as usize;
+        let mut bytes = vec![0_u8; self.slot_size() as usize];
+        bytes[0..4].copy_from_slice(&(cell_count as u32).to_le_bytes());
+        let series = [
+            day_data.get_update_calls_data(),
+            day_data.get_canister_heap_memory_size_data(),
+            day_data.get_canister_memory_size_data(),
+            day_data.get_canister_cycles_data(),
+        ];
+        for (index, values) in series.iter().enumerate() {
+            let start = SLOT_HEADER_SIZE as usize + index * max_cell_count * 8;
+            for (cell, value) in values.iter().enumerate() {
+                let offset = start + cell * 8;
+                bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        let mut dropped_series_count = 0;
+        let mut custom_metrics_data = day_data.get_custom_metrics_data();
+        if custom_metrics_data.len() as u64 > self.header.max_custom_metric_count {
+            ic_util::debug_print("Too many custom metrics for the stable metrics storage");
+            dropped_series_count +=
+                custom_metrics_data.len() - self.header.max_custom_metric_count as usize;
+            custom_metrics_data =
+                &custom_metrics_data[..self.header.max_custom_metric_count as usize];
+        }
+        let mut method_calls_data = day_data.get_method_calls_data();
+        if method_calls_data.len() as u64 > self.header.max_method_count {
+            ic_util::debug_print("Too many methods for the stable metrics storage");
+            dropped_series_count += method_calls_data.len() - self.header.max_method_count as usize;
+            method_calls_data = &method_calls_data[..self.header.max_method_count as usize];
+        }
+        let mut call_instructions_data = day_data.get_call_instructions_data();
+        if call_instructions_data.len() as u64 > self.header.max_profiled_method_count {
+            ic_util::debug_print("Too many profiled methods for the stable metrics storage");
+            dropped_series_count +=
+                call_instructions_data.len() - self.header.max_profiled_method_count as usize;
+            call_instructions_data =
+                &call_instructions_data[..self.header.max_profiled_method_count as usize];
+        }
+        if !custom_metrics_data.is_empty()
+            || !method_calls_data.is_empty()
+            || !call_instructions_data.is_empty()
+        {
+            match Encode!(
+                &custom_metrics_data,
+                &Some(method_calls_data),
+                &Some(call_instructions_data)
+            ) {
+                Ok(encoded) if encoded.len() as u64 <= self.named_series_capacity() => {
+                    let start = (SLOT_HEADER_SIZE + self.series_size()) as usize;
+                    bytes[4..8].copy_from_slice(&(encoded.len() as u32).to_le_bytes());
+                    bytes[start..start + encoded.len()].copy_from_slice(&encoded);
+                }
+                _ => {
+                    ic_util::debug_print(
+                        "Can not store named series in the stable metrics storage",
+                    );
+                    dropped_series_count += custom_metrics_data.len()
+                        + method_calls_data.len()
+                        + call_instructions_data.len();
+                }
+            }
+        }
+        self.memory.write(self.slot_offset(slot), &bytes);
+        self.dropped_series_count = dropped_series_count;
+        true
+    }
+}
+
+impl DayDataInfoSupplier for StableStorage {
+    fn get_day_data_info(&self, year: &i32, month: &u32, day: &u32) -> Option<Cow<'_, DayData>> {
+        let day_number = Self::to_day_number(year, month, day)?;
+        self.read_day_data(day_number).map(Cow::Owned)
+    }
+}
+
+impl DayDataStorage for StableStorage {
+    fn get_day_data(&mut self, year: &i32, month: &u32, day: &u32) -> Option<&mut DayData> {
+        let day_number = Self::to_day_number(year, month, day)?;
+        let cached = matches!(&self.current, Some((number, _)) if *number == day_number);
+        if !cached {
+            self.current = self
+                .read_day_data(day_number)
+                .map(|day_data| (day_number, day_data));
+        }
+        self.current.as_mut().map(|(_, day_data)| day_data)
+    }
+
+    fn store_day_data(&mut self, year: &i32, month: &u32, day: &u32, day_data: DayData) {
+        if let Some(day_number) = Self::to_day_number(year, month, day) {
+            // data of a day which has not been stored is not cached either
+            if self.write_day_data(day_number, &day_data) {
+                self.current = Some((day_number, day_data));
+            }
+        }
+    }
+
+    fn remove_days_before(&mut self, year: &i32, month: &u32, day: &u32) -> usize {
+        let day_number = match Self::to_day_number(year, month, day) {
+            Some(day_number) => day_number,
+            None => return 0,
+        };
+        if self.is_empty() || day_number <= self.header.base_day {
+            return 0;
+        }
+        let count = ((day_number - self.header.base_day) as u64).min(self.header.slot_count);
+        let removed = (0..count).filter(|slot| !self.is_slot_empty(*slot)).count();
+        self.remove_first_slots(count);
+        removed
+    }
+
+    fn remove_first_day(&mut self) -> bool {
+        // skip empty slots of the days without data
+        match (1..self.header.slot_count).find(|slot| !self.is_slot_empty(*slot)) {
+            Some(slot) => {
+                self.remove_first_slots(slot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn get_size_in_bytes(&self) -> u64 {
+        self.header.slot_count * self.slot_size()
+    }
+
+    fn get_last_day_cell_count(&self) -> Option<usize> {
+        if self.header.slot_count == 0 {
+            return None;
+        }
+        let mut bytes = [0_u8; 4];
+        self.memory
+            .read(self.slot_offset(self.header.slot_count - 1), &mut bytes);
+        Some(u32::from_le_bytes(bytes) as usize)
+    }
+
+    fn day_data_changed(&mut self, year: &i32, month: &u32, day: &u32) {
+        if let Some((day_number, day_data)) = self.current.take() {
+            if Self::to_day_number(year, month, day) == Some(day_number) {
+                self.write_day_data(day_number, &day_data);
+            }
+            self.current = Some((day_number, day_data));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::super::api_type::CustomMetricKind;
+    use super::super::super::super::ic_util::memory::VecMemory;
+    use super::super::super::data_type::{
+        DayData, DayDataInfo, DayDataInfoSupplier, DayDataStorage,
+    };
+    use super::StableStorage;
+
+    #[test]
+    fn test_store_and_reopen() {
+        let memory = VecMemory::default();
+        let mut storage = StableStorage::init(Box::new(memory.clone()), 4, 0, 0).unwrap();
+        assert!(storage.is_empty());
+        assert!(storage.get_day_data(&2022, &1, &28).is_none());
+
+        let mut day_data = DayData::new(&4);
+        day_data.store(&1, 3, 100, 200, 300);
+        storage.store_day_data(&2022, &1, &28, day_data);
+
+        let day_data = storage.get_day_data(&2022, &1, &28).unwrap();
+        day_data.increment_update_calls(&1);
+        day_data.store(&3, 1, 101, 201, 301);
+        storage.day_data_changed(&2022, &1, &28);
+
+        let mut day_data = DayData::new(&4);
+        day_data.store(&0, 7, 1, 2, 3);
+        storage.store_day_data(&2022, &2, &2, day_data);
+
+        // day before the first stored one is not addressable
+        storage.store_day_data(&2022, &1, &27, DayData::new(&4));
+        assert!(storage.get_day_data_info(&2022, &1, &27).is_none());
+
+        let storage = StableStorage::init(Box::new(memory), 288, 0, 0).unwrap();
+        assert!(!storage.is_empty());
+        assert_eq!(storage.get_max_cell_count(), 4);
+        assert_eq!(storage.get_last_day_cell_count(), Some(4));
+        assert!(storage.get_day_data_info(&2022, &1, &29).is_none());
+        assert!(storage.get_day_data_info(&2022, &2, &3).is_none());
+
+        let day_data = storage.get_day_data_info(&2022, &1, &28).unwrap();
+        assert_eq!(day_data.get_update_calls_data(), &vec![0, 4, 0, 1]);
+        assert_eq!(
+            day_data.get_canister_heap_memory_size_data(),
+            &vec![0, 100, 0, 101]
+        );
+        assert_eq!(
+            day_data.get_canister_memory_size_data(),
+            &vec![0, 200, 0, 201]
+        );
+        assert_eq!(day_data.get_canister_cycles_data(), &vec![0, 300, 0, 301]);
+
+        let day_data = storage.get_day_data_info(&2022, &2, &2).unwrap();
+        assert_eq!(day_data.get_update_calls_data(), &vec![7, 0, 0, 0]);
+        assert_eq!(day_data.get_canister_cycles_data(), &vec![3, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_remove_days() {
+        let memory = VecMemory::default();
+        let mut storage = StableStorage::init(Box::new(memory.clone()), 4, 0, 0).unwrap();
+        for day in [1, 2, 4, 5, 6] {
+            let mut day_data = DayData::new(&4);
+            day_data.store(&0, day as u64, 0, 0, 0);
+            storage.store_day_data(&2022, &3, &day, day_data);
+        }
+        let slot_size = 8 + 4 * 4 * 8;
+        assert_eq!(storage.get_size_in_bytes(), 6 * slot_size);
+
+        assert_eq!(storage.remove_days_before(&2022, &3, &1), 0);
+        assert_eq!(storage.remove_days_before(&2022, &3, &2), 1);
+        assert!(storage.get_day_data_info(&2022, &3, &1).is_none());
+        assert_eq!(storage.get_size_in_bytes(), 5 * slot_size);
+
+        // empty slot of the 3rd day is removed together with the 2nd day
+        assert!(storage.remove_first_day());
+        assert_eq!(storage.get_size_in_bytes(), 3 * slot_size);
+        assert_eq!(
+            storage
+                .get_day_data_info(&2022, &3, &4)
+                .unwrap()
+                .get_update_calls_data(),
+            &vec![4, 0, 0, 0]
+        );
+
+        // the remaining slots are compacted
+        assert!(storage.remove_first_day());
+        let mut day_data = DayData::new(&4);
+        day_data.store(&1, 7, 0, 0, 0);
+        storage.store_day_data(&2022, &3, &7, day_data);
+        let storage = StableStorage::init(Box::new(memory), 4, 0, 0).unwrap();
+        assert!(storage.get_day_data_info(&2022, &3, &4).is_none());
+        assert_eq!(
+            storage
+                .get_day_data_info(&2022, &3, &5)
+                .unwrap()
+                .get_update_calls_data(),
+            &vec![5, 0, 0, 0]
+        );
+        assert_eq!(
+            storage
+                .get_day_data_info(&2022, &3, &6)
+                .unwrap()
+                .get_update_calls_data(),
+            &vec![6, 0, 0, 0]
+        );
+        assert_eq!(
+            storage
+                .get_day_data_info(&2022, &3, &7)
+                .unwrap()
+                .get_update_calls_data(),
+            &vec![0, 7, 0, 0]
+        );
+
+        let mut storage = storage;
+        assert!(storage.remove_first_day());
+        assert!(storage.remove_first_day());
+        assert!(!storage.remove_first_day());
+        assert_eq!(storage.remove_days_before(&2022, &4, &1), 1);
+        assert!(storage.is_empty());
+        assert_eq!(storage.get_size_in_bytes(), 0);
+    }
+
+    #[test]
+    fn test_day_before_first_day() {
+        let mut storage = StableStorage::init(Box::new(VecMemory::default()), 4, 0, 0).unwrap();
+        storage.store_day_data(&2022, &3, &2, DayData::new(&4));
+        storage.store_day_data(&2022, &3, &3, DayData::new(&4));
+        assert_eq!(storage.remove_days_before(&2022, &3, &3), 1);
+
+        // days before the first stored one are not stored
+        storage.store_day_data(&2022, &3, &2, DayData::new(&4));
+        assert!(storage.get_day_data(&2022, &3, &2).is_none());
+        assert!(storage.get_day_data_info(&2022, &3, &2).is_none());
+        storage.store_day_data(&2022, &3, &1, DayData::new(&4));
+        assert!(storage.get_day_data(&2022, &3, &1).is_none());
+        assert!(storage.get_day_data(&2022, &3, &3).is_some());
+    }
+
+    #[test]
+    fn test_custom_metrics() {
+        let memory = VecMemory::default();
+        let mut storage = StableStorage::init(Box::new(memory.clone()), 4, 2, 0).unwrap();
+        assert_eq!(storage.get_max_custom_metric_count(), 2);
+
+        let mut day_data = DayData::new(&4);
+        day_data.store_custom_metric(&1, "orders", CustomMetricKind::counter, 3);
+        day_data.store_custom_metric(&2, "queue", CustomMetricKind::gauge, 9);
+        storage.store_day_data(&2022, &1, &28, day_data);
+
+        assert_eq!(storage.get_dropped_series_count(), 0);
+
+        // metrics over the capacity are dropped and counted
+        let mut day_data = DayData::new(&4);
+        for name in ["a", "b", "c"] {
+            day_data.store_custom_metric(&0, name, CustomMetricKind::counter, 1);
+        }
+        day_data.store(&0, 1, 0, 0, 0);
+        storage.store_day_data(&2022, &1, &29, day_data);
+        assert_eq!(storage.get_dropped_series_count(), 1);
+
+        let storage = StableStorage::init(Box::new(memory), 4, 0, 0).unwrap();
+        assert_eq!(storage.get_max_custom_metric_count(), 2);
+        let day_data = storage.get_day_data_info(&2022, &1, &28).unwrap();
+        let custom_metrics_data = day_data.get_custom_metrics_data();
+        assert_eq!(custom_metrics_data.len(), 2);
+        assert_eq!(custom_metrics_data[0].values, vec![0, 3, 0, 0]);
+        assert_eq!(custom_metrics_data[1].name, "queue");
+        assert_eq!(custom_metrics_data[1].values, vec![0, 0, 9, 0]);
+
+        let day_data = storage.get_day_data_info(&2022, &1, &29).unwrap();
+        assert_eq!(day_data.get_update_calls_data(), &vec![1, 0, 0, 0]);
+        let custom_metrics_data = day_data.get_custom_metrics_data();
+        assert_eq!(custom_metrics_data.len(), 2);
+        assert_eq!(custom_metrics_data[1].name, "b");
+        assert_eq!(custom_metrics_data[1].values, vec![1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_method_calls() {
+        let memory = VecMemory::default();
+        let mut storage = StableStorage::init(Box::new(memory.clone()), 4, 1, 2).unwrap();
+        assert_eq!(storage.get_max_method_count(), 2);
+
+        let mut day_data = DayData::new(&4);
+        day_data.store_custom_metric(&1, "orders", CustomMetricKind::counter, 3);
+        day_data.increment_method_calls(&0, "doThis", 3);
+        day_data.increment_method_calls(&3, "doThat", 3);
+        day_data.increment_method_calls(&3, "doThat", 3);
+        day_data.increment_method_calls(&2, "doSomethingElse", 3);
+        storage.store_day_data(&2022, &1, &28, day_data);
+        assert_eq!(storage.get_dropped_series_count(), 1);
+
+        let storage = StableStorage::init(Box::new(memory), 4, 0, 0).unwrap();
+        let day_data = storage.get_day_data_info(&2022, &1, &28).unwrap();
+        assert_eq!(
+            day_data.get_custom_metrics_data()[0].values,
+            vec![0, 3, 0, 0]
+        );
+        // methods over the capacity are not stored
+        let method_calls_data = day_data.get_method_calls_data();
+        assert_eq!(method_calls_data.len(), 2);
+        assert_eq!(method_calls_data[0].name, "doThis");
+        assert_eq!(method_calls_data[0].values, vec![1, 0, 0, 0]);
+        assert_eq!(method_calls_data[1].values, vec![0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn test_call_instructions() {
+        let memory = VecMemory::default();
+        let mut storage = StableStorage::init(Box::new(memory.clone()), 4, 0, 1).unwrap();
+        assert_eq!(storage.get_max_profiled_method_count(), 1);
+
+        let mut day_data = DayData::new(&4);
+        day_data.store_call_instructions(&1, "transfer", 1000, 2);
+        day_data.store_call_instructions(&1, "transfer", 3000, 2);
+        day_data.store_call_instructions(&1, "approve", 10, 2);
+        storage.store_day_data(&2022, &1, &28, day_data);
+
+        let storage = StableStorage::init(Box::new(memory), 4, 0, 0).unwrap();
+        let day_data = storage.get_day_data_info(&2022, &1, &28).unwrap();
+        let call_instructions_data = day_data.get_call_instructions_data();
+        assert_eq!(call_instructions_data.len(), 1);
+        assert_eq!(call_instructions_data[0].name, "transfer");
+        assert_eq!(call_instructions_data[0].calls, vec![0, 2, 0, 0]);
+        assert_eq!(call_instructions_data[0].total, vec![0, 4000, 0, 0]);
+        assert_eq!(call_instructions_data[0].max, vec![0, 3000, 0, 0]);
+    }
+
+    #[test]
+    fn test_too_many_cells() {
+        let mut storage = StableStorage::init(Box::new(VecMemory::default()), 4, 0, 0).unwrap();
+        storage.store_day_data(&2022, &1, &28, DayData::new(&5));
+        assert!(storage.is_empty());
+        assert!(storage.get_day_data_info(&2022, &1, &28).is_none());
+    }
+}